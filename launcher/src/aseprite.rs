@@ -0,0 +1,621 @@
+use crate::image_formats::{self, ByteReader};
+
+use cottontail::image::bitmap::*;
+
+use flate2::read::ZlibDecoder;
+
+use std::io::Read;
+
+const HEADER_MAGIC: u16 = 0xA5E0;
+const FRAME_MAGIC: u16 = 0xF1FA;
+
+const CHUNK_TYPE_OLD_PALETTE: u16 = 0x0004;
+const CHUNK_TYPE_LAYER: u16 = 0x2004;
+const CHUNK_TYPE_CEL: u16 = 0x2005;
+const CHUNK_TYPE_PALETTE: u16 = 0x2019;
+
+const LAYER_FLAG_VISIBLE: u16 = 1;
+const LAYER_FLAG_BACKGROUND: u16 = 8;
+const LAYER_FLAG_REFERENCE: u16 = 64;
+
+const CEL_TYPE_RAW_IMAGE: u16 = 0;
+const CEL_TYPE_LINKED: u16 = 1;
+const CEL_TYPE_COMPRESSED_IMAGE: u16 = 2;
+
+struct Layer {
+    /// NOTE: This is lowercase
+    name: String,
+    is_visible: bool,
+    is_background: bool,
+    opacity: u8,
+}
+
+#[derive(Clone)]
+struct Cel {
+    layer_index: usize,
+    pos_x: i32,
+    pos_y: i32,
+    opacity: u8,
+    z_index: i32,
+    bitmap: Bitmap,
+}
+
+/// Blends the source color over the destination color using straight alpha
+fn blend_normal(source: PixelRGBA, destination: PixelRGBA, opacity: u8) -> PixelRGBA {
+    let source_alpha = (source.a as f32 / 255.0) * (opacity as f32 / 255.0);
+    if source_alpha <= 0.0 {
+        return destination;
+    }
+    let destination_alpha = destination.a as f32 / 255.0;
+    let result_alpha = source_alpha + destination_alpha * (1.0 - source_alpha);
+    let blend_channel = |source_channel: u8, destination_channel: u8| {
+        let value = (source_channel as f32 * source_alpha
+            + destination_channel as f32 * destination_alpha * (1.0 - source_alpha))
+            / result_alpha;
+        value.round().min(255.0) as u8
+    };
+    PixelRGBA::new(
+        blend_channel(source.r, destination.r),
+        blend_channel(source.g, destination.g),
+        blend_channel(source.b, destination.b),
+        (255.0 * result_alpha).round() as u8,
+    )
+}
+
+fn read_cel_pixels(
+    pixel_data: &[u8],
+    width: i32,
+    height: i32,
+    color_depth: u16,
+    palette: &[PixelRGBA],
+    transparent_index: u8,
+    is_background_layer: bool,
+) -> Result<Bitmap, String> {
+    let bytes_per_pixel = color_depth as usize / 8;
+    let pixel_count = image_formats::pixel_count_checked(width, height)?;
+    if pixel_data.len() < pixel_count * bytes_per_pixel {
+        return Err(format!(
+            "Cel pixel data too short: expected {} bytes but got {}",
+            pixel_count * bytes_per_pixel,
+            pixel_data.len()
+        ));
+    }
+
+    let mut pixels = Vec::with_capacity(pixel_count);
+    for bytes in pixel_data.chunks_exact(bytes_per_pixel).take(pixel_count) {
+        let color = match color_depth {
+            32 => PixelRGBA::new(bytes[0], bytes[1], bytes[2], bytes[3]),
+            16 => PixelRGBA::new(bytes[0], bytes[0], bytes[0], bytes[1]),
+            _ => {
+                let index = bytes[0];
+                if index == transparent_index && !is_background_layer {
+                    PixelRGBA::transparent()
+                } else {
+                    *palette
+                        .get(index as usize)
+                        .ok_or_else(|| format!("Invalid palette index {}", index))?
+                }
+            }
+        };
+        pixels.push(color);
+    }
+    Ok(Bitmap::new_from_buffer(width as u32, height as u32, pixels))
+}
+
+/// A decoded frame of an Aseprite file
+pub struct AsepriteFrame {
+    /// All visible layers flattened into one image
+    pub image: Bitmap,
+    /// The requested auxiliary layers that exist in the file together with their lowercase names
+    pub auxiliary_layers: Vec<(String, Bitmap)>,
+}
+
+/// Decodes all frames of an Aseprite (`.ase`/`.aseprite`) file. The visible layers of every frame
+/// are flattened into a single image. Hidden layers, layers in hidden groups and reference layers
+/// are ignored.
+///
+/// Layers named like one of the `auxiliary_layer_names` (ignoring case) are not part of the image.
+/// They are returned separately with full opacity even if they are hidden.
+///
+/// NOTE: All layer blend modes are treated as normal blending and tilemap layers are ignored
+pub fn frames_create_from_aseprite_data(
+    data: &[u8],
+    auxiliary_layer_names: &[&str],
+) -> Result<Vec<AsepriteFrame>, String> {
+    let mut reader = ByteReader::new(data);
+
+    // Header
+    reader.skip(4)?;
+    if reader.u16()? != HEADER_MAGIC {
+        return Err("Missing Aseprite signature".to_owned());
+    }
+    let frame_count = reader.u16()? as usize;
+    let width = reader.u16()? as i32;
+    let height = reader.u16()? as i32;
+    let color_depth = reader.u16()?;
+    let header_flags = reader.u32()?;
+    reader.skip(2 + 4 + 4)?;
+    let transparent_index = reader.u8()?;
+    reader.seek(128)?;
+
+    if width == 0 || height == 0 {
+        return Err(format!("Invalid Aseprite dimensions {}x{}", width, height));
+    }
+    image_formats::pixel_count_checked(width, height)?;
+    if ![8, 16, 32].contains(&color_depth) {
+        return Err(format!("Unsupported Aseprite color depth {}", color_depth));
+    }
+    let layer_opacity_is_valid = header_flags & 1 != 0;
+
+    let mut layers: Vec<Layer> = Vec::new();
+    // NOTE: Contains the effective visibility of the last group layer on every nesting level
+    let mut group_visibility_per_level: Vec<bool> = Vec::new();
+    let mut palette: Vec<PixelRGBA> = vec![PixelRGBA::black(); 256];
+    let mut cels_per_frame: Vec<Vec<Cel>> = Vec::new();
+
+    for frame_index in 0..frame_count {
+        let frame_start = reader.pos();
+        let frame_size = reader.u32()? as usize;
+        if reader.u16()? != FRAME_MAGIC {
+            return Err(format!("Invalid header of frame {}", frame_index + 1));
+        }
+        let chunk_count_old = reader.u16()? as usize;
+        reader.skip(2 + 2)?;
+        let chunk_count_new = reader.u32()? as usize;
+        let chunk_count = if chunk_count_new == 0 {
+            chunk_count_old
+        } else {
+            chunk_count_new
+        };
+
+        let mut cels = Vec::new();
+        for _ in 0..chunk_count {
+            let chunk_start = reader.pos();
+            let chunk_size = reader.u32()? as usize;
+            let chunk_type = reader.u16()?;
+            if chunk_size < 6 {
+                return Err(format!("Invalid chunk size {}", chunk_size));
+            }
+
+            match chunk_type {
+                CHUNK_TYPE_LAYER => {
+                    let flags = reader.u16()?;
+                    reader.skip(2)?;
+                    let child_level = reader.u16()? as usize;
+                    reader.skip(2 + 2 + 2)?;
+                    let opacity = reader.u8()?;
+                    reader.skip(3)?;
+                    let name_length = reader.u16()? as usize;
+                    let name = String::from_utf8_lossy(reader.bytes(name_length)?).to_lowercase();
+
+                    let parent_is_visible = child_level == 0
+                        || group_visibility_per_level
+                            .get(child_level - 1)
+                            .copied()
+                            .unwrap_or(true);
+                    let is_visible = flags & LAYER_FLAG_VISIBLE != 0
+                        && flags & LAYER_FLAG_REFERENCE == 0
+                        && parent_is_visible;
+                    group_visibility_per_level.truncate(child_level);
+                    group_visibility_per_level.push(is_visible);
+
+                    layers.push(Layer {
+                        name,
+                        is_visible,
+                        is_background: flags & LAYER_FLAG_BACKGROUND != 0,
+                        opacity: if layer_opacity_is_valid { opacity } else { 255 },
+                    });
+                }
+                CHUNK_TYPE_CEL => {
+                    let layer_index = reader.u16()? as usize;
+                    let pos_x = reader.i16()? as i32;
+                    let pos_y = reader.i16()? as i32;
+                    let opacity = reader.u8()?;
+                    let cel_type = reader.u16()?;
+                    let z_index = reader.i16()? as i32;
+                    reader.skip(5)?;
+
+                    let is_background_layer = layers
+                        .get(layer_index)
+                        .map(|layer| layer.is_background)
+                        .unwrap_or(false);
+                    match cel_type {
+                        CEL_TYPE_RAW_IMAGE | CEL_TYPE_COMPRESSED_IMAGE => {
+                            let cel_width = reader.u16()? as i32;
+                            let cel_height = reader.u16()? as i32;
+                            let pixel_data_size = (chunk_start + chunk_size)
+                                .checked_sub(reader.pos())
+                                .ok_or_else(|| format!("Invalid cel chunk size {}", chunk_size))?;
+                            let pixel_data = reader.bytes(pixel_data_size)?;
+                            let pixel_data = if cel_type == CEL_TYPE_COMPRESSED_IMAGE {
+                                let mut decompressed = Vec::new();
+                                ZlibDecoder::new(pixel_data)
+                                    .read_to_end(&mut decompressed)
+                                    .map_err(|error| {
+                                        format!("Cannot decompress cel pixels: {}", error)
+                                    })?;
+                                decompressed
+                            } else {
+                                pixel_data.to_vec()
+                            };
+                            cels.push(Cel {
+                                layer_index,
+                                pos_x,
+                                pos_y,
+                                opacity,
+                                z_index,
+                                bitmap: read_cel_pixels(
+                                    &pixel_data,
+                                    cel_width,
+                                    cel_height,
+                                    color_depth,
+                                    &palette,
+                                    transparent_index,
+                                    is_background_layer,
+                                )?,
+                            });
+                        }
+                        CEL_TYPE_LINKED => {
+                            let linked_frame_index = reader.u16()? as usize;
+                            let linked_cel = cels_per_frame
+                                .get(linked_frame_index)
+                                .and_then(|frame_cels: &Vec<Cel>| {
+                                    frame_cels.iter().find(|cel| cel.layer_index == layer_index)
+                                })
+                                .ok_or_else(|| {
+                                    format!(
+                                        "Linked cel in frame {} refers to missing frame {}",
+                                        frame_index + 1,
+                                        linked_frame_index + 1
+                                    )
+                                })?;
+                            cels.push(Cel {
+                                pos_x,
+                                pos_y,
+                                opacity,
+                                z_index,
+                                ..linked_cel.clone()
+                            });
+                        }
+                        _ => {
+                            // NOTE: Tilemap cels are not supported
+                        }
+                    }
+                }
+                CHUNK_TYPE_PALETTE => {
+                    let palette_size = reader.u32()? as usize;
+                    let first_index = reader.u32()? as usize;
+                    let last_index = reader.u32()? as usize;
+                    reader.skip(8)?;
+                    if palette.len() < palette_size {
+                        palette.resize(palette_size, PixelRGBA::black());
+                    }
+                    for index in first_index..=last_index {
+                        let entry_flags = reader.u16()?;
+                        let color = reader.bytes(4)?;
+                        if entry_flags & 1 != 0 {
+                            let name_length = reader.u16()? as usize;
+                            reader.skip(name_length)?;
+                        }
+                        if index >= palette.len() {
+                            palette.resize(index + 1, PixelRGBA::black());
+                        }
+                        palette[index] = PixelRGBA::new(color[0], color[1], color[2], color[3]);
+                    }
+                }
+                CHUNK_TYPE_OLD_PALETTE => {
+                    let packet_count = reader.u16()?;
+                    let mut index = 0;
+                    for _ in 0..packet_count {
+                        index += reader.u8()? as usize;
+                        let color_count = match reader.u8()? {
+                            0 => 256,
+                            color_count => color_count as usize,
+                        };
+                        for _ in 0..color_count {
+                            let color = reader.bytes(3)?;
+                            if index < palette.len() {
+                                palette[index] = PixelRGBA::new(color[0], color[1], color[2], 255);
+                            }
+                            index += 1;
+                        }
+                    }
+                }
+                _ => {
+                    // NOTE: All other chunks like tags, user data or color profiles are not
+                    //       relevant for flattening
+                }
+            }
+
+            reader.seek(chunk_start + chunk_size)?;
+        }
+
+        cels_per_frame.push(cels);
+        reader.seek(frame_start + frame_size)?;
+    }
+
+    let is_auxiliary_layer = |layer: &Layer| {
+        auxiliary_layer_names
+            .iter()
+            .any(|auxiliary_name| auxiliary_name.to_lowercase() == layer.name)
+    };
+
+    // Flatten layers
+    let mut frames = Vec::new();
+    for cels in cels_per_frame.iter_mut() {
+        // NOTE: This is the same ordering Aseprite uses for cels with a z-index
+        cels.sort_by_key(|cel| (cel.layer_index as i32 + cel.z_index, cel.z_index));
+
+        let image = flatten_cels(cels, width, height, |cel| {
+            match layers.get(cel.layer_index) {
+                Some(layer) if layer.is_visible && !is_auxiliary_layer(layer) => {
+                    Some(((cel.opacity as u32 * layer.opacity as u32) / 255) as u8)
+                }
+                _ => None,
+            }
+        });
+
+        let mut auxiliary_layers = Vec::new();
+        for (layer_index, layer) in layers.iter().enumerate() {
+            if is_auxiliary_layer(layer) {
+                let auxiliary_image = flatten_cels(cels, width, height, |cel| {
+                    if cel.layer_index == layer_index {
+                        Some(255)
+                    } else {
+                        None
+                    }
+                });
+                auxiliary_layers.push((layer.name.clone(), auxiliary_image));
+            }
+        }
+
+        frames.push(AsepriteFrame {
+            image,
+            auxiliary_layers,
+        });
+    }
+
+    if frames.is_empty() {
+        return Err("No frame found".to_owned());
+    }
+    Ok(frames)
+}
+
+/// Blends the given cels on top of each other. The `opacity_of_cel` function returns the
+/// effective opacity of a cel or `None` if the cel should be skipped.
+fn flatten_cels(
+    cels: &[Cel],
+    width: i32,
+    height: i32,
+    opacity_of_cel: impl Fn(&Cel) -> Option<u8>,
+) -> Bitmap {
+    let mut result = Bitmap::new_filled(width as u32, height as u32, PixelRGBA::transparent());
+    for cel in cels.iter() {
+        let opacity = match opacity_of_cel(cel) {
+            Some(opacity) => opacity,
+            None => continue,
+        };
+        for cel_y in 0..cel.bitmap.height {
+            for cel_x in 0..cel.bitmap.width {
+                let x = cel.pos_x + cel_x;
+                let y = cel.pos_y + cel_y;
+                if x < 0 || y < 0 || x >= width || y >= height {
+                    continue;
+                }
+                let blended = blend_normal(cel.bitmap.get(cel_x, cel_y), result.get(x, y), opacity);
+                result.set(x, y, blended);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    const RED: PixelRGBA = PixelRGBA::new(255, 0, 0, 255);
+    const BLUE: PixelRGBA = PixelRGBA::new(0, 0, 255, 255);
+
+    fn chunk(chunk_type: u16, content: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&(6 + content.len() as u32).to_le_bytes());
+        data.extend_from_slice(&chunk_type.to_le_bytes());
+        data.extend_from_slice(content);
+        data
+    }
+
+    fn layer_chunk(name: &str, flags: u16, child_level: u16, opacity: u8) -> Vec<u8> {
+        let mut content = Vec::new();
+        content.extend_from_slice(&flags.to_le_bytes());
+        content.extend_from_slice(&0u16.to_le_bytes());
+        content.extend_from_slice(&child_level.to_le_bytes());
+        content.extend_from_slice(&[0; 6]);
+        content.push(opacity);
+        content.extend_from_slice(&[0; 3]);
+        content.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        content.extend_from_slice(name.as_bytes());
+        chunk(CHUNK_TYPE_LAYER, &content)
+    }
+
+    /// Creates a cel chunk with 32 bit RGBA pixels
+    fn cel_chunk(
+        layer_index: u16,
+        pos: (i16, i16),
+        dimensions: (u16, u16),
+        pixels: &[PixelRGBA],
+        compressed: bool,
+    ) -> Vec<u8> {
+        let mut pixel_data = Vec::new();
+        for pixel in pixels {
+            pixel_data.extend_from_slice(&[pixel.r, pixel.g, pixel.b, pixel.a]);
+        }
+        if compressed {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&pixel_data).unwrap();
+            pixel_data = encoder.finish().unwrap();
+        }
+
+        let cel_type = if compressed {
+            CEL_TYPE_COMPRESSED_IMAGE
+        } else {
+            CEL_TYPE_RAW_IMAGE
+        };
+        let mut content = Vec::new();
+        content.extend_from_slice(&layer_index.to_le_bytes());
+        content.extend_from_slice(&pos.0.to_le_bytes());
+        content.extend_from_slice(&pos.1.to_le_bytes());
+        content.push(255);
+        content.extend_from_slice(&cel_type.to_le_bytes());
+        content.extend_from_slice(&0i16.to_le_bytes());
+        content.extend_from_slice(&[0; 5]);
+        content.extend_from_slice(&dimensions.0.to_le_bytes());
+        content.extend_from_slice(&dimensions.1.to_le_bytes());
+        content.extend_from_slice(&pixel_data);
+        chunk(CHUNK_TYPE_CEL, &content)
+    }
+
+    /// Creates a 32 bit RGBA Aseprite file with valid layer opacity
+    fn aseprite_data(width: u16, height: u16, frames: &[Vec<Vec<u8>>]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&HEADER_MAGIC.to_le_bytes());
+        data.extend_from_slice(&(frames.len() as u16).to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&32u16.to_le_bytes());
+        data.extend_from_slice(&1u32.to_le_bytes());
+        data.resize(128, 0);
+
+        for chunks in frames {
+            let chunks_size: usize = chunks.iter().map(|chunk| chunk.len()).sum();
+            data.extend_from_slice(&(16 + chunks_size as u32).to_le_bytes());
+            data.extend_from_slice(&FRAME_MAGIC.to_le_bytes());
+            data.extend_from_slice(&(chunks.len() as u16).to_le_bytes());
+            data.extend_from_slice(&[0; 4]);
+            data.extend_from_slice(&0u32.to_le_bytes());
+            for chunk in chunks {
+                data.extend_from_slice(chunk);
+            }
+        }
+
+        let file_size = data.len() as u32;
+        data[0..4].copy_from_slice(&file_size.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn visible_layers_are_flattened_and_hidden_layers_ignored() {
+        let data = aseprite_data(
+            2,
+            2,
+            &[vec![
+                layer_chunk("Background", LAYER_FLAG_VISIBLE, 0, 255),
+                layer_chunk("Hidden", 0, 0, 255),
+                layer_chunk("Top", LAYER_FLAG_VISIBLE, 0, 255),
+                cel_chunk(0, (0, 0), (2, 2), &[RED; 4], false),
+                cel_chunk(1, (0, 0), (2, 2), &[BLUE; 4], false),
+                cel_chunk(2, (1, 1), (1, 1), &[BLUE], true),
+            ]],
+        );
+        assert_eq!(
+            image_formats::detect_image_format(&data),
+            Some(image_formats::ImageFormat::Aseprite)
+        );
+
+        let frames = frames_create_from_aseprite_data(&data, &[]).unwrap();
+        assert_eq!(frames.len(), 1);
+        let image = &frames[0].image;
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.get(0, 0), RED);
+        assert_eq!(image.get(1, 0), RED);
+        assert_eq!(image.get(0, 1), RED);
+        assert_eq!(image.get(1, 1), BLUE);
+        assert!(frames[0].auxiliary_layers.is_empty());
+    }
+
+    #[test]
+    fn layers_in_hidden_groups_are_ignored() {
+        let data = aseprite_data(
+            1,
+            1,
+            &[vec![
+                layer_chunk("Base", LAYER_FLAG_VISIBLE, 0, 255),
+                layer_chunk("Group", 0, 0, 255),
+                layer_chunk("Child", LAYER_FLAG_VISIBLE, 1, 255),
+                cel_chunk(0, (0, 0), (1, 1), &[RED], false),
+                cel_chunk(2, (0, 0), (1, 1), &[BLUE], false),
+            ]],
+        );
+        let frames = frames_create_from_aseprite_data(&data, &[]).unwrap();
+        assert_eq!(frames[0].image.get(0, 0), RED);
+    }
+
+    #[test]
+    fn layer_opacity_is_blended() {
+        let data = aseprite_data(
+            1,
+            1,
+            &[vec![
+                layer_chunk("Base", LAYER_FLAG_VISIBLE, 0, 255),
+                layer_chunk("Overlay", LAYER_FLAG_VISIBLE, 0, 128),
+                cel_chunk(0, (0, 0), (1, 1), &[RED], false),
+                cel_chunk(1, (0, 0), (1, 1), &[BLUE], false),
+            ]],
+        );
+        let frames = frames_create_from_aseprite_data(&data, &[]).unwrap();
+        assert_eq!(frames[0].image.get(0, 0), PixelRGBA::new(127, 0, 128, 255));
+    }
+
+    #[test]
+    fn auxiliary_layers_are_returned_separately_for_every_frame() {
+        let data = aseprite_data(
+            1,
+            1,
+            &[
+                vec![
+                    layer_chunk("Image", LAYER_FLAG_VISIBLE, 0, 255),
+                    layer_chunk("Backstitch", 0, 0, 255),
+                    cel_chunk(0, (0, 0), (1, 1), &[RED], false),
+                    cel_chunk(1, (0, 0), (1, 1), &[BLUE], false),
+                ],
+                vec![cel_chunk(0, (0, 0), (1, 1), &[BLUE], false)],
+            ],
+        );
+        let frames = frames_create_from_aseprite_data(&data, &["BACKSTITCH"]).unwrap();
+        assert_eq!(frames.len(), 2);
+
+        assert_eq!(frames[0].image.get(0, 0), RED);
+        assert_eq!(frames[0].auxiliary_layers.len(), 1);
+        assert_eq!(frames[0].auxiliary_layers[0].0, "backstitch");
+        assert_eq!(frames[0].auxiliary_layers[0].1.get(0, 0), BLUE);
+
+        assert_eq!(frames[1].image.get(0, 0), BLUE);
+        assert_eq!(
+            frames[1].auxiliary_layers[0].1.get(0, 0),
+            PixelRGBA::transparent()
+        );
+    }
+
+    #[test]
+    fn broken_files_are_rejected() {
+        let mut wrong_magic = aseprite_data(1, 1, &[vec![]]);
+        wrong_magic[4] = 0;
+        assert!(frames_create_from_aseprite_data(&wrong_magic, &[]).is_err());
+
+        let data = aseprite_data(
+            2,
+            2,
+            &[vec![
+                layer_chunk("Image", LAYER_FLAG_VISIBLE, 0, 255),
+                cel_chunk(0, (0, 0), (2, 2), &[RED; 4], false),
+            ]],
+        );
+        let truncated = &data[..data.len() - 4];
+        assert!(frames_create_from_aseprite_data(truncated, &[]).is_err());
+    }
+}
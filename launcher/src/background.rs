@@ -0,0 +1,174 @@
+use cottontail::image::bitmap::*;
+
+/// Pixels with an alpha below this are left unstitched. By default only fully transparent pixels.
+pub const ALPHA_THRESHOLD_DEFAULT: u8 = 1;
+
+/// A background color of an image that marks pixels which are left unstitched
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyColor {
+    Color(PixelRGBA),
+    /// The color of the top-left pixel of the image
+    CornerPixel,
+}
+
+impl KeyColor {
+    /// Returns `None` if the key color is taken from a corner pixel that is already transparent
+    pub fn color_in_image(self, image: &Bitmap) -> Option<PixelRGBA> {
+        let color = match self {
+            KeyColor::Color(color) => color,
+            KeyColor::CornerPixel => image.get(0, 0),
+        };
+        if color.a == 0 {
+            None
+        } else {
+            Some(color)
+        }
+    }
+}
+
+/// Blends a semi-transparent color over the opaque fabric color
+pub fn color_composited_on_fabric(color: PixelRGBA, fabric_color: PixelRGBA) -> PixelRGBA {
+    let alpha = color.a as u32;
+    let blend = |channel: u8, fabric_channel: u8| {
+        ((alpha * channel as u32 + (255 - alpha) * fabric_channel as u32 + 127) / 255) as u8
+    };
+    PixelRGBA::new(
+        blend(color.r, fabric_color.r),
+        blend(color.g, fabric_color.g),
+        blend(color.b, fabric_color.b),
+        255,
+    )
+}
+
+/// Makes the pixels of the key colors and the pixels with an alpha below `alpha_threshold` fully
+/// transparent, so that they are left unstitched. All other semi-transparent pixels are
+/// composited against the fabric color, which makes every pixel either fully transparent or
+/// opaque.
+///
+/// NOTE: Key colors are compared without their alpha, so a key color also matches semi-transparent
+///       pixels of that color
+pub fn image_clear_background(
+    image: &Bitmap,
+    key_colors: &[KeyColor],
+    alpha_threshold: u8,
+    fabric_color: PixelRGBA,
+) -> Bitmap {
+    let key_colors: Vec<PixelRGBA> = key_colors
+        .iter()
+        .filter_map(|key_color| key_color.color_in_image(image))
+        .collect();
+    let is_key_color = |color: PixelRGBA| {
+        key_colors.iter().any(|key_color| {
+            key_color.r == color.r && key_color.g == color.g && key_color.b == color.b
+        })
+    };
+
+    let mut result = image.clone();
+    for pixel in result.data.iter_mut() {
+        if pixel.a == 0 || pixel.a < alpha_threshold || is_key_color(*pixel) {
+            *pixel = PixelRGBA::transparent();
+        } else if pixel.a < 255 {
+            *pixel = color_composited_on_fabric(*pixel, fabric_color);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: PixelRGBA = PixelRGBA::new(255, 0, 0, 255);
+    const GREEN: PixelRGBA = PixelRGBA::new(0, 255, 0, 255);
+    const BLUE: PixelRGBA = PixelRGBA::new(0, 0, 255, 255);
+    const WHITE: PixelRGBA = PixelRGBA::new(255, 255, 255, 255);
+
+    fn image_row(pixels: &[PixelRGBA]) -> Bitmap {
+        Bitmap::new_from_buffer(pixels.len() as u32, 1, pixels.to_vec())
+    }
+
+    #[test]
+    fn key_colors_are_cleared_regardless_of_alpha() {
+        let red_semi_transparent = PixelRGBA::new(255, 0, 0, 128);
+        let image = image_row(&[RED, GREEN, red_semi_transparent, BLUE]);
+
+        let result = image_clear_background(
+            &image,
+            &[KeyColor::Color(RED)],
+            ALPHA_THRESHOLD_DEFAULT,
+            WHITE,
+        );
+        assert_eq!(
+            result.data,
+            vec![
+                PixelRGBA::transparent(),
+                GREEN,
+                PixelRGBA::transparent(),
+                BLUE
+            ]
+        );
+    }
+
+    #[test]
+    fn corner_key_color_is_taken_from_the_top_left_pixel() {
+        let image = image_row(&[GREEN, RED, GREEN]);
+        let result = image_clear_background(
+            &image,
+            &[KeyColor::CornerPixel],
+            ALPHA_THRESHOLD_DEFAULT,
+            WHITE,
+        );
+        assert_eq!(
+            result.data,
+            vec![PixelRGBA::transparent(), RED, PixelRGBA::transparent()]
+        );
+
+        // NOTE: A transparent corner must not turn black pixels into background
+        let black = PixelRGBA::new(0, 0, 0, 255);
+        let image = image_row(&[PixelRGBA::new(0, 0, 0, 0), black]);
+        assert_eq!(KeyColor::CornerPixel.color_in_image(&image), None);
+        let result = image_clear_background(
+            &image,
+            &[KeyColor::CornerPixel],
+            ALPHA_THRESHOLD_DEFAULT,
+            WHITE,
+        );
+        assert_eq!(result.data[1], black);
+    }
+
+    #[test]
+    fn pixels_below_the_alpha_threshold_are_cleared() {
+        let alpha_threshold = 100;
+        let image = image_row(&[
+            PixelRGBA::new(255, 0, 0, alpha_threshold - 1),
+            PixelRGBA::new(255, 0, 0, alpha_threshold),
+        ]);
+        let result = image_clear_background(&image, &[], alpha_threshold, WHITE);
+        assert_eq!(result.data[0], PixelRGBA::transparent());
+        assert_eq!(
+            result.data[1],
+            color_composited_on_fabric(image.data[1], WHITE)
+        );
+        assert_eq!(result.data[1].a, 255);
+    }
+
+    #[test]
+    fn semi_transparent_colors_are_blended_onto_the_fabric() {
+        assert_eq!(color_composited_on_fabric(RED, WHITE), RED);
+        assert_eq!(
+            color_composited_on_fabric(PixelRGBA::new(255, 0, 0, 0), BLUE),
+            BLUE
+        );
+        assert_eq!(
+            color_composited_on_fabric(PixelRGBA::new(255, 0, 0, 128), WHITE),
+            PixelRGBA::new(255, 127, 127, 255)
+        );
+        assert_eq!(
+            color_composited_on_fabric(
+                PixelRGBA::new(0, 0, 0, 51),
+                PixelRGBA::new(100, 200, 50, 255)
+            ),
+            PixelRGBA::new(80, 160, 40, 255)
+        );
+    }
+}
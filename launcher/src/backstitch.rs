@@ -0,0 +1,210 @@
+use crate::floss::{Floss, FlossCatalog};
+
+use cottontail::image::bitmap::*;
+use cottontail::math::*;
+
+use indexmap::IndexMap;
+
+use std::collections::HashSet;
+
+/// Name of the Aseprite layer and suffix of the image file (i.e. `cat_backstitch.png` for
+/// `cat.png`) that contain the backstitch lines of an image
+pub const BACKSTITCH_LAYER_NAME: &str = "backstitch";
+
+/// A straight backstitch between two neighbouring grid points. Grid points are the corners of
+/// the stitch cells, the top-left corner of the image is at 0x0 and the bottom-right corner at
+/// `width`x`height`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Backstitch {
+    pub start: Vec2i,
+    pub end: Vec2i,
+    pub color: PixelRGBA,
+}
+
+impl Backstitch {
+    /// Returns the same stitch with start and end in a fixed order so that we can detect
+    /// duplicates
+    pub(crate) fn normalized(self) -> Backstitch {
+        if (self.start.y, self.start.x) <= (self.end.y, self.end.x) {
+            self
+        } else {
+            Backstitch {
+                start: self.end,
+                end: self.start,
+                color: self.color,
+            }
+        }
+    }
+
+    /// Length in stitch cells
+    pub fn length(&self) -> f64 {
+        let delta_x = (self.end.x - self.start.x) as f64;
+        let delta_y = (self.end.y - self.start.y) as f64;
+        f64::sqrt(delta_x * delta_x + delta_y * delta_y)
+    }
+}
+
+/// Statistics of all backstitches of one color
+#[derive(Debug, Clone)]
+pub struct BackstitchInfo {
+    pub color: PixelRGBA,
+    pub count: usize,
+    /// Length in stitch cells
+    pub length: f64,
+    pub floss: Option<Floss>,
+}
+
+/// Outlines the regions of the given colors with backstitches of `stitch_color`. Lines are placed
+/// on every cell border between a cell of an outlined color and a cell of another color or the
+/// image border.
+pub fn backstitches_create_from_region_outlines(
+    image: &Bitmap,
+    outline_colors: &[PixelRGBA],
+    stitch_color: PixelRGBA,
+) -> Vec<Backstitch> {
+    let mut result = Vec::new();
+    let mut known = HashSet::new();
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let color = image.get(x, y);
+            if !outline_colors.contains(&color) {
+                continue;
+            }
+
+            let neighbour_differs = |neighbour_x: i32, neighbour_y: i32| {
+                neighbour_x < 0
+                    || neighbour_y < 0
+                    || neighbour_x >= image.width
+                    || neighbour_y >= image.height
+                    || image.get(neighbour_x, neighbour_y) != color
+            };
+            let mut borders = Vec::new();
+            if neighbour_differs(x, y - 1) {
+                borders.push((Vec2i::new(x, y), Vec2i::new(x + 1, y)));
+            }
+            if neighbour_differs(x, y + 1) {
+                borders.push((Vec2i::new(x, y + 1), Vec2i::new(x + 1, y + 1)));
+            }
+            if neighbour_differs(x - 1, y) {
+                borders.push((Vec2i::new(x, y), Vec2i::new(x, y + 1)));
+            }
+            if neighbour_differs(x + 1, y) {
+                borders.push((Vec2i::new(x + 1, y), Vec2i::new(x + 1, y + 1)));
+            }
+
+            // NOTE: Borders between two outlined regions would otherwise be stitched twice
+            for (start, end) in borders {
+                let backstitch = Backstitch {
+                    start,
+                    end,
+                    color: stitch_color,
+                };
+                if known.insert(backstitch.normalized()) {
+                    result.push(backstitch);
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Traces the backstitches of a line layer. Every opaque pixel of the layer is a grid point, the
+/// pixel at 0x0 is the top-left corner of the top-left cell. Neighbouring grid points of the same
+/// color are connected. Diagonal neighbours are only connected if they are not already connected
+/// via a shared horizontal or vertical neighbour.
+///
+/// NOTE: The layer can be one pixel wider and higher than the image to reach its right and bottom
+///       border
+pub fn backstitches_create_from_line_layer(layer: &Bitmap) -> Vec<Backstitch> {
+    let is_point_of_color = |x: i32, y: i32, color: PixelRGBA| {
+        x >= 0 && y >= 0 && x < layer.width && y < layer.height && layer.get(x, y) == color
+    };
+
+    let mut result = Vec::new();
+    for y in 0..layer.height {
+        for x in 0..layer.width {
+            let color = layer.get(x, y);
+            if color.a == 0 {
+                continue;
+            }
+
+            // NOTE: We only look forward so that every line is only found once
+            let mut neighbours = Vec::new();
+            if is_point_of_color(x + 1, y, color) {
+                neighbours.push(Vec2i::new(x + 1, y));
+            }
+            if is_point_of_color(x, y + 1, color) {
+                neighbours.push(Vec2i::new(x, y + 1));
+            }
+            if is_point_of_color(x + 1, y + 1, color)
+                && !is_point_of_color(x + 1, y, color)
+                && !is_point_of_color(x, y + 1, color)
+            {
+                neighbours.push(Vec2i::new(x + 1, y + 1));
+            }
+            if is_point_of_color(x - 1, y + 1, color)
+                && !is_point_of_color(x - 1, y, color)
+                && !is_point_of_color(x, y + 1, color)
+            {
+                neighbours.push(Vec2i::new(x - 1, y + 1));
+            }
+
+            for neighbour in neighbours {
+                result.push(Backstitch {
+                    start: Vec2i::new(x, y),
+                    end: neighbour,
+                    color: PixelRGBA::new(color.r, color.g, color.b, 255),
+                });
+            }
+        }
+    }
+    result
+}
+
+/// Returns the backstitches that lie completely inside the given region of grid points, moved so
+/// that the region starts at 0x0
+pub fn backstitches_in_region(
+    backstitches: &[Backstitch],
+    first_point: Vec2i,
+    width: i32,
+    height: i32,
+) -> Vec<Backstitch> {
+    let is_inside = |point: Vec2i| {
+        first_point.x <= point.x
+            && point.x <= first_point.x + width
+            && first_point.y <= point.y
+            && point.y <= first_point.y + height
+    };
+    backstitches
+        .iter()
+        .filter(|backstitch| is_inside(backstitch.start) && is_inside(backstitch.end))
+        .map(|backstitch| Backstitch {
+            start: backstitch.start - first_point,
+            end: backstitch.end - first_point,
+            color: backstitch.color,
+        })
+        .collect()
+}
+
+/// Sums up the backstitches per color. If a floss catalog is given every color gets its closest
+/// floss.
+pub fn create_backstitch_infos(
+    backstitches: &[Backstitch],
+    floss_catalog: Option<&FlossCatalog>,
+) -> IndexMap<PixelRGBA, BackstitchInfo> {
+    let mut result = IndexMap::new();
+    for backstitch in backstitches {
+        let entry = result
+            .entry(backstitch.color)
+            .or_insert_with(|| BackstitchInfo {
+                color: backstitch.color,
+                count: 0,
+                length: 0.0,
+                floss: floss_catalog
+                    .map(|floss_catalog| floss_catalog.find_closest(backstitch.color).clone()),
+            });
+        entry.count += 1;
+        entry.length += backstitch.length();
+    }
+    result
+}
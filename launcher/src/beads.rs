@@ -0,0 +1,46 @@
+/// The most common square pegboard of Hama, Perler and Artkal midi beads
+pub const PEGBOARD_SIZE_DEFAULT: i32 = 29;
+/// Number of beads in the common small bags of Hama, Perler and Artkal
+pub const BEAD_BAG_SIZE_DEFAULT: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BeadSettings {
+    /// Number of pegs per row of one pegboard
+    pub pegboard_width: i32,
+    /// Number of pegs per column of one pegboard
+    pub pegboard_height: i32,
+    /// Number of beads per bag of one color
+    pub bag_size: usize,
+}
+
+impl Default for BeadSettings {
+    fn default() -> Self {
+        BeadSettings {
+            pegboard_width: PEGBOARD_SIZE_DEFAULT,
+            pegboard_height: PEGBOARD_SIZE_DEFAULT,
+            bag_size: BEAD_BAG_SIZE_DEFAULT,
+        }
+    }
+}
+
+impl BeadSettings {
+    /// Example: "29x29 pegboards, bags of 1000"
+    pub fn display_name(&self) -> String {
+        format!(
+            "{}x{} pegboards, bags of {}",
+            self.pegboard_width, self.pegboard_height, self.bag_size
+        )
+    }
+
+    /// NOTE: This is always rounded up because we can only buy whole bags
+    pub fn bag_count(&self, bead_count: usize) -> usize {
+        bead_count.div_ceil(self.bag_size)
+    }
+
+    /// Number of pegboards needed to lay out an image of the given size
+    pub fn pegboard_count(&self, image_width: i32, image_height: i32) -> usize {
+        let columns = (image_width + self.pegboard_width - 1) / self.pegboard_width;
+        let rows = (image_height + self.pegboard_height - 1) / self.pegboard_height;
+        (columns * rows) as usize
+    }
+}
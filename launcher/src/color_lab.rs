@@ -0,0 +1,241 @@
+use cottontail::image::bitmap::*;
+
+/// A color in the CIELAB color space (D65 whitepoint). Euclidean distances in this space roughly
+/// match the perceived difference between two colors which makes it a good space for comparing,
+/// averaging and clustering colors.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColorLab {
+    pub l: f64,
+    pub a: f64,
+    pub b: f64,
+}
+
+impl ColorLab {
+    pub fn from_pixelrgba(color: PixelRGBA) -> ColorLab {
+        fn srgb_to_linear(channel: u8) -> f64 {
+            let value = channel as f64 / 255.0;
+            if value <= 0.04045 {
+                value / 12.92
+            } else {
+                ((value + 0.055) / 1.055).powf(2.4)
+            }
+        }
+        fn xyz_to_lab_component(value: f64) -> f64 {
+            if value > 216.0 / 24389.0 {
+                value.cbrt()
+            } else {
+                (24389.0 / 27.0 * value + 16.0) / 116.0
+            }
+        }
+
+        let r = srgb_to_linear(color.r);
+        let g = srgb_to_linear(color.g);
+        let b = srgb_to_linear(color.b);
+
+        // NOTE: The XYZ values are already normalized by the D65 reference white
+        let x = (0.4124564 * r + 0.3575761 * g + 0.1804375 * b) / 0.95047;
+        let y = 0.2126729 * r + 0.7151522 * g + 0.0721750 * b;
+        let z = (0.0193339 * r + 0.1191920 * g + 0.9503041 * b) / 1.08883;
+
+        let fx = xyz_to_lab_component(x);
+        let fy = xyz_to_lab_component(y);
+        let fz = xyz_to_lab_component(z);
+
+        ColorLab {
+            l: 116.0 * fy - 16.0,
+            a: 500.0 * (fx - fy),
+            b: 200.0 * (fy - fz),
+        }
+    }
+
+    /// The CIE76 color difference which is the plain euclidean distance in Lab space. A value of
+    /// around 2.3 is considered a just noticeable difference.
+    pub fn distance_cie76(self, other: ColorLab) -> f64 {
+        self.distance_cie76_squared(other).sqrt()
+    }
+
+    pub fn distance_cie76_squared(self, other: ColorLab) -> f64 {
+        let dl = self.l - other.l;
+        let da = self.a - other.a;
+        let db = self.b - other.b;
+        dl * dl + da * da + db * db
+    }
+
+    /// The CIEDE2000 color difference. It corrects the perceptual non-uniformities of CIE76 for
+    /// saturated colors, blues and near-neutral colors and is the best choice for matching colors
+    /// against a fixed catalog of colors.
+    ///
+    /// See "The CIEDE2000 Color-Difference Formula: Implementation Notes, Supplementary Test Data,
+    /// and Mathematical Observations" by Sharma, Wu and Dalal
+    pub fn distance_ciede2000(self, other: ColorLab) -> f64 {
+        const POW_25_7: f64 = 6103515625.0;
+
+        let chroma_1 = (self.a * self.a + self.b * self.b).sqrt();
+        let chroma_2 = (other.a * other.a + other.b * other.b).sqrt();
+        let chroma_mean_pow_7 = ((chroma_1 + chroma_2) / 2.0).powi(7);
+        let g = 0.5 * (1.0 - (chroma_mean_pow_7 / (chroma_mean_pow_7 + POW_25_7)).sqrt());
+
+        let a_prime_1 = (1.0 + g) * self.a;
+        let a_prime_2 = (1.0 + g) * other.a;
+        let chroma_prime_1 = (a_prime_1 * a_prime_1 + self.b * self.b).sqrt();
+        let chroma_prime_2 = (a_prime_2 * a_prime_2 + other.b * other.b).sqrt();
+
+        fn hue_angle_degrees(b: f64, a_prime: f64) -> f64 {
+            if b == 0.0 && a_prime == 0.0 {
+                0.0
+            } else {
+                let angle = b.atan2(a_prime).to_degrees();
+                if angle < 0.0 {
+                    angle + 360.0
+                } else {
+                    angle
+                }
+            }
+        }
+        let hue_prime_1 = hue_angle_degrees(self.b, a_prime_1);
+        let hue_prime_2 = hue_angle_degrees(other.b, a_prime_2);
+
+        let chroma_product = chroma_prime_1 * chroma_prime_2;
+        let delta_lightness = other.l - self.l;
+        let delta_chroma = chroma_prime_2 - chroma_prime_1;
+        let delta_hue_angle = if chroma_product == 0.0 {
+            0.0
+        } else {
+            let difference = hue_prime_2 - hue_prime_1;
+            if difference > 180.0 {
+                difference - 360.0
+            } else if difference < -180.0 {
+                difference + 360.0
+            } else {
+                difference
+            }
+        };
+        let delta_hue = 2.0 * chroma_product.sqrt() * (delta_hue_angle / 2.0).to_radians().sin();
+
+        let lightness_mean = (self.l + other.l) / 2.0;
+        let chroma_prime_mean = (chroma_prime_1 + chroma_prime_2) / 2.0;
+        let hue_prime_mean = if chroma_product == 0.0 {
+            hue_prime_1 + hue_prime_2
+        } else if (hue_prime_1 - hue_prime_2).abs() <= 180.0 {
+            (hue_prime_1 + hue_prime_2) / 2.0
+        } else if hue_prime_1 + hue_prime_2 < 360.0 {
+            (hue_prime_1 + hue_prime_2 + 360.0) / 2.0
+        } else {
+            (hue_prime_1 + hue_prime_2 - 360.0) / 2.0
+        };
+
+        let t = 1.0 - 0.17 * (hue_prime_mean - 30.0).to_radians().cos()
+            + 0.24 * (2.0 * hue_prime_mean).to_radians().cos()
+            + 0.32 * (3.0 * hue_prime_mean + 6.0).to_radians().cos()
+            - 0.20 * (4.0 * hue_prime_mean - 63.0).to_radians().cos();
+        let delta_theta = 30.0 * (-((hue_prime_mean - 275.0) / 25.0).powi(2)).exp();
+        let chroma_prime_mean_pow_7 = chroma_prime_mean.powi(7);
+        let rotation_chroma =
+            2.0 * (chroma_prime_mean_pow_7 / (chroma_prime_mean_pow_7 + POW_25_7)).sqrt();
+        let lightness_offset_squared = (lightness_mean - 50.0).powi(2);
+        let scale_lightness =
+            1.0 + 0.015 * lightness_offset_squared / (20.0 + lightness_offset_squared).sqrt();
+        let scale_chroma = 1.0 + 0.045 * chroma_prime_mean;
+        let scale_hue = 1.0 + 0.015 * chroma_prime_mean * t;
+        let rotation = -(2.0 * delta_theta).to_radians().sin() * rotation_chroma;
+
+        let term_lightness = delta_lightness / scale_lightness;
+        let term_chroma = delta_chroma / scale_chroma;
+        let term_hue = delta_hue / scale_hue;
+        (term_lightness * term_lightness
+            + term_chroma * term_chroma
+            + term_hue * term_hue
+            + rotation * term_chroma * term_hue)
+            .sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_pixelrgba_maps_black_and_white_to_neutral_extremes() {
+        let white = ColorLab::from_pixelrgba(PixelRGBA::new(255, 255, 255, 255));
+        assert!((white.l - 100.0).abs() < 1e-3);
+        assert!(white.a.abs() < 1e-3);
+        assert!(white.b.abs() < 1e-3);
+
+        let black = ColorLab::from_pixelrgba(PixelRGBA::new(0, 0, 0, 255));
+        assert!(black.l.abs() < 1e-9);
+        assert!(black.a.abs() < 1e-9);
+        assert!(black.b.abs() < 1e-9);
+    }
+
+    #[test]
+    fn distance_ciede2000_matches_sharma_test_data() {
+        // NOTE: Table 1 of the paper by Sharma, Wu and Dalal referenced in `distance_ciede2000`
+        // Each row is L1, a1, b1, L2, a2, b2 and the expected difference
+        let test_data: [[f64; 7]; 34] = [
+            [50.0, 2.6772, -79.7751, 50.0, 0.0, -82.7485, 2.0425],
+            [50.0, 3.1571, -77.2803, 50.0, 0.0, -82.7485, 2.8615],
+            [50.0, 2.8361, -74.0200, 50.0, 0.0, -82.7485, 3.4412],
+            [50.0, -1.3802, -84.2814, 50.0, 0.0, -82.7485, 1.0000],
+            [50.0, -1.1848, -84.8006, 50.0, 0.0, -82.7485, 1.0000],
+            [50.0, -0.9009, -85.5211, 50.0, 0.0, -82.7485, 1.0000],
+            [50.0, 0.0, 0.0, 50.0, -1.0, 2.0, 2.3669],
+            [50.0, -1.0, 2.0, 50.0, 0.0, 0.0, 2.3669],
+            [50.0, 2.4900, -0.0010, 50.0, -2.4900, 0.0009, 7.1792],
+            [50.0, 2.4900, -0.0010, 50.0, -2.4900, 0.0010, 7.1792],
+            [50.0, 2.4900, -0.0010, 50.0, -2.4900, 0.0011, 7.2195],
+            [50.0, 2.4900, -0.0010, 50.0, -2.4900, 0.0012, 7.2195],
+            [50.0, -0.0010, 2.4900, 50.0, 0.0009, -2.4900, 4.8045],
+            [50.0, -0.0010, 2.4900, 50.0, 0.0010, -2.4900, 4.8045],
+            [50.0, -0.0010, 2.4900, 50.0, 0.0011, -2.4900, 4.7461],
+            [50.0, 2.5, 0.0, 50.0, 0.0, -2.5, 4.3065],
+            [50.0, 2.5, 0.0, 73.0, 25.0, -18.0, 27.1492],
+            [50.0, 2.5, 0.0, 61.0, -5.0, 29.0, 22.8977],
+            [50.0, 2.5, 0.0, 56.0, -27.0, -3.0, 31.9030],
+            [50.0, 2.5, 0.0, 58.0, 24.0, 15.0, 19.4535],
+            [50.0, 2.5, 0.0, 50.0, 3.1736, 0.5854, 1.0000],
+            [50.0, 2.5, 0.0, 50.0, 3.2972, 0.0, 1.0000],
+            [50.0, 2.5, 0.0, 50.0, 1.8634, 0.5757, 1.0000],
+            [50.0, 2.5, 0.0, 50.0, 3.2592, 0.3350, 1.0000],
+            [
+                60.2574, -34.0099, 36.2677, 60.4626, -34.1751, 39.4387, 1.2644,
+            ],
+            [
+                63.0109, -31.0961, -5.8663, 62.8187, -29.7946, -4.0864, 1.2630,
+            ],
+            [61.2901, 3.7196, -5.3901, 61.4292, 2.2480, -4.9620, 1.8731],
+            [35.0831, -44.1164, 3.7933, 35.0232, -40.0716, 1.5901, 1.8645],
+            [
+                22.7233, 20.0904, -46.6940, 23.0331, 14.9730, -42.5619, 2.0373,
+            ],
+            [36.4612, 47.8580, 18.3852, 36.2715, 50.5065, 21.2231, 1.4146],
+            [90.8027, -2.0831, 1.4410, 91.1528, -1.6435, 0.0447, 1.4441],
+            [90.9257, -0.5406, -0.9208, 88.6381, -0.8985, -0.7239, 1.5381],
+            [6.7747, -0.2908, -2.4247, 5.8714, -0.0985, -2.2286, 0.6377],
+            [2.0776, 0.0795, -1.1350, 0.9033, -0.0636, -0.5514, 0.9082],
+        ];
+
+        for (index, row) in test_data.iter().enumerate() {
+            let color_1 = ColorLab {
+                l: row[0],
+                a: row[1],
+                b: row[2],
+            };
+            let color_2 = ColorLab {
+                l: row[3],
+                a: row[4],
+                b: row[5],
+            };
+            let expected = row[6];
+            let difference = color_1.distance_ciede2000(color_2);
+            assert!(
+                (difference - expected).abs() < 1e-4,
+                "pair {}: expected {} but got {}",
+                index + 1,
+                expected,
+                difference
+            );
+            let difference_reversed = color_2.distance_ciede2000(color_1);
+            assert!((difference - difference_reversed).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,468 @@
+use crate::color_lab::ColorLab;
+
+use cottontail::image::bitmap::*;
+use indexmap::IndexMap;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+const KMEANS_REFINEMENT_ITERATION_COUNT: usize = 10;
+
+/// A color of the reduced palette and all source colors that were merged into it
+pub struct ColorReductionEntry {
+    pub palette_color: PixelRGBA,
+    pub palette_color_count: usize,
+    /// Source colors with their pixel counts, sorted by pixel count descending
+    pub merged_colors: Vec<(PixelRGBA, usize)>,
+}
+
+pub struct ColorReduction {
+    pub source_color_count: usize,
+    pub entries: Vec<ColorReductionEntry>,
+    mapping: HashMap<PixelRGBA, PixelRGBA>,
+}
+
+impl ColorReduction {
+    /// Replaces every non-transparent pixel with its palette color
+    pub fn apply_to_image(&self, image: &Bitmap) -> Bitmap {
+        let data = image
+            .data
+            .iter()
+            .map(|pixel| {
+                if pixel.a == 0 {
+                    *pixel
+                } else {
+                    // NOTE: We can unwrap here because the mapping was created from the same image
+                    *self.mapping.get(pixel).unwrap()
+                }
+            })
+            .collect();
+        Bitmap::new_from_buffer(image.width as u32, image.height as u32, data)
+    }
+
+    pub fn to_report_text(&self) -> String {
+        let mut result = format!(
+            "Reduced {} colors to {} colors\n",
+            self.source_color_count,
+            self.entries.len()
+        );
+        let palette_lab_colors: Vec<ColorLab> = self
+            .entries
+            .iter()
+            .map(|entry| ColorLab::from_pixelrgba(entry.palette_color))
+            .collect();
+        for (entry, palette_color_lab) in self.entries.iter().zip(palette_lab_colors.iter()) {
+            result += &format!(
+                "\n{} ({} stitches) merged from:\n",
+                color_to_hex_string(entry.palette_color),
+                entry.palette_color_count
+            );
+            for (source_color, source_count) in &entry.merged_colors {
+                let difference =
+                    palette_color_lab.distance_cie76(ColorLab::from_pixelrgba(*source_color));
+                result += &format!(
+                    "    {} ({} stitches, color difference {:.1})\n",
+                    color_to_hex_string(*source_color),
+                    source_count,
+                    difference
+                );
+            }
+        }
+        result
+    }
+}
+
+pub fn color_to_hex_string(color: PixelRGBA) -> String {
+    if color.a == 255 {
+        format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+    } else {
+        format!(
+            "#{:02X}{:02X}{:02X}{:02X}",
+            color.r, color.g, color.b, color.a
+        )
+    }
+}
+
+/// Parses `#RRGGBB` or `#RRGGBBAA` with or without the leading `#`
+pub fn color_from_hex_string(text: &str) -> Option<PixelRGBA> {
+    let text = text.trim().trim_start_matches('#');
+    if !text.is_ascii() {
+        return None;
+    }
+    let channel = |index: usize| u8::from_str_radix(&text[2 * index..2 * index + 2], 16).ok();
+    match text.len() {
+        6 => Some(PixelRGBA::new(channel(0)?, channel(1)?, channel(2)?, 255)),
+        8 => Some(PixelRGBA::new(
+            channel(0)?,
+            channel(1)?,
+            channel(2)?,
+            channel(3)?,
+        )),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Copy)]
+struct ColorSample {
+    color: PixelRGBA,
+    lab: ColorLab,
+    count: usize,
+}
+
+fn weighted_mean(samples: &[ColorSample]) -> ColorLab {
+    let mut sum = ColorLab {
+        l: 0.0,
+        a: 0.0,
+        b: 0.0,
+    };
+    let mut total_count = 0.0;
+    for sample in samples {
+        let weight = sample.count as f64;
+        sum.l += weight * sample.lab.l;
+        sum.a += weight * sample.lab.a;
+        sum.b += weight * sample.lab.b;
+        total_count += weight;
+    }
+    ColorLab {
+        l: sum.l / total_count,
+        a: sum.a / total_count,
+        b: sum.b / total_count,
+    }
+}
+
+fn weighted_squared_error(samples: &[ColorSample]) -> f64 {
+    let mean = weighted_mean(samples);
+    samples
+        .iter()
+        .map(|sample| sample.count as f64 * sample.lab.distance_cie76_squared(mean))
+        .sum()
+}
+
+fn lab_channel(color: ColorLab, axis: usize) -> f64 {
+    match axis {
+        0 => color.l,
+        1 => color.a,
+        _ => color.b,
+    }
+}
+
+/// Splits the given box of samples at the weighted median of its widest Lab axis
+fn median_cut_split(mut samples: Vec<ColorSample>) -> (Vec<ColorSample>, Vec<ColorSample>) {
+    assert!(samples.len() > 1);
+
+    let widest_axis = (0..3)
+        .map(|axis| {
+            let min = samples
+                .iter()
+                .map(|sample| lab_channel(sample.lab, axis))
+                .fold(f64::MAX, f64::min);
+            let max = samples
+                .iter()
+                .map(|sample| lab_channel(sample.lab, axis))
+                .fold(f64::MIN, f64::max);
+            (axis, max - min)
+        })
+        .fold((0, -1.0), |best, candidate| {
+            if candidate.1 > best.1 {
+                candidate
+            } else {
+                best
+            }
+        })
+        .0;
+
+    // NOTE: We break ties by the raw color values to keep the result deterministic
+    samples.sort_by(|a, b| {
+        lab_channel(a.lab, widest_axis)
+            .partial_cmp(&lab_channel(b.lab, widest_axis))
+            .unwrap()
+            .then_with(|| {
+                (a.color.r, a.color.g, a.color.b, a.color.a)
+                    .cmp(&(b.color.r, b.color.g, b.color.b, b.color.a))
+            })
+    });
+
+    let total_count: usize = samples.iter().map(|sample| sample.count).sum();
+    let mut accumulated_count = 0;
+    let mut split_index = 1;
+    for (index, sample) in samples.iter().enumerate() {
+        accumulated_count += sample.count;
+        if 2 * accumulated_count >= total_count {
+            split_index = index + 1;
+            break;
+        }
+    }
+    let split_index = split_index.max(1).min(samples.len() - 1);
+
+    let upper = samples.split_off(split_index);
+    (samples, upper)
+}
+
+/// Reduces the colors of the given image to at most `max_color_count` colors. This uses median cut
+/// in Lab space followed by a couple of k-means refinement iterations. Every palette color is the
+/// source color that is perceptually closest to its cluster center, so no new colors are invented.
+///
+/// Fully transparent pixels are ignored. Returns `None` if the image already has few enough colors.
+pub fn reduce_colors(image: &Bitmap, max_color_count: usize) -> Option<ColorReduction> {
+    assert!(max_color_count > 0);
+
+    let mut color_counts: IndexMap<PixelRGBA, usize> = IndexMap::new();
+    for pixel in &image.data {
+        if pixel.a != 0 {
+            *color_counts.entry(*pixel).or_insert(0) += 1;
+        }
+    }
+    let source_color_count = color_counts.len();
+    if source_color_count <= max_color_count {
+        return None;
+    }
+
+    let samples: Vec<ColorSample> = color_counts
+        .iter()
+        .map(|(color, count)| ColorSample {
+            color: *color,
+            lab: ColorLab::from_pixelrgba(*color),
+            count: *count,
+        })
+        .collect();
+
+    // Median cut
+    let mut boxes = vec![samples.clone()];
+    while boxes.len() < max_color_count {
+        let mut split_candidate: Option<(usize, f64)> = None;
+        for (box_index, samples_box) in boxes.iter().enumerate() {
+            if samples_box.len() < 2 {
+                continue;
+            }
+            let error = weighted_squared_error(samples_box);
+            if split_candidate.map_or(true, |(_, best_error)| error > best_error) {
+                split_candidate = Some((box_index, error));
+            }
+        }
+
+        if let Some((box_index, _)) = split_candidate {
+            let (lower, upper) = median_cut_split(boxes.remove(box_index));
+            boxes.push(lower);
+            boxes.push(upper);
+        } else {
+            break;
+        }
+    }
+
+    // K-means refinement
+    let mut centers: Vec<ColorLab> = boxes.iter().map(|samples| weighted_mean(samples)).collect();
+    let mut clusters: Vec<Vec<ColorSample>> = boxes;
+    for _ in 0..KMEANS_REFINEMENT_ITERATION_COUNT {
+        let mut new_clusters: Vec<Vec<ColorSample>> = vec![Vec::new(); centers.len()];
+        for sample in &samples {
+            let mut best_center_index = 0;
+            let mut best_distance = f64::MAX;
+            for (center_index, center) in centers.iter().enumerate() {
+                let distance = sample.lab.distance_cie76_squared(*center);
+                if distance < best_distance {
+                    best_distance = distance;
+                    best_center_index = center_index;
+                }
+            }
+            new_clusters[best_center_index].push(*sample);
+        }
+        new_clusters.retain(|cluster| !cluster.is_empty());
+        centers = new_clusters
+            .iter()
+            .map(|cluster| weighted_mean(cluster))
+            .collect();
+        clusters = new_clusters;
+    }
+
+    // Pick palette colors and build the mapping
+    let mut mapping = HashMap::new();
+    let mut entries = Vec::new();
+    for (cluster, center) in clusters.iter().zip(centers.iter()) {
+        let mut palette_sample = cluster[0];
+        for sample in cluster.iter().skip(1) {
+            if sample.lab.distance_cie76_squared(*center)
+                < palette_sample.lab.distance_cie76_squared(*center)
+            {
+                palette_sample = *sample;
+            }
+        }
+
+        let mut merged_colors: Vec<(PixelRGBA, usize)> = cluster
+            .iter()
+            .map(|sample| (sample.color, sample.count))
+            .collect();
+        merged_colors.sort_by_key(|&(_color, count)| Reverse(count));
+
+        for (source_color, _count) in &merged_colors {
+            mapping.insert(*source_color, palette_sample.color);
+        }
+        entries.push(ColorReductionEntry {
+            palette_color: palette_sample.color,
+            palette_color_count: cluster.iter().map(|sample| sample.count).sum(),
+            merged_colors,
+        });
+    }
+
+    // NOTE: This matches the order of the colors in our legend
+    entries.sort_by(|a, b| {
+        PixelRGBA::compare_by_hue_luminosity_saturation(&a.palette_color, &b.palette_color)
+    });
+
+    Some(ColorReduction {
+        source_color_count,
+        entries,
+        mapping,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(color: PixelRGBA, count: usize) -> ColorSample {
+        ColorSample {
+            color,
+            lab: ColorLab::from_pixelrgba(color),
+            count,
+        }
+    }
+
+    /// Builds a one pixel high image with the given colors repeated by their counts
+    fn image_from_color_counts(color_counts: &[(PixelRGBA, usize)]) -> Bitmap {
+        let data: Vec<PixelRGBA> = color_counts
+            .iter()
+            .flat_map(|&(color, count)| std::iter::repeat(color).take(count))
+            .collect();
+        Bitmap::new_from_buffer(data.len() as u32, 1, data)
+    }
+
+    #[test]
+    fn median_cut_split_splits_at_weighted_median_of_widest_axis() {
+        // NOTE: The lightness axis is the widest here
+        let samples = vec![
+            sample(PixelRGBA::new(250, 250, 250, 255), 1),
+            sample(PixelRGBA::new(10, 10, 10, 255), 5),
+            sample(PixelRGBA::new(200, 200, 200, 255), 1),
+            sample(PixelRGBA::new(20, 20, 20, 255), 1),
+        ];
+        let (lower, upper) = median_cut_split(samples);
+
+        let lower_colors: Vec<PixelRGBA> = lower.iter().map(|sample| sample.color).collect();
+        let upper_colors: Vec<PixelRGBA> = upper.iter().map(|sample| sample.color).collect();
+        assert_eq!(lower_colors, vec![PixelRGBA::new(10, 10, 10, 255)]);
+        assert_eq!(
+            upper_colors,
+            vec![
+                PixelRGBA::new(20, 20, 20, 255),
+                PixelRGBA::new(200, 200, 200, 255),
+                PixelRGBA::new(250, 250, 250, 255),
+            ]
+        );
+    }
+
+    #[test]
+    fn median_cut_split_never_produces_an_empty_box() {
+        let samples = vec![
+            sample(PixelRGBA::new(0, 0, 0, 255), 100),
+            sample(PixelRGBA::new(255, 255, 255, 255), 1),
+        ];
+        let (lower, upper) = median_cut_split(samples);
+        assert_eq!(lower.len(), 1);
+        assert_eq!(upper.len(), 1);
+    }
+
+    #[test]
+    fn reduce_colors_returns_none_if_image_has_few_enough_colors() {
+        let image = image_from_color_counts(&[
+            (PixelRGBA::new(255, 0, 0, 255), 3),
+            (PixelRGBA::new(0, 0, 255, 255), 2),
+            (PixelRGBA::transparent(), 4),
+        ]);
+        assert!(reduce_colors(&image, 2).is_none());
+    }
+
+    #[test]
+    fn reduce_colors_merges_similar_colors_into_existing_source_colors() {
+        let red = PixelRGBA::new(200, 20, 20, 255);
+        let red_similar = PixelRGBA::new(205, 22, 18, 255);
+        let blue = PixelRGBA::new(20, 20, 200, 255);
+        let blue_similar = PixelRGBA::new(18, 24, 196, 255);
+        let image = image_from_color_counts(&[
+            (red, 10),
+            (red_similar, 2),
+            (blue, 1),
+            (blue_similar, 7),
+            (PixelRGBA::transparent(), 3),
+        ]);
+
+        let reduction = reduce_colors(&image, 2).unwrap();
+        assert_eq!(reduction.source_color_count, 4);
+        assert_eq!(reduction.entries.len(), 2);
+
+        let red_entry = reduction
+            .entries
+            .iter()
+            .find(|entry| entry.palette_color == red)
+            .unwrap();
+        assert_eq!(red_entry.palette_color_count, 12);
+        assert_eq!(red_entry.merged_colors, vec![(red, 10), (red_similar, 2)]);
+
+        // NOTE: The palette color is the source color closest to the cluster center which is
+        //       dominated by the more frequent color
+        let blue_entry = reduction
+            .entries
+            .iter()
+            .find(|entry| entry.palette_color == blue_similar)
+            .unwrap();
+        assert_eq!(blue_entry.palette_color_count, 8);
+        assert_eq!(blue_entry.merged_colors, vec![(blue_similar, 7), (blue, 1)]);
+
+        let reduced_image = reduction.apply_to_image(&image);
+        assert_eq!(reduced_image.get(0, 0), red);
+        assert_eq!(reduced_image.get(10, 0), red);
+        assert_eq!(reduced_image.get(12, 0), blue_similar);
+        assert_eq!(reduced_image.get(13, 0), blue_similar);
+        assert_eq!(reduced_image.get(20, 0), PixelRGBA::transparent());
+    }
+
+    #[test]
+    fn reduce_colors_is_deterministic() {
+        let mut color_counts = Vec::new();
+        for index in 0..64u32 {
+            let color = PixelRGBA::new(
+                (index * 37 % 256) as u8,
+                (index * 91 % 256) as u8,
+                (index * 53 % 256) as u8,
+                255,
+            );
+            color_counts.push((color, 1 + (index as usize * 7) % 5));
+        }
+        let image = image_from_color_counts(&color_counts);
+
+        let first = reduce_colors(&image, 8).unwrap();
+        let second = reduce_colors(&image, 8).unwrap();
+        assert!(first.entries.len() <= 8);
+        assert_eq!(first.to_report_text(), second.to_report_text());
+
+        let total_count: usize = first
+            .entries
+            .iter()
+            .map(|entry| entry.palette_color_count)
+            .sum();
+        assert_eq!(total_count, image.data.len());
+    }
+
+    #[test]
+    fn hex_strings_round_trip() {
+        let opaque = PixelRGBA::new(0x12, 0xAB, 0xFF, 255);
+        assert_eq!(color_to_hex_string(opaque), "#12ABFF");
+        assert_eq!(color_from_hex_string("#12abff"), Some(opaque));
+
+        let translucent = PixelRGBA::new(1, 2, 3, 4);
+        assert_eq!(color_to_hex_string(translucent), "#01020304");
+        assert_eq!(color_from_hex_string(" 01020304 "), Some(translucent));
+
+        assert_eq!(color_from_hex_string("#12345"), None);
+        assert_eq!(color_from_hex_string("#GG0000"), None);
+    }
+}
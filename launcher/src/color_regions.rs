@@ -0,0 +1,289 @@
+use cottontail::image::bitmap::*;
+use cottontail::math::*;
+
+use std::collections::VecDeque;
+
+/// A connected area of stitches of the same color. Cells are connected via their edges, not via
+/// their corners.
+#[derive(Debug, Clone)]
+pub struct ColorRegion {
+    pub color: PixelRGBA,
+    pub cell_count: usize,
+    /// The cell of the region that is furthest away from its outline. This is where a
+    /// paint-by-numbers label goes.
+    pub label_cell: Vec2i,
+}
+
+/// The connected same-color areas of an image. Transparent pixels don't belong to any region.
+pub struct ColorRegions {
+    pub width: i32,
+    pub height: i32,
+    pub regions: Vec<ColorRegion>,
+    region_indices: Vec<Option<usize>>,
+}
+
+impl ColorRegions {
+    pub fn from_image(image: &Bitmap) -> ColorRegions {
+        let width = image.width;
+        let height = image.height;
+        let cell_index = |pos: Vec2i| (pos.y * width + pos.x) as usize;
+        let neighbours_of = |pos: Vec2i| {
+            [
+                Vec2i::new(pos.x - 1, pos.y),
+                Vec2i::new(pos.x + 1, pos.y),
+                Vec2i::new(pos.x, pos.y - 1),
+                Vec2i::new(pos.x, pos.y + 1),
+            ]
+        };
+        let is_inside = |pos: Vec2i| 0 <= pos.x && pos.x < width && 0 <= pos.y && pos.y < height;
+
+        // Flood fill every region and remember its cells
+        let mut region_indices: Vec<Option<usize>> = vec![None; (width * height) as usize];
+        let mut region_cells: Vec<Vec<Vec2i>> = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                let start = Vec2i::new(x, y);
+                let color = image.get(x, y);
+                if color.a == 0 || region_indices[cell_index(start)].is_some() {
+                    continue;
+                }
+
+                let region_index = region_cells.len();
+                let mut cells = vec![start];
+                region_indices[cell_index(start)] = Some(region_index);
+                let mut next_cell_index = 0;
+                while next_cell_index < cells.len() {
+                    let pos = cells[next_cell_index];
+                    next_cell_index += 1;
+                    for &neighbour in neighbours_of(pos).iter() {
+                        if is_inside(neighbour)
+                            && region_indices[cell_index(neighbour)].is_none()
+                            && image.get(neighbour.x, neighbour.y) == color
+                        {
+                            region_indices[cell_index(neighbour)] = Some(region_index);
+                            cells.push(neighbour);
+                        }
+                    }
+                }
+                region_cells.push(cells);
+            }
+        }
+
+        // Find the label cell of every region by walking inwards from its outline
+        let mut distances: Vec<i32> = vec![-1; (width * height) as usize];
+        let regions = region_cells
+            .iter()
+            .enumerate()
+            .map(|(region_index, cells)| {
+                let mut queue = VecDeque::new();
+                for &pos in cells {
+                    let is_on_outline = neighbours_of(pos).iter().any(|&neighbour| {
+                        !is_inside(neighbour)
+                            || region_indices[cell_index(neighbour)] != Some(region_index)
+                    });
+                    if is_on_outline {
+                        distances[cell_index(pos)] = 0;
+                        queue.push_back(pos);
+                    }
+                }
+                while let Some(pos) = queue.pop_front() {
+                    for &neighbour in neighbours_of(pos).iter() {
+                        if is_inside(neighbour)
+                            && region_indices[cell_index(neighbour)] == Some(region_index)
+                            && distances[cell_index(neighbour)] < 0
+                        {
+                            distances[cell_index(neighbour)] = distances[cell_index(pos)] + 1;
+                            queue.push_back(neighbour);
+                        }
+                    }
+                }
+
+                // NOTE: Of the cells that are furthest inside we take the one closest to the
+                //       center of the region, so that labels of symmetric regions are centered
+                let center_x2 =
+                    cells.iter().map(|pos| 2 * pos.x + 1).sum::<i32>() as f32 / cells.len() as f32;
+                let center_y2 =
+                    cells.iter().map(|pos| 2 * pos.y + 1).sum::<i32>() as f32 / cells.len() as f32;
+                let distance_to_center_squared = |pos: Vec2i| {
+                    let delta_x = (2 * pos.x + 1) as f32 - center_x2;
+                    let delta_y = (2 * pos.y + 1) as f32 - center_y2;
+                    delta_x * delta_x + delta_y * delta_y
+                };
+                let mut label_cell = cells[0];
+                for &pos in cells.iter().skip(1) {
+                    let distance = distances[cell_index(pos)];
+                    let label_distance = distances[cell_index(label_cell)];
+                    if distance > label_distance
+                        || (distance == label_distance
+                            && distance_to_center_squared(pos)
+                                < distance_to_center_squared(label_cell))
+                    {
+                        label_cell = pos;
+                    }
+                }
+
+                ColorRegion {
+                    color: image.get(cells[0].x, cells[0].y),
+                    cell_count: cells.len(),
+                    label_cell,
+                }
+            })
+            .collect();
+
+        ColorRegions {
+            width,
+            height,
+            regions,
+            region_indices,
+        }
+    }
+
+    /// Returns `None` for transparent cells and cells outside of the image
+    pub fn region_index(&self, x: i32, y: i32) -> Option<usize> {
+        if 0 <= x && x < self.width && 0 <= y && y < self.height {
+            self.region_indices[(y * self.width + x) as usize]
+        } else {
+            None
+        }
+    }
+
+    /// Returns whether the given cell is the label cell of its region
+    pub fn is_label_cell(&self, x: i32, y: i32) -> bool {
+        self.region_index(x, y)
+            .map(|region_index| self.regions[region_index].label_cell == Vec2i::new(x, y))
+            .unwrap_or(false)
+    }
+
+    /// Returns the cell edges that separate two different regions or a region from transparent
+    /// cells or the image border as pairs of grid points. Neighbouring edges on the same grid line
+    /// are merged.
+    pub fn outline_edges(&self) -> Vec<(Vec2i, Vec2i)> {
+        let mut result = Vec::new();
+
+        // NOTE: We check the edge above every cell and the one to its left. The extra row and
+        //       column covers the bottom and right border of the image.
+        for y in 0..=self.height {
+            let mut edge_start = None;
+            for x in 0..=self.width {
+                let is_edge =
+                    x < self.width && self.region_index(x, y - 1) != self.region_index(x, y);
+                match (edge_start, is_edge) {
+                    (None, true) => edge_start = Some(x),
+                    (Some(start), false) => {
+                        result.push((Vec2i::new(start, y), Vec2i::new(x, y)));
+                        edge_start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        for x in 0..=self.width {
+            let mut edge_start = None;
+            for y in 0..=self.height {
+                let is_edge =
+                    y < self.height && self.region_index(x - 1, y) != self.region_index(x, y);
+                match (edge_start, is_edge) {
+                    (None, true) => edge_start = Some(y),
+                    (Some(start), false) => {
+                        result.push((Vec2i::new(x, start), Vec2i::new(x, y)));
+                        edge_start = None;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an image from rows where every letter is a color and `.` is transparent
+    fn image_from_rows(rows: &[&str]) -> Bitmap {
+        let mut result = Bitmap::new(rows[0].len() as u32, rows.len() as u32);
+        for (y, row) in rows.iter().enumerate() {
+            for (x, character) in row.bytes().enumerate() {
+                if character != b'.' {
+                    result.set(x as i32, y as i32, PixelRGBA::new(character, 0, 0, 255));
+                }
+            }
+        }
+        result
+    }
+
+    fn assert_label_cells_are_inside_their_regions(regions: &ColorRegions) {
+        for (region_index, region) in regions.regions.iter().enumerate() {
+            let label_cell = region.label_cell;
+            assert_eq!(
+                regions.region_index(label_cell.x, label_cell.y),
+                Some(region_index)
+            );
+            assert!(regions.is_label_cell(label_cell.x, label_cell.y));
+        }
+    }
+
+    #[test]
+    fn label_of_square_is_centered() {
+        let regions = ColorRegions::from_image(&image_from_rows(&[
+            "AAAAA", "AAAAA", "AAAAA", "AAAAA", "AAAAA",
+        ]));
+        assert_eq!(regions.regions.len(), 1);
+        assert_eq!(regions.regions[0].cell_count, 25);
+        assert_eq!(regions.regions[0].label_cell, Vec2i::new(2, 2));
+        assert!(!regions.is_label_cell(0, 0));
+    }
+
+    #[test]
+    fn label_goes_into_thickest_part_of_region() {
+        let regions = ColorRegions::from_image(&image_from_rows(&[
+            "AAAAAAA", "AAAAAAA", "AAAAAAA", "A......", "A......",
+        ]));
+        assert_eq!(regions.regions.len(), 1);
+        assert_eq!(regions.regions[0].cell_count, 23);
+        assert_eq!(regions.regions[0].label_cell, Vec2i::new(3, 1));
+    }
+
+    #[test]
+    fn label_of_concave_region_is_inside_of_it() {
+        // NOTE: The center of the `C` region lies in the `B` region
+        let regions = ColorRegions::from_image(&image_from_rows(&[
+            "CCCCC", "CBBBB", "CBBBB", "CBBBB", "CCCCC",
+        ]));
+        assert_eq!(regions.regions.len(), 2);
+        assert_label_cells_are_inside_their_regions(&regions);
+    }
+
+    #[test]
+    fn regions_only_connect_via_edges() {
+        let regions = ColorRegions::from_image(&image_from_rows(&["A.A", ".A.", "A.A"]));
+        assert_eq!(regions.regions.len(), 5);
+        assert!(regions.regions.iter().all(|region| region.cell_count == 1));
+        assert_eq!(regions.region_index(1, 0), None);
+        assert_eq!(regions.region_index(-1, 0), None);
+        assert_eq!(regions.region_index(3, 0), None);
+        assert_label_cells_are_inside_their_regions(&regions);
+    }
+
+    #[test]
+    fn outline_edges_are_merged_along_grid_lines() {
+        let regions = ColorRegions::from_image(&image_from_rows(&["AB"]));
+        let mut edges = regions.outline_edges();
+        edges.sort_by_key(|(start, end)| (start.x, start.y, end.x, end.y));
+        assert_eq!(
+            edges,
+            vec![
+                (Vec2i::new(0, 0), Vec2i::new(0, 1)),
+                (Vec2i::new(0, 0), Vec2i::new(2, 0)),
+                (Vec2i::new(0, 1), Vec2i::new(2, 1)),
+                (Vec2i::new(1, 0), Vec2i::new(1, 1)),
+                (Vec2i::new(2, 0), Vec2i::new(2, 1)),
+            ]
+        );
+
+        let regions = ColorRegions::from_image(&image_from_rows(&["AA"]));
+        assert_eq!(regions.outline_edges().len(), 4);
+    }
+}
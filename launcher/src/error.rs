@@ -0,0 +1,111 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The content of an input file is broken or in a format we don't support
+    Decode {
+        filepath: String,
+        message: String,
+    },
+    /// The image has more colors than we have symbols to tell them apart
+    TooManyColors {
+        color_count: usize,
+        symbol_count: usize,
+    },
+    /// The requested frame of an animated image does not exist
+    FrameNotFound {
+        filepath: String,
+        frame_index: usize,
+        frame_count: usize,
+    },
+    /// A file of the `resources` dir (symbols, stitch images, floss catalogs) is missing or broken
+    Resource {
+        path: String,
+        message: String,
+    },
+    Io {
+        path: String,
+        error: std::io::Error,
+    },
+    /// We were asked not to touch existing output
+    OutputDirExists {
+        path: String,
+    },
+    /// The pattern options, resources or image layers don't fit together, i.e. a fractional
+    /// stitch map with a different size than its image
+    InvalidInput {
+        message: String,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    pub fn io(path: &str, error: std::io::Error) -> Error {
+        Error::Io {
+            path: path.to_owned(),
+            error,
+        }
+    }
+
+    pub fn resource(path: &str, message: &str) -> Error {
+        Error::Resource {
+            path: path.to_owned(),
+            message: message.to_owned(),
+        }
+    }
+
+    pub fn invalid_input(message: String) -> Error {
+        Error::InvalidInput { message }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Decode { filepath, message } => {
+                write!(f, "Cannot decode file '{}': {}", filepath, message)
+            }
+            Error::TooManyColors {
+                color_count,
+                symbol_count,
+            } => write!(
+                f,
+                "Not enough symbols to map {} colors, we only have {} symbols. \
+                 Use the `--max-colors` option to reduce the number of colors",
+                color_count, symbol_count
+            ),
+            Error::FrameNotFound {
+                filepath,
+                frame_index,
+                frame_count,
+            } => write!(
+                f,
+                "Cannot select frame {} of '{}' which only has {} frame(s)",
+                frame_index + 1,
+                filepath,
+                frame_count
+            ),
+            Error::Resource { path, message } => {
+                write!(f, "Invalid resource '{}': {}", path, message)
+            }
+            Error::Io { path, error } => write!(f, "Cannot access '{}': {}", path, error),
+            Error::OutputDirExists { path } => write!(
+                f,
+                "The output directory '{}' already exists. Use `--existing-output` with \
+                 `replace`, `version` or `timestamp` to write it anyway",
+                path
+            ),
+            Error::InvalidInput { message } => write!(f, "Invalid input: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,178 @@
+use crate::color_lab::ColorLab;
+use crate::thread_usage::CENTIMETERS_PER_INCH;
+
+use cottontail::image::bitmap::*;
+
+/// Color of plain white aida
+pub const FABRIC_COLOR_DEFAULT: PixelRGBA = PixelRGBA::new(255, 255, 255, 255);
+/// Stitches of colors that are closer (CIEDE2000) to the fabric color barely stand out from it
+const FABRIC_COLOR_DISTANCE_MIN: f64 = 10.0;
+/// Fabric added around the design on every side for framing and for holding it in a hoop
+pub const FABRIC_MARGIN_CENTIMETERS_DEFAULT: i32 = 5;
+
+/// Common embroidery hoop diameters
+const HOOP_DIAMETERS_INCHES: [i32; 9] = [4, 5, 6, 7, 8, 9, 10, 12, 14];
+/// Common bar lengths of scroll frames
+const SCROLL_FRAME_BAR_LENGTHS_INCHES: [i32; 6] = [8, 12, 18, 24, 27, 36];
+/// Space between the design and the hoop ring on every side so that the ring does not crush
+/// finished stitches
+const HOOP_CLEARANCE_INCHES: f64 = 0.5;
+
+pub struct Fabric {
+    pub name: &'static str,
+    /// NOTE: Evenweave worked over two threads has half as many stitches per inch as its count
+    pub stitches_per_inch: i32,
+}
+
+/// The fabrics we show finished sizes for in the legend
+pub const FABRICS: [Fabric; 6] = [
+    Fabric {
+        name: "11 count Aida",
+        stitches_per_inch: 11,
+    },
+    Fabric {
+        name: "14 count Aida",
+        stitches_per_inch: 14,
+    },
+    Fabric {
+        name: "16 count Aida",
+        stitches_per_inch: 16,
+    },
+    Fabric {
+        name: "18 count Aida",
+        stitches_per_inch: 18,
+    },
+    Fabric {
+        name: "28 count evenweave over two",
+        stitches_per_inch: 14,
+    },
+    Fabric {
+        name: "32 count evenweave over two",
+        stitches_per_inch: 16,
+    },
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameSuggestion {
+    /// Diameter in inches
+    Hoop(i32),
+    /// Bar length in inches
+    ScrollFrame(i32),
+    /// The design is too big for the usual scroll frames
+    Custom,
+}
+
+impl FrameSuggestion {
+    /// Example: "7 inch hoop"
+    pub fn display_name(&self) -> String {
+        match self {
+            FrameSuggestion::Hoop(diameter) => format!("{} inch hoop", diameter),
+            FrameSuggestion::ScrollFrame(bar_length) => {
+                format!("{} inch scroll frame", bar_length)
+            }
+            FrameSuggestion::Custom => "large custom frame".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct FinishedSize {
+    pub width_inches: f64,
+    pub height_inches: f64,
+    pub width_centimeters: f64,
+    pub height_centimeters: f64,
+    /// The finished size plus the margins on all sides
+    pub cut_width_centimeters: f64,
+    pub cut_height_centimeters: f64,
+    pub frame: FrameSuggestion,
+}
+
+impl Fabric {
+    /// Calculates the size of a design of the given number of stitches on this fabric and
+    /// how much fabric we need to cut for it with the given margin on every side
+    pub fn finished_size(
+        &self,
+        stitch_count_x: i32,
+        stitch_count_y: i32,
+        margin_centimeters: i32,
+    ) -> FinishedSize {
+        let width_inches = stitch_count_x as f64 / self.stitches_per_inch as f64;
+        let height_inches = stitch_count_y as f64 / self.stitches_per_inch as f64;
+        let cut_width_centimeters =
+            width_inches * CENTIMETERS_PER_INCH + 2.0 * margin_centimeters as f64;
+        let cut_height_centimeters =
+            height_inches * CENTIMETERS_PER_INCH + 2.0 * margin_centimeters as f64;
+
+        // NOTE: A hoop needs to contain the whole design, so we go by its diagonal. Bigger designs
+        //       are rolled onto a scroll frame whose bars need to be as long as the cut fabric is
+        //       wide.
+        let diagonal_inches =
+            f64::sqrt(width_inches * width_inches + height_inches * height_inches);
+        let frame = if let Some(diameter) = HOOP_DIAMETERS_INCHES
+            .iter()
+            .find(|&&diameter| diameter as f64 >= diagonal_inches + 2.0 * HOOP_CLEARANCE_INCHES)
+        {
+            FrameSuggestion::Hoop(*diameter)
+        } else if let Some(bar_length) = SCROLL_FRAME_BAR_LENGTHS_INCHES
+            .iter()
+            .find(|&&bar_length| bar_length as f64 >= cut_width_centimeters / CENTIMETERS_PER_INCH)
+        {
+            FrameSuggestion::ScrollFrame(*bar_length)
+        } else {
+            FrameSuggestion::Custom
+        };
+
+        FinishedSize {
+            width_inches,
+            height_inches,
+            width_centimeters: width_inches * CENTIMETERS_PER_INCH,
+            height_centimeters: height_inches * CENTIMETERS_PER_INCH,
+            cut_width_centimeters,
+            cut_height_centimeters,
+            frame,
+        }
+    }
+}
+
+/// Returns whether stitches of the given color would be hard to see on fabric of the given color
+pub fn color_is_hard_to_see_on_fabric(color: PixelRGBA, fabric_color: PixelRGBA) -> bool {
+    ColorLab::from_pixelrgba(color).distance_ciede2000(ColorLab::from_pixelrgba(fabric_color))
+        < FABRIC_COLOR_DISTANCE_MIN
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FABRIC_14_COUNT: &Fabric = &FABRICS[1];
+
+    fn assert_approx_eq(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn small_design_fits_a_hoop() {
+        let size = FABRIC_14_COUNT.finished_size(42, 56, 5);
+        assert_approx_eq(size.width_inches, 3.0);
+        assert_approx_eq(size.height_inches, 4.0);
+        assert_approx_eq(size.width_centimeters, 7.62);
+        assert_approx_eq(size.cut_width_centimeters, 17.62);
+        assert_approx_eq(size.cut_height_centimeters, 20.16);
+        // NOTE: The diagonal is 5 inches plus half an inch of clearance on both sides
+        assert_eq!(size.frame, FrameSuggestion::Hoop(6));
+        assert_eq!(size.frame.display_name(), "6 inch hoop");
+    }
+
+    #[test]
+    fn wide_design_needs_a_scroll_frame() {
+        // NOTE: 20 inches plus 2x5 cm margin is about 23.9 inches of cut fabric
+        let size = FABRIC_14_COUNT.finished_size(280, 140, 5);
+        assert_eq!(size.frame, FrameSuggestion::ScrollFrame(24));
+    }
+
+    #[test]
+    fn huge_design_needs_a_custom_frame() {
+        let size = FABRIC_14_COUNT.finished_size(700, 140, 5);
+        assert_eq!(size.frame, FrameSuggestion::Custom);
+    }
+}
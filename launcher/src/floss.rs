@@ -0,0 +1,233 @@
+use crate::color_lab::ColorLab;
+use crate::error::{Error, Result};
+
+use cottontail::core::*;
+use cottontail::image::bitmap::*;
+
+use std::collections::HashMap;
+
+/// A single thread of a floss catalog like "DMC 321 Red"
+#[derive(Debug, Clone)]
+pub struct Floss {
+    pub catalog_name: String,
+    pub number: String,
+    pub name: String,
+    pub color: PixelRGBA,
+    pub color_lab: ColorLab,
+}
+
+impl Floss {
+    /// Example: "DMC 321 Red"
+    pub fn display_name(&self) -> String {
+        format!("{} {} {}", self.catalog_name, self.number, self.name)
+    }
+}
+
+pub struct FlossCatalog {
+    pub entries: Vec<Floss>,
+}
+
+impl FlossCatalog {
+    /// Loads a catalog from a `.csv` file with the columns `number,name,red,green,blue`. Lines
+    /// starting with `#` are comments. The display name of the catalog is given by a
+    /// `# name: <catalog name>` comment and defaults to the filename otherwise.
+    pub fn from_csv_file(catalog_filepath: &str) -> Result<FlossCatalog> {
+        let content = std::fs::read_to_string(catalog_filepath)
+            .map_err(|error| Error::io(catalog_filepath, error))?;
+
+        let mut catalog_name = path_to_filename_without_extension(catalog_filepath);
+        let mut entries = Vec::new();
+        let mut found_header = false;
+        for (line_index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            if line.starts_with('#') {
+                let comment = line.trim_start_matches('#').trim();
+                if comment.starts_with("name:") {
+                    catalog_name = comment.trim_start_matches("name:").trim().to_owned();
+                }
+                continue;
+            }
+            if !found_header {
+                // NOTE: The first non-comment line contains the column names
+                found_header = true;
+                continue;
+            }
+
+            let columns: Vec<&str> = line.split(',').map(|column| column.trim()).collect();
+            if columns.len() != 5 {
+                return Err(Error::resource(
+                    catalog_filepath,
+                    &format!(
+                        "Invalid entry in line {}: expected 5 columns but got {}",
+                        line_index + 1,
+                        columns.len()
+                    ),
+                ));
+            }
+            let parse_channel = |column: &str| {
+                column.parse::<u8>().map_err(|_| {
+                    Error::resource(
+                        catalog_filepath,
+                        &format!(
+                            "Invalid color value '{}' in line {}",
+                            column,
+                            line_index + 1
+                        ),
+                    )
+                })
+            };
+            let color = PixelRGBA::new(
+                parse_channel(columns[2])?,
+                parse_channel(columns[3])?,
+                parse_channel(columns[4])?,
+                255,
+            );
+            entries.push(Floss {
+                catalog_name: String::new(),
+                number: columns[0].to_owned(),
+                name: columns[1].to_owned(),
+                color,
+                color_lab: ColorLab::from_pixelrgba(color),
+            });
+        }
+
+        if entries.is_empty() {
+            return Err(Error::resource(
+                catalog_filepath,
+                "The floss catalog does not contain any entries",
+            ));
+        }
+        for entry in entries.iter_mut() {
+            entry.catalog_name = catalog_name.clone();
+        }
+
+        Ok(FlossCatalog { entries })
+    }
+
+    /// Returns the thread that is perceptually closest to the given color using CIEDE2000
+    pub fn find_closest(&self, color: PixelRGBA) -> &Floss {
+        let color_lab = ColorLab::from_pixelrgba(color);
+        let mut result = &self.entries[0];
+        let mut result_distance = f64::MAX;
+        for entry in &self.entries {
+            let distance = color_lab.distance_ciede2000(entry.color_lab);
+            if distance < result_distance {
+                result = entry;
+                result_distance = distance;
+            }
+        }
+        result
+    }
+
+    /// Replaces every non-transparent pixel by the color of its closest thread. Note that this can
+    /// merge multiple image colors into one.
+    pub fn snap_image_colors(&self, image: &Bitmap) -> Bitmap {
+        let mut snapped_colors: HashMap<PixelRGBA, PixelRGBA> = HashMap::new();
+        let data = image
+            .data
+            .iter()
+            .map(|pixel| {
+                if pixel.a == 0 {
+                    *pixel
+                } else {
+                    *snapped_colors
+                        .entry(*pixel)
+                        .or_insert_with(|| self.find_closest(*pixel).color)
+                }
+            })
+            .collect();
+        Bitmap::new_from_buffer(image.width as u32, image.height as u32, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn floss(number: &str, color: PixelRGBA) -> Floss {
+        Floss {
+            catalog_name: "Test".to_owned(),
+            number: number.to_owned(),
+            name: format!("Color {}", number),
+            color,
+            color_lab: ColorLab::from_pixelrgba(color),
+        }
+    }
+
+    fn test_catalog() -> FlossCatalog {
+        FlossCatalog {
+            entries: vec![
+                floss("1", PixelRGBA::new(250, 250, 250, 255)),
+                floss("2", PixelRGBA::new(200, 20, 30, 255)),
+                floss("3", PixelRGBA::new(20, 40, 180, 255)),
+            ],
+        }
+    }
+
+    #[test]
+    fn from_csv_file_reads_catalog_name_and_entries() {
+        let catalog_filepath = std::env::temp_dir()
+            .join(format!("floss_test_{}.csv", std::process::id()))
+            .to_string_lossy()
+            .to_string();
+        std::fs::write(
+            &catalog_filepath,
+            "# name: Test Floss\n# Some comment\nnumber,name,red,green,blue\n\n\
+             321, Red ,200,20,30\nB5200,Snow White,255,255,255\n",
+        )
+        .unwrap();
+        let catalog = FlossCatalog::from_csv_file(&catalog_filepath).unwrap();
+        std::fs::remove_file(&catalog_filepath).ok();
+
+        assert_eq!(catalog.entries.len(), 2);
+        assert_eq!(catalog.entries[0].display_name(), "Test Floss 321 Red");
+        assert_eq!(catalog.entries[0].color, PixelRGBA::new(200, 20, 30, 255));
+        assert_eq!(catalog.entries[1].number, "B5200");
+        assert_eq!(catalog.entries[1].catalog_name, "Test Floss");
+    }
+
+    #[test]
+    fn find_closest_returns_perceptually_nearest_thread() {
+        let catalog = test_catalog();
+        assert_eq!(
+            catalog
+                .find_closest(PixelRGBA::new(180, 30, 40, 255))
+                .number,
+            "2"
+        );
+        assert_eq!(
+            catalog
+                .find_closest(PixelRGBA::new(30, 30, 150, 255))
+                .number,
+            "3"
+        );
+        assert_eq!(
+            catalog
+                .find_closest(PixelRGBA::new(240, 235, 230, 255))
+                .number,
+            "1"
+        );
+    }
+
+    #[test]
+    fn snap_image_colors_keeps_transparent_pixels() {
+        let catalog = test_catalog();
+        let transparent = PixelRGBA::new(12, 34, 56, 0);
+        let image = Bitmap::new_from_buffer(
+            3,
+            1,
+            vec![
+                PixelRGBA::new(190, 10, 20, 255),
+                transparent,
+                PixelRGBA::new(255, 255, 255, 255),
+            ],
+        );
+        let snapped = catalog.snap_image_colors(&image);
+        assert_eq!(snapped.data[0], PixelRGBA::new(200, 20, 30, 255));
+        assert_eq!(snapped.data[1], transparent);
+        assert_eq!(snapped.data[2], PixelRGBA::new(250, 250, 250, 255));
+    }
+}
@@ -0,0 +1,664 @@
+use cottontail::image::bitmap::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    Gif,
+    Bmp,
+    Tga,
+    Aseprite,
+    /// Open Cross Stitch pattern
+    Oxs,
+}
+
+/// Determines the format of a file by its magic bytes. Returns `None` for unknown formats.
+pub fn detect_image_format(data: &[u8]) -> Option<ImageFormat> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return Some(ImageFormat::Png);
+    }
+    if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        return Some(ImageFormat::Gif);
+    }
+    if data.starts_with(b"BM") && data.len() >= 26 {
+        return Some(ImageFormat::Bmp);
+    }
+    // NOTE: Aseprite files start with their file size followed by the magic number 0xA5E0
+    if data.len() >= 128 && data[4..6] == [0xE0, 0xA5] {
+        return Some(ImageFormat::Aseprite);
+    }
+    let text_start = String::from_utf8_lossy(&data[..data.len().min(256)]);
+    let text_start = text_start.trim_start_matches('\u{feff}').trim_start();
+    if (text_start.starts_with("<?xml") || text_start.starts_with("<chart"))
+        && String::from_utf8_lossy(data).contains("<chart")
+    {
+        return Some(ImageFormat::Oxs);
+    }
+    // NOTE: TGA has no magic number at the beginning. Newer files have a footer signature and
+    //       for older files we check whether the header makes sense.
+    if data.ends_with(b"TRUEVISION-XFILE.\0") || tga_header_is_plausible(data) {
+        return Some(ImageFormat::Tga);
+    }
+    None
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Dimensions
+
+/// Far more pixels than any pixel art needs, but small enough to allocate without trouble
+pub const PIXEL_COUNT_MAX: usize = 8192 * 8192;
+
+/// Returns the number of pixels of an image with the given dimensions. Dimensions that come from
+/// a file are not trusted, so implausibly big images are an error instead of an overflow or a huge
+/// allocation.
+pub fn pixel_count_checked(width: i32, height: i32) -> Result<usize, String> {
+    if width < 0 || height < 0 {
+        return Err(format!("Invalid image dimensions {}x{}", width, height));
+    }
+    (width as usize)
+        .checked_mul(height as usize)
+        .filter(|&pixel_count| pixel_count <= PIXEL_COUNT_MAX)
+        .ok_or_else(|| format!("Image dimensions {}x{} are too big", width, height))
+}
+
+/// Checks the dimensions in the header of a PNG file with `pixel_count_checked`. We don't decode
+/// PNG files ourselves, so this must happen before handing them to the decoder.
+pub fn png_check_dimensions(data: &[u8]) -> Result<(), String> {
+    let mut reader = ByteReader::new(data);
+    reader.seek(12)?;
+    if reader.bytes(4)? != b"IHDR" {
+        return Err("Missing PNG header".to_owned());
+    }
+    let mut read_dimension = || -> Result<i32, String> {
+        let bytes = reader.bytes(4)?;
+        let value = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if value > i32::MAX as u32 {
+            return Err(format!("Invalid PNG dimension {}", value));
+        }
+        Ok(value as i32)
+    };
+    let width = read_dimension()?;
+    let height = read_dimension()?;
+    pixel_count_checked(width, height).map(|_| ())
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Byte reading
+
+/// Little endian reader that returns an error instead of reading past the end of the data
+pub struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn new(data: &'a [u8]) -> ByteReader<'a> {
+        ByteReader { data, pos: 0 }
+    }
+
+    pub fn pos(&self) -> usize {
+        self.pos
+    }
+
+    pub fn seek(&mut self, pos: usize) -> Result<(), String> {
+        if pos > self.data.len() {
+            return Err(format!(
+                "Unexpected end of data: cannot seek to byte {} of {}",
+                pos,
+                self.data.len()
+            ));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    pub fn skip(&mut self, byte_count: usize) -> Result<(), String> {
+        self.seek(self.pos + byte_count)
+    }
+
+    pub fn bytes(&mut self, byte_count: usize) -> Result<&'a [u8], String> {
+        if self.pos + byte_count > self.data.len() {
+            return Err(format!(
+                "Unexpected end of data: cannot read {} bytes at byte {} of {}",
+                byte_count,
+                self.pos,
+                self.data.len()
+            ));
+        }
+        let result = &self.data[self.pos..self.pos + byte_count];
+        self.pos += byte_count;
+        Ok(result)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, String> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub fn i16(&mut self) -> Result<i16, String> {
+        Ok(self.u16()? as i16)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, String> {
+        Ok(self.u32()? as i32)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// BMP
+
+/// Returns the value of the bits selected by the mask scaled to the range 0-255
+fn bitfield_extract(value: u32, mask: u32) -> u8 {
+    if mask == 0 {
+        return 0;
+    }
+    let shift = mask.trailing_zeros();
+    let max = (mask >> shift) as u64;
+    let bits = ((value & mask) >> shift) as u64;
+    ((bits * 255 + max / 2) / max) as u8
+}
+
+/// Decodes uncompressed and bitfield BMP images with 1, 4, 8, 16, 24 or 32 bits per pixel
+pub fn bitmap_create_from_bmp_data(data: &[u8]) -> Result<Bitmap, String> {
+    let mut reader = ByteReader::new(data);
+    if reader.bytes(2)? != b"BM" {
+        return Err("Missing BMP signature".to_owned());
+    }
+    reader.skip(8)?;
+    let pixel_data_offset = reader.u32()? as usize;
+
+    let header_start = reader.pos();
+    let header_size = reader.u32()? as usize;
+    let (width, height, bits_per_pixel, compression, palette_color_count) = if header_size == 12 {
+        // NOTE: This is the old OS/2 BITMAPCOREHEADER
+        let width = reader.u16()? as i32;
+        let height = reader.u16()? as i32;
+        reader.skip(2)?;
+        let bits_per_pixel = reader.u16()?;
+        (width, height, bits_per_pixel, 0, 0)
+    } else if header_size >= 40 {
+        let width = reader.i32()?;
+        let height = reader.i32()?;
+        reader.skip(2)?;
+        let bits_per_pixel = reader.u16()?;
+        let compression = reader.u32()?;
+        reader.skip(12)?;
+        let palette_color_count = reader.u32()? as usize;
+        (
+            width,
+            height,
+            bits_per_pixel,
+            compression,
+            palette_color_count,
+        )
+    } else {
+        return Err(format!("Unsupported BMP header size {}", header_size));
+    };
+
+    // NOTE: A negative height means the rows are stored top-down instead of bottom-up
+    let is_top_down = height < 0;
+    let height = height
+        .checked_abs()
+        .ok_or_else(|| format!("Invalid BMP height {}", height))?;
+    if width <= 0 || height == 0 {
+        return Err(format!("Invalid BMP dimensions {}x{}", width, height));
+    }
+    pixel_count_checked(width, height)?;
+
+    // Color masks
+    const BI_RGB: u32 = 0;
+    const BI_BITFIELDS: u32 = 3;
+    const BI_ALPHABITFIELDS: u32 = 6;
+    let mut masks = match bits_per_pixel {
+        16 => [0x7C00, 0x03E0, 0x001F, 0],
+        _ => [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0],
+    };
+    match compression {
+        BI_RGB => {}
+        BI_BITFIELDS | BI_ALPHABITFIELDS => {
+            reader.seek(header_start + 40)?;
+            masks[0] = reader.u32()?;
+            masks[1] = reader.u32()?;
+            masks[2] = reader.u32()?;
+            if compression == BI_ALPHABITFIELDS || header_size >= 56 {
+                masks[3] = reader.u32()?;
+            }
+        }
+        _ => {
+            return Err(format!(
+                "Unsupported BMP compression method {}. Please save the image uncompressed",
+                compression
+            ))
+        }
+    }
+
+    // Palette
+    let mut palette = Vec::new();
+    if bits_per_pixel <= 8 {
+        let palette_entry_size = if header_size == 12 { 3 } else { 4 };
+        let palette_color_count = if palette_color_count == 0 {
+            1 << bits_per_pixel
+        } else {
+            palette_color_count
+        };
+        let masks_size = if compression == BI_BITFIELDS && header_size == 40 {
+            12
+        } else {
+            0
+        };
+        reader.seek(header_start + header_size + masks_size)?;
+        for _ in 0..palette_color_count {
+            let entry = reader.bytes(palette_entry_size)?;
+            palette.push(PixelRGBA::new(entry[2], entry[1], entry[0], 255));
+        }
+    }
+
+    // Pixels
+    let row_size = (bits_per_pixel as usize * width as usize).div_ceil(32) * 4;
+    let mut result = Bitmap::new_filled(width as u32, height as u32, PixelRGBA::transparent());
+    let mut has_alpha = false;
+    for row in 0..height {
+        reader.seek(pixel_data_offset + row as usize * row_size)?;
+        let row_data = reader.bytes(row_size)?;
+        let y = if is_top_down { row } else { height - 1 - row };
+        for x in 0..width {
+            let color = match bits_per_pixel {
+                1 | 4 | 8 => {
+                    let bit_offset = x as usize * bits_per_pixel as usize;
+                    let byte = row_data[bit_offset / 8];
+                    let shift = 8 - bits_per_pixel as usize - bit_offset % 8;
+                    let index = (byte >> shift) as usize & ((1 << bits_per_pixel) - 1);
+                    *palette.get(index).ok_or_else(|| {
+                        format!("Invalid BMP palette index {} at {}x{}", index, x, y)
+                    })?
+                }
+                16 | 24 | 32 => {
+                    let byte_count = bits_per_pixel as usize / 8;
+                    let offset = x as usize * byte_count;
+                    let mut value = 0u32;
+                    for (byte_index, byte) in
+                        row_data[offset..offset + byte_count].iter().enumerate()
+                    {
+                        value |= (*byte as u32) << (8 * byte_index);
+                    }
+                    let alpha = if masks[3] != 0 {
+                        has_alpha = true;
+                        bitfield_extract(value, masks[3])
+                    } else if bits_per_pixel == 32 && compression == BI_RGB {
+                        // NOTE: Some programs store alpha in the otherwise unused fourth byte
+                        let alpha = (value >> 24) as u8;
+                        has_alpha |= alpha != 0;
+                        alpha
+                    } else {
+                        255
+                    };
+                    PixelRGBA::new(
+                        bitfield_extract(value, masks[0]),
+                        bitfield_extract(value, masks[1]),
+                        bitfield_extract(value, masks[2]),
+                        alpha,
+                    )
+                }
+                _ => {
+                    return Err(format!(
+                        "Unsupported BMP bit depth of {} bits per pixel",
+                        bits_per_pixel
+                    ))
+                }
+            };
+            result.set(x, y, color);
+        }
+    }
+
+    // NOTE: If the unused fourth byte of a 32 bit image is zero everywhere it does not contain
+    //       alpha and the image is meant to be fully opaque
+    if bits_per_pixel == 32 && masks[3] == 0 && !has_alpha {
+        for pixel in result.data.iter_mut() {
+            pixel.a = 255;
+        }
+    }
+
+    Ok(result)
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// TGA
+
+fn tga_header_is_plausible(data: &[u8]) -> bool {
+    if data.len() < 18 {
+        return false;
+    }
+    let color_map_type = data[1];
+    let image_type = data[2];
+    let color_map_entry_size = data[7];
+    let width = u16::from_le_bytes([data[12], data[13]]);
+    let height = u16::from_le_bytes([data[14], data[15]]);
+    let pixel_depth = data[16];
+
+    let image_type_ok = [1, 2, 3, 9, 10, 11].contains(&image_type);
+    let color_map_ok = match color_map_type {
+        0 => true,
+        1 => [15, 16, 24, 32].contains(&color_map_entry_size),
+        _ => false,
+    };
+    let pixel_depth_ok = [8, 15, 16, 24, 32].contains(&pixel_depth);
+    image_type_ok && color_map_ok && pixel_depth_ok && width > 0 && height > 0
+}
+
+fn tga_color_from_bytes(bytes: &[u8], has_alpha: bool) -> PixelRGBA {
+    match bytes.len() {
+        2 => {
+            let value = u16::from_le_bytes([bytes[0], bytes[1]]) as u32;
+            let alpha = if has_alpha && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            PixelRGBA::new(
+                bitfield_extract(value, 0x7C00),
+                bitfield_extract(value, 0x03E0),
+                bitfield_extract(value, 0x001F),
+                alpha,
+            )
+        }
+        3 => PixelRGBA::new(bytes[2], bytes[1], bytes[0], 255),
+        _ => PixelRGBA::new(
+            bytes[2],
+            bytes[1],
+            bytes[0],
+            if has_alpha { bytes[3] } else { 255 },
+        ),
+    }
+}
+
+/// Decodes colormapped, truecolor and grayscale TGA images with or without RLE compression
+pub fn bitmap_create_from_tga_data(data: &[u8]) -> Result<Bitmap, String> {
+    let mut reader = ByteReader::new(data);
+    let id_length = reader.u8()? as usize;
+    let color_map_type = reader.u8()?;
+    let image_type = reader.u8()?;
+    let color_map_first_index = reader.u16()? as usize;
+    let color_map_length = reader.u16()? as usize;
+    let color_map_entry_size = reader.u8()?;
+    reader.skip(4)?;
+    let width = reader.u16()? as i32;
+    let height = reader.u16()? as i32;
+    let pixel_depth = reader.u8()?;
+    let descriptor = reader.u8()?;
+    reader.skip(id_length)?;
+
+    if width == 0 || height == 0 {
+        return Err(format!("Invalid TGA dimensions {}x{}", width, height));
+    }
+    let alpha_bits = descriptor & 0x0F;
+    let is_right_to_left = descriptor & 0x10 != 0;
+    let is_top_down = descriptor & 0x20 != 0;
+
+    // Color map
+    let mut color_map = Vec::new();
+    if color_map_type == 1 {
+        let entry_size = (color_map_entry_size as usize).div_ceil(8);
+        for _ in 0..color_map_length {
+            let entry = reader.bytes(entry_size)?;
+            color_map.push(tga_color_from_bytes(entry, alpha_bits > 0));
+        }
+    }
+
+    let is_rle = image_type >= 9;
+    let bytes_per_pixel = (pixel_depth as usize).div_ceil(8);
+    let decode_pixel = |bytes: &[u8]| -> Result<PixelRGBA, String> {
+        match image_type & 0x07 {
+            1 => {
+                let mut index = 0usize;
+                for (byte_index, byte) in bytes.iter().enumerate() {
+                    index |= (*byte as usize) << (8 * byte_index);
+                }
+                index
+                    .checked_sub(color_map_first_index)
+                    .and_then(|index| color_map.get(index))
+                    .copied()
+                    .ok_or_else(|| format!("Invalid TGA color map index {}", index))
+            }
+            2 => Ok(tga_color_from_bytes(bytes, alpha_bits > 0)),
+            3 => {
+                let alpha = if bytes.len() > 1 && alpha_bits > 0 {
+                    bytes[1]
+                } else {
+                    255
+                };
+                Ok(PixelRGBA::new(bytes[0], bytes[0], bytes[0], alpha))
+            }
+            _ => Err(format!("Unsupported TGA image type {}", image_type)),
+        }
+    };
+
+    // Pixels
+    let pixel_count = pixel_count_checked(width, height)?;
+    let mut pixels = Vec::with_capacity(pixel_count);
+    while pixels.len() < pixel_count {
+        if is_rle {
+            let packet_header = reader.u8()?;
+            let run_length = (packet_header & 0x7F) as usize + 1;
+            if packet_header & 0x80 != 0 {
+                let color = decode_pixel(reader.bytes(bytes_per_pixel)?)?;
+                for _ in 0..run_length {
+                    pixels.push(color);
+                }
+            } else {
+                for _ in 0..run_length {
+                    pixels.push(decode_pixel(reader.bytes(bytes_per_pixel)?)?);
+                }
+            }
+        } else {
+            pixels.push(decode_pixel(reader.bytes(bytes_per_pixel)?)?);
+        }
+    }
+
+    let mut result = Bitmap::new(width as u32, height as u32);
+    for (pixel_index, color) in pixels.into_iter().take(pixel_count).enumerate() {
+        let column = pixel_index as i32 % width;
+        let row = pixel_index as i32 / width;
+        let x = if is_right_to_left {
+            width - 1 - column
+        } else {
+            column
+        };
+        // NOTE: TGA rows are stored bottom-up by default
+        let y = if is_top_down { row } else { height - 1 - row };
+        result.set(x, y, color);
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: PixelRGBA = PixelRGBA::new(255, 0, 0, 255);
+    const GREEN: PixelRGBA = PixelRGBA::new(0, 255, 0, 255);
+    const BLUE: PixelRGBA = PixelRGBA::new(0, 0, 255, 255);
+    const WHITE: PixelRGBA = PixelRGBA::new(255, 255, 255, 255);
+
+    /// Creates a BMP file with a BITMAPINFOHEADER, the given palette and the given rows which
+    /// must already be padded to four bytes
+    fn bmp_data(
+        width: i32,
+        height: i32,
+        bits_per_pixel: u16,
+        palette: &[[u8; 4]],
+        rows: &[&[u8]],
+    ) -> Vec<u8> {
+        let pixel_data_offset = 14 + 40 + 4 * palette.len() as u32;
+        let mut data = Vec::new();
+        data.extend_from_slice(b"BM");
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&pixel_data_offset.to_le_bytes());
+        data.extend_from_slice(&40u32.to_le_bytes());
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&bits_per_pixel.to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+        data.extend_from_slice(&0u32.to_le_bytes());
+        for entry in palette {
+            data.extend_from_slice(entry);
+        }
+        for row in rows {
+            data.extend_from_slice(row);
+        }
+        data
+    }
+
+    fn tga_header(
+        image_type: u8,
+        width: u16,
+        height: u16,
+        pixel_depth: u8,
+        descriptor: u8,
+    ) -> Vec<u8> {
+        let mut data = vec![0, 0, image_type, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        data.extend_from_slice(&width.to_le_bytes());
+        data.extend_from_slice(&height.to_le_bytes());
+        data.push(pixel_depth);
+        data.push(descriptor);
+        data
+    }
+
+    #[test]
+    fn bmp_24_bit_bottom_up() {
+        // NOTE: The first stored row is the bottom row
+        let data = bmp_data(
+            2,
+            2,
+            24,
+            &[],
+            &[
+                &[255, 0, 0, 0, 255, 0, 0, 0],
+                &[0, 0, 255, 255, 255, 255, 0, 0],
+            ],
+        );
+        assert_eq!(detect_image_format(&data), Some(ImageFormat::Bmp));
+
+        let image = bitmap_create_from_bmp_data(&data).unwrap();
+        assert_eq!((image.width, image.height), (2, 2));
+        assert_eq!(image.get(0, 0), RED);
+        assert_eq!(image.get(1, 0), WHITE);
+        assert_eq!(image.get(0, 1), BLUE);
+        assert_eq!(image.get(1, 1), GREEN);
+    }
+
+    #[test]
+    fn bmp_4_bit_palette_top_down() {
+        let data = bmp_data(
+            3,
+            -1,
+            4,
+            &[[0, 0, 255, 0], [255, 0, 0, 0]],
+            &[&[0x01, 0x00, 0x00, 0x00]],
+        );
+        let image = bitmap_create_from_bmp_data(&data).unwrap();
+        assert_eq!((image.width, image.height), (3, 1));
+        assert_eq!(image.get(0, 0), RED);
+        assert_eq!(image.get(1, 0), BLUE);
+        assert_eq!(image.get(2, 0), RED);
+    }
+
+    #[test]
+    fn bmp_32_bit_alpha_is_only_used_if_present() {
+        let opaque = bmp_data(1, 1, 32, &[], &[&[0, 255, 0, 0]]);
+        assert_eq!(
+            bitmap_create_from_bmp_data(&opaque).unwrap().get(0, 0),
+            GREEN
+        );
+
+        let translucent = bmp_data(1, 1, 32, &[], &[&[0, 255, 0, 128]]);
+        assert_eq!(
+            bitmap_create_from_bmp_data(&translucent).unwrap().get(0, 0),
+            PixelRGBA::new(0, 255, 0, 128)
+        );
+    }
+
+    #[test]
+    fn bmp_rejects_broken_files() {
+        let invalid_palette_index = bmp_data(1, 1, 8, &[[0, 0, 0, 0]], &[&[5, 0, 0, 0]]);
+        assert!(bitmap_create_from_bmp_data(&invalid_palette_index).is_err());
+
+        let truncated = bmp_data(2, 2, 24, &[], &[&[255, 0, 0, 0, 255, 0, 0, 0]]);
+        assert!(bitmap_create_from_bmp_data(&truncated).is_err());
+
+        let huge = bmp_data(100_000, 100_000, 24, &[], &[]);
+        assert!(bitmap_create_from_bmp_data(&huge).is_err());
+    }
+
+    #[test]
+    fn tga_truecolor_uncompressed_bottom_up() {
+        let mut data = tga_header(2, 2, 2, 24, 0);
+        data.extend_from_slice(&[255, 0, 0, 0, 255, 0, 0, 0, 255, 255, 255, 255]);
+        assert_eq!(detect_image_format(&data), Some(ImageFormat::Tga));
+
+        let image = bitmap_create_from_tga_data(&data).unwrap();
+        assert_eq!(image.get(0, 1), BLUE);
+        assert_eq!(image.get(1, 1), GREEN);
+        assert_eq!(image.get(0, 0), RED);
+        assert_eq!(image.get(1, 0), WHITE);
+    }
+
+    #[test]
+    fn tga_rle_with_alpha_top_down() {
+        // NOTE: A run of three red pixels followed by one raw transparent pixel
+        let mut data = tga_header(10, 2, 2, 32, 0x28);
+        data.extend_from_slice(&[0x82, 0, 0, 255, 255]);
+        data.extend_from_slice(&[0x00, 0, 0, 0, 0]);
+
+        let image = bitmap_create_from_tga_data(&data).unwrap();
+        assert_eq!(image.get(0, 0), RED);
+        assert_eq!(image.get(1, 0), RED);
+        assert_eq!(image.get(0, 1), RED);
+        assert_eq!(image.get(1, 1), PixelRGBA::transparent());
+    }
+
+    #[test]
+    fn tga_rejects_broken_files() {
+        let mut truncated = tga_header(2, 2, 2, 24, 0);
+        truncated.extend_from_slice(&[255, 0, 0]);
+        assert!(bitmap_create_from_tga_data(&truncated).is_err());
+
+        let mut invalid_color_map_index = tga_header(1, 1, 1, 8, 0);
+        invalid_color_map_index.push(3);
+        assert!(bitmap_create_from_tga_data(&invalid_color_map_index).is_err());
+    }
+
+    #[test]
+    fn png_check_dimensions_reads_the_header() {
+        let png_header = |width: u32, height: u32| {
+            let mut data = b"\x89PNG\r\n\x1a\n\0\0\0\x0dIHDR".to_vec();
+            data.extend_from_slice(&width.to_be_bytes());
+            data.extend_from_slice(&height.to_be_bytes());
+            data.extend_from_slice(&[8, 6, 0, 0, 0]);
+            data
+        };
+        assert_eq!(png_check_dimensions(&png_header(64, 32)), Ok(()));
+        assert!(png_check_dimensions(&png_header(65535, 65535)).is_err());
+        assert!(png_check_dimensions(&png_header(u32::MAX, 1)).is_err());
+        assert!(png_check_dimensions(&png_header(64, 32)[..20]).is_err());
+    }
+
+    #[test]
+    fn pixel_count_checked_rejects_implausible_dimensions() {
+        assert_eq!(pixel_count_checked(3, 4), Ok(12));
+        assert!(pixel_count_checked(-1, 4).is_err());
+        assert!(pixel_count_checked(8193, 8192).is_err());
+        assert!(pixel_count_checked(i32::MAX, i32::MAX).is_err());
+    }
+}
@@ -0,0 +1,2946 @@
+//! Creates cross stitch and paint-by-numbers patterns from pixel art images.
+//!
+//! All bitmaps are created in memory. Loading images and resources as well as writing the
+//! results to disk is up to the caller, see `main_launcher.rs` for an example.
+
+pub mod aseprite;
+pub mod background;
+pub mod backstitch;
+pub mod beads;
+pub mod color_lab;
+pub mod color_quantization;
+pub mod color_regions;
+pub mod error;
+pub mod fabric;
+pub mod floss;
+pub mod image_formats;
+pub mod oxs;
+pub mod pdf;
+pub mod pixel_scale;
+pub mod progress;
+pub mod stitch_kinds;
+pub mod svg;
+pub mod symbol_assignment;
+pub mod symbol_generator;
+pub mod thread_usage;
+mod xml;
+
+use backstitch::{Backstitch, BackstitchInfo};
+use beads::BeadSettings;
+use color_quantization::color_to_hex_string;
+use color_regions::ColorRegions;
+pub use error::{Error, Result};
+use floss::{Floss, FlossCatalog};
+use progress::ProgressMap;
+use stitch_kinds::{StitchKind, StitchKindMap};
+use thread_usage::{ThreadUsage, ThreadUsageSettings};
+
+use cottontail::image::ColorBlendMode;
+use cottontail::image::{bitmap::*, font::*};
+use cottontail::math::*;
+
+use indexmap::IndexMap;
+use rayon::prelude::*;
+
+use std::collections::HashSet;
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Constants
+
+pub const TILE_SIZE_DEFAULT: i32 = 16;
+pub const TILE_SIZE_MIN: i32 = 10;
+pub const ALPHANUMERIC_SYMBOL_CHARS: &str = "123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ";
+pub(crate) const LEGEND_BLOCK_ENTRY_COUNT: usize = 5;
+pub const SPLIT_SEGMENT_WIDTH_DEFAULT: i32 = 60;
+pub const SPLIT_SEGMENT_HEIGHT_DEFAULT: i32 = 80;
+pub(crate) const COLOR_GRID_THIN: PixelRGBA = PixelRGBA::new(128, 128, 128, 255);
+pub(crate) const COLOR_GRID_THICK: PixelRGBA = PixelRGBA::new(64, 64, 64, 255);
+pub(crate) const COLOR_OVERLAP_SHADE: PixelRGBA = PixelRGBA::new(160, 160, 160, 255);
+pub(crate) const COLOR_FRACTIONAL_STITCH_UNCOLORIZED: PixelRGBA =
+    PixelRGBA::new(200, 200, 200, 255);
+pub(crate) const COLOR_REGION_OUTLINE: PixelRGBA = PixelRGBA::new(64, 64, 64, 255);
+pub(crate) const COLOR_PROGRESS_HATCH: PixelRGBA = PixelRGBA::new(64, 64, 64, 255);
+const COLOR_BEAD_PREVIEW_TABLE: PixelRGBA = PixelRGBA::new(245, 245, 245, 255);
+const COLOR_PEGBOARD: PixelRGBA = PixelRGBA::new(222, 222, 228, 255);
+const COLOR_PEGBOARD_BORDER: PixelRGBA = PixelRGBA::new(180, 180, 190, 255);
+const COLOR_PEGBOARD_PEG: PixelRGBA = PixelRGBA::new(200, 200, 208, 255);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternType {
+    BlackAndWhite,
+    Colorized,
+    ColorizedNoSymbols,
+    PaintByNumbers,
+    /// Paint by numbers with outlined same-color areas and one label per area instead of a label
+    /// in every cell
+    PaintByNumbersRegions,
+}
+
+impl PatternType {
+    pub fn filename_prefix(self) -> &'static str {
+        match self {
+            PatternType::BlackAndWhite => "cross_stitch",
+            PatternType::Colorized => "cross_stitch_colorized",
+            PatternType::ColorizedNoSymbols => "cross_stitch_colorized_no_symbols",
+            PatternType::PaintByNumbers => "paint_by_numbers",
+            PatternType::PaintByNumbersRegions => "paint_by_numbers_regions",
+        }
+    }
+
+    /// Paint by numbers patterns have neither a 10x10 grid nor origin bars and their labels have no
+    /// outline
+    pub fn is_paint_by_numbers(self) -> bool {
+        self == PatternType::PaintByNumbers || self == PatternType::PaintByNumbersRegions
+    }
+
+    /// Example: "cross_stitch_colorized_segment_3" or "paint_by_numbers_complete"
+    pub fn filename_suffix(self, segment_index: Option<usize>) -> String {
+        if let Some(segment_index) = segment_index {
+            format!("{}_segment_{}", self.filename_prefix(), segment_index)
+        } else {
+            format!("{}_complete", self.filename_prefix())
+        }
+    }
+}
+
+pub struct PatternPage {
+    pub pattern_type: PatternType,
+    /// This is `None` for the complete pattern
+    pub segment_index: Option<usize>,
+    pub bitmap: Bitmap,
+}
+
+impl PatternPage {
+    pub fn filename_suffix(&self) -> String {
+        self.pattern_type.filename_suffix(self.segment_index)
+    }
+}
+
+/// The legend and pattern pages of one image
+pub struct PatternSet {
+    pub legend: Bitmap,
+    /// The complete patterns followed by the patterns of the individual segments if there is more
+    /// than one segment
+    pub pages: Vec<PatternPage>,
+}
+
+/// Photo-like previews of the stitched image on aida fabric or of the beads on pegboards
+pub struct Preview {
+    pub background: Bitmap,
+    pub stitches: Bitmap,
+    /// Stitches on top of the background
+    pub combined: Bitmap,
+}
+
+/// One frame of an input image together with its fractional stitches, backstitches and the cells
+/// that are already stitched
+pub struct ImageFrame {
+    pub image: Bitmap,
+    pub stitch_kinds: StitchKindMap,
+    pub backstitches: Vec<Backstitch>,
+    pub progress: ProgressMap,
+}
+
+/// Everything `create_patterns` makes for one image. The pattern sets and the preview are only
+/// there if they were enabled in the `PatternOptions`.
+pub struct Patterns {
+    pub color_mappings: IndexMap<PixelRGBA, ColorInfo>,
+    pub backstitch_infos: IndexMap<PixelRGBA, BackstitchInfo>,
+    pub patterns: Option<PatternSet>,
+    pub patterns_centered: Option<PatternSet>,
+    pub preview: Option<Preview>,
+}
+
+/// The part of an image that a pattern page shows together with everything that is drawn on top
+/// of its cells. This is the same for all pattern types of a page.
+#[derive(Clone, Copy)]
+pub struct PatternRenderContext<'a> {
+    pub bitmap: &'a Bitmap,
+    pub stitch_kinds: &'a StitchKindMap,
+    /// NOTE: The backstitches are relative to the top-left corner of the bitmap
+    pub backstitches: &'a [Backstitch],
+    pub progress: &'a ProgressMap,
+    pub color_mappings: &'a IndexMap<PixelRGBA, ColorInfo>,
+    pub fabric_color: PixelRGBA,
+    /// This is `None` for the complete pattern
+    pub segment_index: Option<usize>,
+    /// Coordinate of the top-left stitch relative to the coordinate origin
+    pub logical_first_coordinate: Vec2i,
+    /// Number of leading stitch columns and rows that are repeated from the previous segments
+    pub overlap_left: i32,
+    pub overlap_top: i32,
+    pub add_origin_grid_bars: bool,
+}
+
+impl<'a> PatternRenderContext<'a> {
+    /// Returns the context of the complete pattern of an image. Centered patterns have their
+    /// coordinate origin in the center of the image and show it with grid bars.
+    pub fn new_complete(
+        image: &'a Bitmap,
+        stitch_kinds: &'a StitchKindMap,
+        backstitches: &'a [Backstitch],
+        progress: &'a ProgressMap,
+        color_mappings: &'a IndexMap<PixelRGBA, ColorInfo>,
+        fabric_color: PixelRGBA,
+        centered: bool,
+    ) -> PatternRenderContext<'a> {
+        let origin = pattern_origin(image, centered);
+        PatternRenderContext {
+            bitmap: image,
+            stitch_kinds,
+            backstitches,
+            progress,
+            color_mappings,
+            fabric_color,
+            segment_index: None,
+            logical_first_coordinate: Vec2i::new(-origin.x, -origin.y),
+            overlap_left: 0,
+            overlap_top: 0,
+            add_origin_grid_bars: centered,
+        }
+    }
+
+    /// Returns the context of the given segment of our image. The segment index starts at 0.
+    pub(crate) fn for_segment(
+        &self,
+        segment: &'a ImageSegment,
+        segment_index: usize,
+    ) -> PatternRenderContext<'a> {
+        PatternRenderContext {
+            bitmap: &segment.bitmap,
+            stitch_kinds: &segment.stitch_kinds,
+            backstitches: &segment.backstitches,
+            progress: &segment.progress,
+            color_mappings: self.color_mappings,
+            fabric_color: self.fabric_color,
+            segment_index: Some(segment_index + 1),
+            logical_first_coordinate: self.logical_first_coordinate + segment.first_coordinate,
+            overlap_left: segment.overlap_left,
+            overlap_top: segment.overlap_top,
+            add_origin_grid_bars: self.add_origin_grid_bars,
+        }
+    }
+
+    /// Returns an error if our layers or the given options don't fit together
+    pub(crate) fn check(&self, options: &PatternOptions) -> Result<()> {
+        options.validate()?;
+        check_layer_dimensions(self.bitmap, self.stitch_kinds, Some(self.progress))
+    }
+
+    /// Cuts our image into the pattern parts of the given options
+    pub(crate) fn segments(&self, options: &PatternOptions) -> Vec<ImageSegment> {
+        image_to_segments_for_options(
+            self.bitmap,
+            self.stitch_kinds,
+            self.backstitches,
+            self.progress,
+            options,
+        )
+    }
+}
+
+pub struct Resources {
+    pub tile_size: i32,
+    pub font: BitmapFont,
+    pub font_big: BitmapFont,
+    /// NOTE: All symbols are `tile_size`x`tile_size`
+    pub symbols: Vec<Bitmap>,
+    /// The first `symbols_provided_count` symbols were given to `Resources::new`, the others are
+    /// generated
+    pub symbols_provided_count: usize,
+    /// How different every pair of `symbols` looks, see `symbol_assignment`
+    pub symbol_dissimilarities: Vec<Vec<f64>>,
+    pub symbols_alphanum: Vec<Bitmap>,
+    pub stitch_images_premultiplied_alpha: Vec<Bitmap>,
+    pub stitch_images_luminance_premultiplied_alpha: Vec<Bitmap>,
+    pub stitch_background_image_8x8_premultiplied_alpha: Bitmap,
+}
+
+impl Resources {
+    /// Takes the symbol images and the stitch preview images (i.e. from the `resources` dir) with
+    /// straight alpha. The symbols are scaled to `tile_size` if necessary and followed by generated
+    /// symbols for when they run out. The stitch images and their luminance images are expected
+    /// to be in matching order.
+    pub fn new(
+        tile_size: i32,
+        symbols: Vec<Bitmap>,
+        stitch_images: Vec<Bitmap>,
+        stitch_images_luminance: Vec<Bitmap>,
+        stitch_background_image_8x8: Bitmap,
+    ) -> Result<Resources> {
+        if tile_size < TILE_SIZE_MIN {
+            return Err(Error::invalid_input(format!(
+                "Tile size {} is smaller than the minimum of {}",
+                tile_size, TILE_SIZE_MIN
+            )));
+        }
+        if stitch_images.len() != stitch_images_luminance.len() {
+            return Err(Error::invalid_input(format!(
+                "Got {} stitch images but {} stitch luminance images",
+                stitch_images.len(),
+                stitch_images_luminance.len()
+            )));
+        }
+
+        let (font, font_big) = load_fonts();
+        let symbols_provided_count = symbols.len();
+        let mut symbols: Vec<Bitmap> = symbols
+            .into_iter()
+            .map(|symbol| {
+                if symbol.width == tile_size && symbol.height == tile_size {
+                    symbol
+                } else {
+                    bitmap_scaled_nearest_neighbor(&symbol, tile_size, tile_size)
+                }
+            })
+            .collect();
+        let symbols_generated = symbol_generator::generate_symbols(&font, tile_size, &symbols);
+        symbols.extend(symbols_generated);
+        let symbol_dissimilarities = symbol_assignment::symbol_dissimilarities(&symbols);
+        let symbols_alphanum = create_alphanumeric_symbols(&font, tile_size, symbols.len());
+
+        Ok(Resources {
+            tile_size,
+            symbols,
+            symbols_provided_count,
+            symbol_dissimilarities,
+            symbols_alphanum,
+            stitch_images_premultiplied_alpha: stitch_images
+                .iter()
+                .map(|image| image.to_premultiplied_alpha())
+                .collect(),
+            stitch_images_luminance_premultiplied_alpha: stitch_images_luminance
+                .iter()
+                .map(|image| image.to_premultiplied_alpha())
+                .collect(),
+            stitch_background_image_8x8_premultiplied_alpha: stitch_background_image_8x8
+                .to_premultiplied_alpha(),
+            font,
+            font_big,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct ColorInfo {
+    pub color: PixelRGBA,
+    /// Number of stitches of any kind
+    pub count: usize,
+    pub count_half: usize,
+    pub count_quarter: usize,
+    pub count_three_quarter: usize,
+    /// Number of stitches of any kind that are already stitched according to the progress mask
+    pub count_completed: usize,
+    /// Index into the symbols
+    pub symbol_index: usize,
+    /// Index into the paint-by-numbers labels. This is the legend position of the color in the
+    /// palette that all frames share, so a color keeps its label on frames that lack other colors.
+    pub label_index: usize,
+    pub symbol: Bitmap,
+    pub symbol_alphanum: Bitmap,
+    pub stitches_premultiplied: Vec<Bitmap>,
+    /// In bead mode this is the closest bead of a bead catalog
+    pub floss: Option<Floss>,
+}
+
+impl ColorInfo {
+    pub fn count_full(&self) -> usize {
+        self.count - self.count_half - self.count_quarter - self.count_three_quarter
+    }
+
+    /// The number of full stitches that need as much thread as all our stitches
+    pub fn count_full_equivalent(&self) -> f64 {
+        // NOTE: Fractional stitches need thread for two, one or three of the four legs of a full
+        //       cross stitch
+        self.count_full() as f64
+            + 0.5 * self.count_half as f64
+            + 0.25 * self.count_quarter as f64
+            + 0.75 * self.count_three_quarter as f64
+    }
+
+    pub fn has_fractional_stitches(&self) -> bool {
+        self.count_half + self.count_quarter + self.count_three_quarter > 0
+    }
+
+    pub fn count_remaining(&self) -> usize {
+        self.count - self.count_completed
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PatternOptions {
+    pub segment_width: i32,
+    pub segment_height: i32,
+    /// Number of stitch rows and columns that are repeated from the previous pattern part
+    pub segment_overlap: i32,
+    pub thread_usage: ThreadUsageSettings,
+    /// Fabric added on every side of the design for the cut sizes in the legend
+    pub fabric_margin_centimeters: i32,
+    /// Shows through the unstitched cells of colorized patterns and tints the aida of the preview
+    pub fabric_color: PixelRGBA,
+    /// Creates fusible bead patterns instead of cross stitch patterns. The segments then have the
+    /// size of the pegboards and the legend counts beads and bags instead of thread.
+    pub beads: Option<BeadSettings>,
+    /// Which parts of `Patterns` we create
+    pub create_patterns: bool,
+    pub create_patterns_centered: bool,
+    pub create_preview: bool,
+}
+
+impl Default for PatternOptions {
+    fn default() -> PatternOptions {
+        PatternOptions {
+            segment_width: SPLIT_SEGMENT_WIDTH_DEFAULT,
+            segment_height: SPLIT_SEGMENT_HEIGHT_DEFAULT,
+            segment_overlap: 0,
+            thread_usage: ThreadUsageSettings::default(),
+            fabric_margin_centimeters: fabric::FABRIC_MARGIN_CENTIMETERS_DEFAULT,
+            fabric_color: fabric::FABRIC_COLOR_DEFAULT,
+            beads: None,
+            create_patterns: true,
+            create_patterns_centered: true,
+            create_preview: true,
+        }
+    }
+}
+
+impl PatternOptions {
+    /// Returns an error if we can't cut patterns into segments with these options
+    pub fn validate(&self) -> Result<()> {
+        if self.segment_width < 1 || self.segment_height < 1 {
+            return Err(Error::invalid_input(format!(
+                "Segment size {}x{} is too small",
+                self.segment_width, self.segment_height
+            )));
+        }
+        if self.segment_overlap < 0
+            || self.segment_overlap >= self.segment_width
+            || self.segment_overlap >= self.segment_height
+        {
+            return Err(Error::invalid_input(format!(
+                "Segment overlap {} must be at least 0 and smaller than the segment size {}x{}",
+                self.segment_overlap, self.segment_width, self.segment_height
+            )));
+        }
+        if let Some(bead_settings) = &self.beads {
+            if bead_settings.pegboard_width < 1 || bead_settings.pegboard_height < 1 {
+                return Err(Error::invalid_input(format!(
+                    "Pegboard size {}x{} is too small",
+                    bead_settings.pegboard_width, bead_settings.pegboard_height
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Creating patterns
+
+/// Creates the color mappings, all patterns and the preview of the given image frame in one go.
+/// Transparent pixels are left unstitched. If a floss catalog is given every color gets its
+/// closest floss. Predefined color mappings keep the floss and symbols of their colors, i.e. of an
+/// imported pattern or of other frames of the same image.
+///
+/// NOTE: Use `StitchKindMap::new_full` if the image has no fractional stitches and
+///       `ProgressMap::new_empty` if nothing is stitched yet. In bead mode the preview shows beads
+///       on pegboards.
+pub fn create_patterns(
+    frame: &ImageFrame,
+    resources: &Resources,
+    floss_catalog: Option<&FlossCatalog>,
+    predefined_color_mappings: Option<&IndexMap<PixelRGBA, ColorInfo>>,
+    options: &PatternOptions,
+) -> Result<Patterns> {
+    let ImageFrame {
+        image,
+        stitch_kinds,
+        backstitches,
+        progress,
+    } = frame;
+    options.validate()?;
+    check_layer_dimensions(image, stitch_kinds, Some(progress))?;
+
+    let mut color_mappings = create_color_mappings_from_image(
+        image,
+        stitch_kinds,
+        resources,
+        floss_catalog,
+        predefined_color_mappings,
+    )?;
+    color_mappings_count_completed(&mut color_mappings, image, progress);
+    let backstitch_infos = backstitch::create_backstitch_infos(backstitches, floss_catalog);
+
+    let context = |centered: bool| {
+        PatternRenderContext::new_complete(
+            image,
+            stitch_kinds,
+            backstitches,
+            progress,
+            &color_mappings,
+            options.fabric_color,
+            centered,
+        )
+    };
+    let mut patterns = None;
+    let mut patterns_centered = None;
+    let mut preview = None;
+    rayon::scope(|scope| {
+        if options.create_patterns {
+            scope.spawn(|_| {
+                // NOTE: We can unwrap here because we checked the options and dimensions above
+                patterns = Some(
+                    create_pattern_set(&context(false), resources, &backstitch_infos, options)
+                        .unwrap(),
+                );
+            });
+        }
+        if options.create_patterns_centered {
+            scope.spawn(|_| {
+                // NOTE: We can unwrap here because we checked the options and dimensions above
+                patterns_centered = Some(
+                    create_pattern_set(&context(true), resources, &backstitch_infos, options)
+                        .unwrap(),
+                );
+            });
+        }
+        if options.create_preview {
+            scope.spawn(|_| {
+                preview = Some(if let Some(bead_settings) = &options.beads {
+                    create_bead_preview(image, resources, bead_settings)
+                } else {
+                    create_preview(
+                        image,
+                        stitch_kinds,
+                        backstitches,
+                        resources,
+                        &color_mappings,
+                        options.fabric_color,
+                    )
+                    // NOTE: We can unwrap here because we checked the dimensions above
+                    .unwrap()
+                });
+            });
+        }
+    });
+
+    Ok(Patterns {
+        color_mappings,
+        backstitch_infos,
+        patterns,
+        patterns_centered,
+        preview,
+    })
+}
+
+/// Returns an error if the fractional stitches or the progress don't have the size of the image
+fn check_layer_dimensions(
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    progress: Option<&ProgressMap>,
+) -> Result<()> {
+    if stitch_kinds.width != image.width || stitch_kinds.height != image.height {
+        return Err(Error::invalid_input(format!(
+            "The fractional stitches are {}x{} but the image is {}x{}",
+            stitch_kinds.width, stitch_kinds.height, image.width, image.height
+        )));
+    }
+    if let Some(progress) = progress {
+        if progress.width != image.width || progress.height != image.height {
+            return Err(Error::invalid_input(format!(
+                "The progress is {}x{} but the image is {}x{}",
+                progress.width, progress.height, image.width, image.height
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// Creates the legend and all pattern pages for the image of the given complete pattern context.
+/// If the image does not fit into a single segment we additionally create patterns for every
+/// segment.
+pub fn create_pattern_set(
+    context: &PatternRenderContext,
+    resources: &Resources,
+    backstitch_infos: &IndexMap<PixelRGBA, BackstitchInfo>,
+    options: &PatternOptions,
+) -> Result<PatternSet> {
+    context.check(options)?;
+    let segments = context.segments(options);
+    let segment_layout_indices: Vec<Vec2i> = segments
+        .iter()
+        .map(|segment| segment.layout_index)
+        .collect();
+
+    let mut legend = None;
+    let mut pages_complete = Vec::new();
+    let mut pages_segments = Vec::new();
+    rayon::scope(|scope| {
+        // Legend
+        scope.spawn(|_| {
+            legend = Some(create_cross_stitch_legend(
+                context.bitmap.dim(),
+                context.color_mappings,
+                backstitch_infos,
+                &resources.font,
+                resources.tile_size,
+                &segment_layout_indices,
+                options,
+            ));
+        });
+
+        // Create patterns for complete set
+        scope.spawn(|_| {
+            pages_complete = create_cross_stitch_pattern_set(context, resources, true);
+        });
+
+        // Create patterns for individual segments if needed
+        if segments.len() > 1 {
+            pages_segments = segments
+                .par_iter()
+                .enumerate()
+                .map(|(segment_index, segment)| {
+                    create_cross_stitch_pattern_set(
+                        &context.for_segment(segment, segment_index),
+                        resources,
+                        false,
+                    )
+                })
+                .collect::<Vec<Vec<PatternPage>>>()
+                .into_iter()
+                .flatten()
+                .collect();
+        }
+    });
+
+    pages_complete.extend(pages_segments);
+    Ok(PatternSet {
+        // NOTE: We can unwrap here because the scope above is guaranteed to have finished
+        legend: legend.unwrap(),
+        pages: pages_complete,
+    })
+}
+
+/// Returns the position of the coordinate origin in the image
+fn pattern_origin(image: &Bitmap, centered: bool) -> Vec2i {
+    if centered {
+        Vec2i::new(
+            make_even_upwards(image.width) / 2,
+            make_even_upwards(image.height) / 2,
+        )
+    } else {
+        Vec2i::zero()
+    }
+}
+
+/// Cuts the image into the pattern parts of the given options
+fn image_to_segments_for_options(
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    backstitches: &[Backstitch],
+    progress: &ProgressMap,
+    options: &PatternOptions,
+) -> Vec<ImageSegment> {
+    // NOTE: Every pegboard is ironed on its own, so bead patterns have one segment per pegboard
+    //       and no overlap
+    let (segment_width, segment_height, segment_overlap) = match &options.beads {
+        Some(bead_settings) => (
+            bead_settings.pegboard_width,
+            bead_settings.pegboard_height,
+            0,
+        ),
+        None => (
+            options.segment_width,
+            options.segment_height,
+            options.segment_overlap,
+        ),
+    };
+    image_to_segments_with_overlap(
+        image,
+        stitch_kinds,
+        backstitches,
+        progress,
+        segment_width,
+        segment_height,
+        segment_overlap,
+    )
+}
+
+/// Creates a preview of how the finished stitched image looks like on aida fabric of the given
+/// color
+pub fn create_preview(
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    backstitches: &[Backstitch],
+    resources: &Resources,
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    fabric_color: PixelRGBA,
+) -> Result<Preview> {
+    check_layer_dimensions(image, stitch_kinds, None)?;
+
+    let preview_padding = 10;
+    let bitmap = image.extended(
+        preview_padding,
+        preview_padding,
+        preview_padding,
+        preview_padding,
+        PixelRGBA::transparent(),
+    );
+    let tile_width = resources
+        .stitch_background_image_8x8_premultiplied_alpha
+        .width
+        / 8;
+    let tile_height = resources
+        .stitch_background_image_8x8_premultiplied_alpha
+        .height
+        / 8;
+
+    // Background only
+    let background_tile = if fabric_color == fabric::FABRIC_COLOR_DEFAULT {
+        resources
+            .stitch_background_image_8x8_premultiplied_alpha
+            .clone()
+    } else {
+        bitmap_tinted(
+            &resources
+                .stitch_background_image_8x8_premultiplied_alpha
+                .to_unpremultiplied_alpha(),
+            fabric_color,
+        )
+        .to_premultiplied_alpha()
+    };
+    let mut background_layer = Bitmap::new(
+        (tile_width * bitmap.width) as u32,
+        (tile_height * bitmap.height) as u32,
+    );
+    for y in 0..=bitmap.height / 8 {
+        for x in 0..=bitmap.width / 8 {
+            let pos = Vec2i::new(background_tile.width * x, background_tile.height * y);
+            background_tile.blit_to(&mut background_layer, pos, true);
+        }
+    }
+
+    // Stitches only
+    let mut colored_stitches_layer = Bitmap::new(
+        (tile_width * bitmap.width) as u32,
+        (tile_height * bitmap.height) as u32,
+    );
+
+    let mut random = Random::new_from_seed(1234);
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let color = bitmap.get(x, y);
+
+            // Add stitch
+            if color.a != 0 {
+                let tile_pos_center =
+                    Vec2i::new(tile_width * x, tile_height * y) + (tile_width / 2);
+                let stitches = &color_mappings.get(&color).unwrap().stitches_premultiplied;
+                let stitches_count = stitches.len();
+                let stitch =
+                    &stitches[random.u32_bounded_exclusive(stitches_count as u32) as usize];
+                let stitch_center = Vec2i::new(stitch.width / 2, stitch.height / 2);
+
+                // NOTE: Fractional stitches only keep the parts of the stitch image that belong
+                //       to their legs
+                let stitch_kind = stitch_kinds.get(x - preview_padding, y - preview_padding);
+                let stitch_fractional;
+                let stitch = if stitch_kind.is_fractional() {
+                    let mut masked = stitch.clone();
+                    for stitch_y in 0..stitch.height {
+                        for stitch_x in 0..stitch.width {
+                            if !stitch_kind.covers_pixel(
+                                stitch_x,
+                                stitch_y,
+                                stitch.width,
+                                stitch.height,
+                                None,
+                            ) {
+                                masked.set(stitch_x, stitch_y, PixelRGBA::transparent());
+                            }
+                        }
+                    }
+                    stitch_fractional = masked;
+                    &stitch_fractional
+                } else {
+                    stitch
+                };
+                stitch.blit_to_alpha_blended_premultiplied(
+                    &mut colored_stitches_layer,
+                    tile_pos_center - stitch_center,
+                    true,
+                    ColorBlendMode::Normal,
+                );
+            }
+        }
+    }
+
+    // Backstitches on top of the stitches
+    let backstitch_thickness = i32::max(2, tile_width / 4);
+    for backstitch in backstitches {
+        let start = Vec2i::new(
+            tile_width * (backstitch.start.x + preview_padding),
+            tile_height * (backstitch.start.y + preview_padding),
+        );
+        let end = Vec2i::new(
+            tile_width * (backstitch.end.x + preview_padding),
+            tile_height * (backstitch.end.y + preview_padding),
+        );
+        let color_shadow = PixelRGBA::new(
+            (backstitch.color.r as u32 * 3 / 5) as u8,
+            (backstitch.color.g as u32 * 3 / 5) as u8,
+            (backstitch.color.b as u32 * 3 / 5) as u8,
+            255,
+        );
+        bitmap_draw_line_thick(
+            &mut colored_stitches_layer,
+            start,
+            end,
+            backstitch_thickness,
+            color_shadow,
+        );
+        bitmap_draw_line_thick(
+            &mut colored_stitches_layer,
+            start,
+            end,
+            backstitch_thickness - 1,
+            backstitch.color,
+        );
+    }
+
+    // Combined
+    let mut combined = background_layer.clone();
+    colored_stitches_layer.blit_to_alpha_blended_premultiplied(
+        &mut combined,
+        Vec2i::zero(),
+        false,
+        ColorBlendMode::Normal,
+    );
+
+    Ok(Preview {
+        background: background_layer,
+        stitches: colored_stitches_layer.to_unpremultiplied_alpha(),
+        combined,
+    })
+}
+
+/// Creates a preview of how the finished image looks like as ironed-on fusible beads. The image
+/// is laid out on as many pegboards as needed, starting at the top-left pegboard.
+pub fn create_bead_preview(
+    image: &Bitmap,
+    resources: &Resources,
+    bead_settings: &BeadSettings,
+) -> Preview {
+    let preview_padding = 2;
+    let bead_size = resources.tile_size;
+    let pegboard_columns =
+        (image.width + bead_settings.pegboard_width - 1) / bead_settings.pegboard_width;
+    let pegboard_rows =
+        (image.height + bead_settings.pegboard_height - 1) / bead_settings.pegboard_height;
+    let peg_count_x = pegboard_columns * bead_settings.pegboard_width;
+    let peg_count_y = pegboard_rows * bead_settings.pegboard_height;
+    let width = bead_size * (peg_count_x + 2 * preview_padding);
+    let height = bead_size * (peg_count_y + 2 * preview_padding);
+
+    // NOTE: The bead shape is the same for every bead, so we only compute it once. It is a ring
+    //       with a hole in the middle that is lit from the top-left.
+    let bead_shape: Vec<Option<f32>> = {
+        let center = (bead_size - 1) as f32 / 2.0;
+        let radius_outer = bead_size as f32 / 2.0 - 0.5;
+        let radius_hole = bead_size as f32 / 5.0;
+        let mut shape = Vec::new();
+        for y in 0..bead_size {
+            for x in 0..bead_size {
+                let delta_x = x as f32 - center;
+                let delta_y = y as f32 - center;
+                let distance = f32::sqrt(delta_x * delta_x + delta_y * delta_y);
+                if radius_hole <= distance && distance <= radius_outer {
+                    let lighting = -(delta_x + delta_y) / (std::f32::consts::SQRT_2 * radius_outer);
+                    shape.push(Some(lighting));
+                } else {
+                    shape.push(None);
+                }
+            }
+        }
+        shape
+    };
+    let peg_radius = i32::max(1, bead_size / 6);
+
+    // Pegboards only
+    let mut background_layer =
+        Bitmap::new_filled(width as u32, height as u32, COLOR_BEAD_PREVIEW_TABLE);
+    for pegboard_y in 0..pegboard_rows {
+        for pegboard_x in 0..pegboard_columns {
+            let pos = Vec2i::new(
+                bead_size * (preview_padding + pegboard_x * bead_settings.pegboard_width),
+                bead_size * (preview_padding + pegboard_y * bead_settings.pegboard_height),
+            );
+            let pegboard_width = bead_size * bead_settings.pegboard_width;
+            let pegboard_height = bead_size * bead_settings.pegboard_height;
+            background_layer.draw_rect_filled(
+                pos.x,
+                pos.y,
+                pegboard_width,
+                pegboard_height,
+                COLOR_PEGBOARD,
+            );
+            background_layer.draw_rect(
+                pos.x,
+                pos.y,
+                pegboard_width,
+                pegboard_height,
+                COLOR_PEGBOARD_BORDER,
+            );
+        }
+    }
+    for peg_y in 0..peg_count_y {
+        for peg_x in 0..peg_count_x {
+            let center = Vec2i::new(
+                bead_size * (preview_padding + peg_x) + bead_size / 2,
+                bead_size * (preview_padding + peg_y) + bead_size / 2,
+            );
+            for y in -peg_radius..=peg_radius {
+                for x in -peg_radius..=peg_radius {
+                    if x * x + y * y <= peg_radius * peg_radius {
+                        background_layer.set(center.x + x, center.y + y, COLOR_PEGBOARD_PEG);
+                    }
+                }
+            }
+        }
+    }
+
+    // Beads only
+    let mut beads_layer = Bitmap::new(width as u32, height as u32);
+    let mut combined = background_layer.clone();
+    for bead_y in 0..image.height {
+        for bead_x in 0..image.width {
+            let color = image.get(bead_x, bead_y);
+            if color.a == 0 {
+                continue;
+            }
+
+            let pos = Vec2i::new(
+                bead_size * (preview_padding + bead_x),
+                bead_size * (preview_padding + bead_y),
+            );
+            for y in 0..bead_size {
+                for x in 0..bead_size {
+                    if let Some(lighting) = bead_shape[(x + y * bead_size) as usize] {
+                        // NOTE: We brighten towards white instead of scaling up so that white
+                        //       beads get shaded too
+                        let shade = |channel: u8| -> u8 {
+                            let channel = channel as f32;
+                            let shaded = if lighting > 0.0 {
+                                channel + (255.0 - channel) * 0.4 * lighting
+                            } else {
+                                channel * (1.0 + 0.35 * lighting)
+                            };
+                            shaded.round().clamp(0.0, 255.0) as u8
+                        };
+                        let bead_color =
+                            PixelRGBA::new(shade(color.r), shade(color.g), shade(color.b), 255);
+                        beads_layer.set(pos.x + x, pos.y + y, bead_color);
+                        combined.set(pos.x + x, pos.y + y, bead_color);
+                    }
+                }
+            }
+        }
+    }
+
+    Preview {
+        background: background_layer,
+        stitches: beads_layer,
+        combined,
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Loading resources
+
+pub fn load_fonts() -> (BitmapFont, BitmapFont) {
+    let mut font_regular = BitmapFont::new(
+        FONT_DEFAULT_TINY_NAME,
+        FONT_DEFAULT_TINY_TTF,
+        FONT_DEFAULT_TINY_PIXEL_HEIGHT,
+        FONT_DEFAULT_TINY_RASTER_OFFSET,
+        0,
+        0,
+        PixelRGBA::black(),
+        PixelRGBA::transparent(),
+    );
+    let mut font_big = BitmapFont::new(
+        FONT_DEFAULT_REGULAR_NAME,
+        FONT_DEFAULT_REGULAR_TTF,
+        2 * FONT_DEFAULT_REGULAR_PIXEL_HEIGHT,
+        FONT_DEFAULT_REGULAR_RASTER_OFFSET,
+        0,
+        0,
+        PixelRGBA::black(),
+        PixelRGBA::transparent(),
+    );
+
+    // NOTE: Because 0 looks like an 8 in this font on crappy printers we replace it with an O (big o)
+    let regular_o = font_regular
+        .glyphs
+        .get(&('O' as Codepoint))
+        .unwrap()
+        .clone();
+    let big_o = font_big.glyphs.get(&('O' as Codepoint)).unwrap().clone();
+    font_regular.glyphs.insert('0' as Codepoint, regular_o);
+    font_big.glyphs.insert('0' as Codepoint, big_o);
+
+    (font_regular, font_big)
+}
+
+/// Returns the paint-by-numbers label of a symbol. The first labels are the single
+/// `ALPHANUMERIC_SYMBOL_CHARS`, followed by all pairs of them, then all triples and so on. This way
+/// every symbol gets its own label.
+pub fn alphanumeric_symbol_label(symbol_index: usize) -> String {
+    let chars: Vec<char> = ALPHANUMERIC_SYMBOL_CHARS.chars().collect();
+    let mut label_length = 1;
+    let mut label_count = chars.len();
+    let mut index = symbol_index;
+    while index >= label_count {
+        index -= label_count;
+        label_length += 1;
+        label_count *= chars.len();
+    }
+
+    let mut label_chars = vec![chars[0]; label_length];
+    for label_char in label_chars.iter_mut().rev() {
+        *label_char = chars[index % chars.len()];
+        index /= chars.len();
+    }
+    label_chars.into_iter().collect()
+}
+
+/// The inverse of `alphanumeric_symbol_label`
+pub fn alphanumeric_symbol_label_to_index(label: &str) -> Option<usize> {
+    let chars: Vec<char> = ALPHANUMERIC_SYMBOL_CHARS.chars().collect();
+    if label.is_empty() {
+        return None;
+    }
+
+    // NOTE: `first_index` is the index of the first label with the length of the given label
+    let mut first_index = 0usize;
+    let mut label_count = 1usize;
+    let mut index = 0usize;
+    for (char_pos, label_char) in label.chars().enumerate() {
+        if char_pos > 0 {
+            label_count = label_count.checked_mul(chars.len())?;
+            first_index = first_index.checked_add(label_count)?;
+        }
+        let char_index = chars.iter().position(|&c| c == label_char)?;
+        index = index.checked_mul(chars.len())?.checked_add(char_index)?;
+    }
+    first_index.checked_add(index)
+}
+
+/// Creates `count` paint-by-numbers labels, see `alphanumeric_symbol_label`. Labels that are too
+/// wide for the tile size are squeezed together and if that is not enough scaled down.
+fn create_alphanumeric_symbols(font: &BitmapFont, tile_size: i32, count: usize) -> Vec<Bitmap> {
+    let mut symbols = Vec::new();
+    for symbol_index in 0..count {
+        let label = alphanumeric_symbol_label(symbol_index);
+        // NOTE: We can unwrap here because we own the font and know that all glyphs exist
+        let glyph_bitmaps: Vec<&Bitmap> = label
+            .chars()
+            .map(|c| {
+                font.glyphs
+                    .get(&(c as Codepoint))
+                    .as_ref()
+                    .unwrap()
+                    .bitmap
+                    .as_ref()
+                    .unwrap()
+            })
+            .collect();
+        let glyphs_width = glyph_bitmaps
+            .iter()
+            .map(|glyph_bitmap| glyph_bitmap.width)
+            .sum::<i32>();
+        let glyph_spacing = if glyphs_width + (glyph_bitmaps.len() as i32 - 1) <= tile_size {
+            1
+        } else {
+            0
+        };
+        let label_width = glyphs_width + glyph_spacing * (glyph_bitmaps.len() as i32 - 1);
+        let label_height = glyph_bitmaps
+            .iter()
+            .map(|glyph_bitmap| glyph_bitmap.height)
+            .max()
+            .unwrap_or(0);
+
+        let mut label_bitmap = Bitmap::new_filled(
+            label_width as u32,
+            label_height as u32,
+            PixelRGBA::transparent(),
+        );
+        let mut pos = Vec2i::zero();
+        for glyph_bitmap in glyph_bitmaps {
+            blit_symbol(
+                glyph_bitmap,
+                &mut label_bitmap,
+                pos,
+                PixelRGBA::transparent(),
+            );
+            pos.x += glyph_bitmap.width + glyph_spacing;
+        }
+        if label_width > tile_size || label_height > tile_size {
+            label_bitmap = bitmap_scaled_down_keeping_strokes(&label_bitmap, tile_size, tile_size);
+        }
+
+        let mut bitmap =
+            Bitmap::new_filled(tile_size as u32, tile_size as u32, PixelRGBA::transparent());
+        blit_symbol(
+            &label_bitmap,
+            &mut bitmap,
+            Vec2i::new(
+                block_centered_in_block(label_bitmap.width, tile_size),
+                block_centered_in_block(label_bitmap.height, tile_size),
+            ),
+            PixelRGBA::transparent(),
+        );
+        symbols.push(bitmap);
+    }
+
+    symbols
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Low level bitmap helper function
+
+fn blit_symbol(symbol_bitmap: &Bitmap, image: &mut Bitmap, pos: Vec2i, mask_color: PixelRGBA) {
+    let symbol_rect = symbol_bitmap.rect();
+
+    assert!(pos.x >= 0);
+    assert!(pos.y >= 0);
+    assert!(pos.x + symbol_rect.width() <= image.width);
+    assert!(pos.y + symbol_rect.height() <= image.height);
+
+    for y in 0..symbol_rect.height() {
+        for x in 0..symbol_rect.width() {
+            let symbol_pixel_color = symbol_bitmap.get(x, y);
+            // NOTE: We assume the symbols-images are black on white backround. We don't want to
+            //       draw the white background so we treat it as transparent
+            if symbol_pixel_color != mask_color {
+                // NOTE: We decide per pixel because cells of fractional stitches are only
+                //       partially colored
+                let dest_color = image.get(pos.x + x, pos.y + y);
+                let relative_luminance = Color::from_pixelrgba(dest_color).to_relative_luminance();
+                let blit_color = if relative_luminance > 0.2 {
+                    PixelRGBA::black()
+                } else {
+                    PixelRGBA::white()
+                };
+                image.set(pos.x + x, pos.y + y, blit_color);
+            }
+        }
+    }
+}
+
+/// Scales the bitmap down to fit into the given size while keeping its aspect ratio. A pixel of the
+/// result takes the first non-transparent pixel of the area it covers, so that thin strokes (i.e.
+/// of glyphs) don't vanish.
+fn bitmap_scaled_down_keeping_strokes(bitmap: &Bitmap, max_width: i32, max_height: i32) -> Bitmap {
+    let scale = f32::min(
+        max_width as f32 / bitmap.width as f32,
+        max_height as f32 / bitmap.height as f32,
+    );
+    let width = i32::max(1, (scale * bitmap.width as f32) as i32);
+    let height = i32::max(1, (scale * bitmap.height as f32) as i32);
+
+    let mut result = Bitmap::new_filled(width as u32, height as u32, PixelRGBA::transparent());
+    for y in 0..height {
+        for x in 0..width {
+            let source_begin_x = (x * bitmap.width) / width;
+            let source_begin_y = (y * bitmap.height) / height;
+            let source_end_x = i32::max(source_begin_x + 1, ((x + 1) * bitmap.width) / width);
+            let source_end_y = i32::max(source_begin_y + 1, ((y + 1) * bitmap.height) / height);
+            let covered_color = (source_begin_y..source_end_y)
+                .flat_map(|source_y| {
+                    (source_begin_x..source_end_x).map(move |source_x| (source_x, source_y))
+                })
+                .map(|(source_x, source_y)| bitmap.get(source_x, source_y))
+                .find(|color| color.a != 0);
+            if let Some(color) = covered_color {
+                result.set(x, y, color);
+            }
+        }
+    }
+    result
+}
+
+fn bitmap_scaled_nearest_neighbor(bitmap: &Bitmap, width: i32, height: i32) -> Bitmap {
+    let mut result = Bitmap::new(width as u32, height as u32);
+    for y in 0..height {
+        for x in 0..width {
+            let source_x = (x * bitmap.width) / width;
+            let source_y = (y * bitmap.height) / height;
+            result.set(x, y, bitmap.get(source_x, source_y));
+        }
+    }
+    result
+}
+
+pub(crate) struct ImageSegment {
+    pub bitmap: Bitmap,
+    pub stitch_kinds: StitchKindMap,
+    /// NOTE: The backstitches are relative to the segments top-left corner
+    pub backstitches: Vec<Backstitch>,
+    pub progress: ProgressMap,
+    /// Column and row of the segment in the pattern parts overview
+    pub layout_index: Vec2i,
+    /// Position of the segments top-left stitch in the image
+    pub first_coordinate: Vec2i,
+    /// Number of leading stitch columns and rows that are repeated from the previous segments
+    pub overlap_left: i32,
+    pub overlap_top: i32,
+}
+
+/// Cuts the image into segments of `segment_width`x`segment_height` new stitches in row-major
+/// order. Every segment except the ones in the first column and row additionally repeats the last
+/// `overlap` stitch columns and rows of its left and top neighbours.
+fn image_to_segments_with_overlap(
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    backstitches: &[Backstitch],
+    progress: &ProgressMap,
+    segment_width: i32,
+    segment_height: i32,
+    overlap: i32,
+) -> Vec<ImageSegment> {
+    assert!(segment_width > overlap && segment_height > overlap);
+
+    let num_columns = (image.width + segment_width - 1) / segment_width;
+    let num_rows = (image.height + segment_height - 1) / segment_height;
+
+    let mut result = Vec::new();
+    for row in 0..num_rows {
+        for column in 0..num_columns {
+            let overlap_left = if column > 0 { overlap } else { 0 };
+            let overlap_top = if row > 0 { overlap } else { 0 };
+            let first_x = segment_width * column - overlap_left;
+            let first_y = segment_height * row - overlap_top;
+            let last_x = i32::min(segment_width * (column + 1), image.width);
+            let last_y = i32::min(segment_height * (row + 1), image.height);
+
+            let mut bitmap = Bitmap::new((last_x - first_x) as u32, (last_y - first_y) as u32);
+            for y in first_y..last_y {
+                for x in first_x..last_x {
+                    bitmap.set(x - first_x, y - first_y, image.get(x, y));
+                }
+            }
+
+            result.push(ImageSegment {
+                bitmap,
+                stitch_kinds: stitch_kinds.sub_map(
+                    first_x,
+                    first_y,
+                    last_x - first_x,
+                    last_y - first_y,
+                ),
+                backstitches: backstitch::backstitches_in_region(
+                    backstitches,
+                    Vec2i::new(first_x, first_y),
+                    last_x - first_x,
+                    last_y - first_y,
+                ),
+                progress: progress.sub_map(first_x, first_y, last_x - first_x, last_y - first_y),
+                layout_index: Vec2i::new(column, row),
+                first_coordinate: Vec2i::new(first_x, first_y),
+                overlap_left,
+                overlap_top,
+            });
+        }
+    }
+    result
+}
+
+/// Shifts the colors of the bitmap so that their average becomes the given color. Unlike
+/// multiplying with the color this keeps the texture of the bitmap visible on dark colors too.
+fn bitmap_tinted(bitmap: &Bitmap, color: PixelRGBA) -> Bitmap {
+    let opaque_pixels: Vec<&PixelRGBA> = bitmap.data.iter().filter(|pixel| pixel.a != 0).collect();
+    if opaque_pixels.is_empty() {
+        return bitmap.clone();
+    }
+    let average = |channel_of: fn(&PixelRGBA) -> u8| {
+        opaque_pixels
+            .iter()
+            .map(|pixel| channel_of(pixel) as i32)
+            .sum::<i32>()
+            / opaque_pixels.len() as i32
+    };
+    let offset_r = color.r as i32 - average(|pixel| pixel.r);
+    let offset_g = color.g as i32 - average(|pixel| pixel.g);
+    let offset_b = color.b as i32 - average(|pixel| pixel.b);
+
+    let shifted =
+        |channel: u8, offset: i32| i32::max(0, i32::min(255, channel as i32 + offset)) as u8;
+    let mut result = bitmap.clone();
+    for pixel in result.data.iter_mut() {
+        *pixel = PixelRGBA::new(
+            shifted(pixel.r, offset_r),
+            shifted(pixel.g, offset_g),
+            shifted(pixel.b, offset_b),
+            pixel.a,
+        );
+    }
+    result
+}
+
+/// Blends every pixel of the given rect halfway towards the shade color
+fn bitmap_shade_rect(bitmap: &mut Bitmap, pos_x: i32, pos_y: i32, width: i32, height: i32) {
+    let blend = |channel: u8, shade: u8| ((channel as u32 + shade as u32) / 2) as u8;
+    for y in pos_y..i32::min(pos_y + height, bitmap.height) {
+        for x in pos_x..i32::min(pos_x + width, bitmap.width) {
+            let color = bitmap.get(x, y);
+            bitmap.set(
+                x,
+                y,
+                PixelRGBA::new(
+                    blend(color.r, COLOR_OVERLAP_SHADE.r),
+                    blend(color.g, COLOR_OVERLAP_SHADE.g),
+                    blend(color.b, COLOR_OVERLAP_SHADE.b),
+                    color.a,
+                ),
+            );
+        }
+    }
+}
+
+/// Fades every pixel of the given rect halfway towards white and hatches it with diagonal lines.
+/// The lines are aligned to the bitmap, so that they continue across neighbouring rects.
+fn bitmap_hatch_rect(
+    bitmap: &mut Bitmap,
+    pos_x: i32,
+    pos_y: i32,
+    width: i32,
+    height: i32,
+    line_spacing: i32,
+) {
+    let fade = |channel: u8| ((channel as u32 + 255) / 2) as u8;
+    for y in pos_y..i32::min(pos_y + height, bitmap.height) {
+        for x in pos_x..i32::min(pos_x + width, bitmap.width) {
+            let color = bitmap.get(x, y);
+            let hatched_color = if (x + y) % line_spacing == 0 {
+                COLOR_PROGRESS_HATCH
+            } else {
+                PixelRGBA::new(fade(color.r), fade(color.g), fade(color.b), color.a)
+            };
+            bitmap.set(x, y, hatched_color);
+        }
+    }
+}
+
+/// Draws a line with round ends. Pixels outside of the bitmap are skipped.
+fn bitmap_draw_line_thick(
+    bitmap: &mut Bitmap,
+    start: Vec2i,
+    end: Vec2i,
+    thickness: i32,
+    color: PixelRGBA,
+) {
+    let radius = thickness as f32 / 2.0;
+    let delta_x = (end.x - start.x) as f32;
+    let delta_y = (end.y - start.y) as f32;
+    let length_squared = delta_x * delta_x + delta_y * delta_y;
+
+    let min_x = i32::max(0, i32::min(start.x, end.x) - thickness);
+    let min_y = i32::max(0, i32::min(start.y, end.y) - thickness);
+    let max_x = i32::min(bitmap.width - 1, i32::max(start.x, end.x) + thickness);
+    let max_y = i32::min(bitmap.height - 1, i32::max(start.y, end.y) + thickness);
+    for y in min_y..=max_y {
+        for x in min_x..=max_x {
+            // NOTE: We project the pixel onto the line and clamp it to the line ends
+            let pos_x = (x - start.x) as f32;
+            let pos_y = (y - start.y) as f32;
+            let percent = if length_squared > 0.0 {
+                ((pos_x * delta_x + pos_y * delta_y) / length_squared).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let distance_x = pos_x - percent * delta_x;
+            let distance_y = pos_y - percent * delta_y;
+            if distance_x * distance_x + distance_y * distance_y <= radius * radius {
+                bitmap.set(x, y, color);
+            }
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Pattern creation
+
+fn draw_origin_line_vertical(bitmap: &mut Bitmap, pos_x: i32) {
+    bitmap.draw_rect_filled_safely(pos_x - 2, 0, 4, bitmap.height, PixelRGBA::black());
+    bitmap.draw_rect_filled_safely(pos_x - 1, 0, 2, bitmap.height, PixelRGBA::white());
+}
+
+fn draw_origin_line_horizontal(bitmap: &mut Bitmap, pos_y: i32) {
+    bitmap.draw_rect_filled_safely(0, pos_y - 2, bitmap.width, 4, PixelRGBA::black());
+    bitmap.draw_rect_filled_safely(0, pos_y - 1, bitmap.width, 2, PixelRGBA::white());
+}
+
+/// Returns the grid lines along one axis that get a label as pairs of grid line index and logical
+/// coordinate. These are the multiples of ten and the first and last grid line.
+pub(crate) fn grid_label_coordinates(
+    grid_size: i32,
+    logical_first_coordinate: i32,
+) -> Vec<(i32, i32)> {
+    let logical_last_coordinate = logical_first_coordinate + grid_size;
+
+    let mut result = Vec::new();
+    for grid_coord in 0..(grid_size + 1) {
+        let logical_coord = logical_first_coordinate + grid_coord;
+        if logical_coord % 10 == 0 {
+            result.push((grid_coord, logical_coord));
+        }
+    }
+
+    // Add label for first and last grid line so that we don't mix up a remaining 7, 8 or 9 pixel
+    // block with a 10 block
+    let pixel_count_in_first_block = i32::abs(
+        ceil_to_multiple_of_target_i32(logical_first_coordinate, 10) - logical_first_coordinate,
+    );
+    if pixel_count_in_first_block > 3 {
+        result.push((0, logical_first_coordinate));
+    }
+    let pixel_count_in_last_block = i32::abs(
+        floor_to_multiple_of_target_i32(logical_last_coordinate, 10) - logical_last_coordinate,
+    );
+    if pixel_count_in_last_block > 3 {
+        result.push((grid_size, logical_last_coordinate));
+    }
+
+    result
+}
+
+/// NOTE: This assumes that the scaled bitmap width and height are a roughly a multiple of
+///       grid_cell_size
+fn place_grid_labels_in_pattern(
+    scaled_bitmap: &Bitmap,
+    grid_cell_size: i32,
+    font: &BitmapFont,
+    logical_first_coordinate_x: i32,
+    logical_first_coordinate_y: i32,
+) -> Bitmap {
+    let grid_width = scaled_bitmap.width / grid_cell_size;
+    let grid_height = scaled_bitmap.height / grid_cell_size;
+
+    let logical_last_coordinate_x = logical_first_coordinate_x + grid_width;
+    let logical_last_coordinate_y = logical_first_coordinate_y + grid_height;
+
+    // Determine how much image-padding we need by calculating the maximum label text dimension
+    let label_padding = {
+        let max_logical_coordinates = [
+            logical_first_coordinate_x,
+            logical_first_coordinate_y,
+            logical_last_coordinate_x,
+            logical_last_coordinate_y,
+        ];
+        let max_text_charcount = max_logical_coordinates
+            .iter()
+            .map(|max_coordinate| max_coordinate.to_string().len())
+            .max()
+            .unwrap();
+
+        font.horizontal_advance_max * (max_text_charcount + 4) as i32
+    };
+
+    let mut result_bitmap = scaled_bitmap.extended(
+        label_padding,
+        label_padding,
+        label_padding,
+        label_padding,
+        PixelRGBA::white(),
+    );
+
+    // Determine all x label positions
+    let label_coords_x = grid_label_coordinates(grid_width, logical_first_coordinate_x);
+
+    // Draw x labels
+    for (bitmap_coord_x, logical_coord_x) in label_coords_x {
+        let text = logical_coord_x.to_string();
+        let draw_x = label_padding + grid_cell_size * bitmap_coord_x;
+        let draw_pos_top = Vec2i::new(draw_x, label_padding / 2);
+        let draw_pos_bottom = Vec2i::new(draw_x, result_bitmap.height - label_padding / 2);
+
+        result_bitmap.draw_text_aligned_in_point(
+            font,
+            &text,
+            1,
+            draw_pos_top,
+            Vec2i::zero(),
+            Some(TextAlignment {
+                horizontal: AlignmentHorizontal::Center,
+                vertical: AlignmentVertical::Center,
+                origin_is_baseline: false,
+                ignore_whitespace: false,
+            }),
+        );
+        result_bitmap.draw_text_aligned_in_point(
+            font,
+            &text,
+            1,
+            draw_pos_bottom,
+            Vec2i::zero(),
+            Some(TextAlignment {
+                horizontal: AlignmentHorizontal::Center,
+                vertical: AlignmentVertical::Center,
+                origin_is_baseline: false,
+                ignore_whitespace: false,
+            }),
+        );
+    }
+
+    // Determine all y label positions
+    let label_coords_y = grid_label_coordinates(grid_height, logical_first_coordinate_y);
+
+    // Draw y labels
+    for (bitmap_coord_y, logical_coord_y) in label_coords_y {
+        // NOTE: In pixel space our y-coordinates are y-down. We want cartesian y-up so we negate y
+        let text = (-logical_coord_y).to_string();
+        let draw_y = label_padding + grid_cell_size * bitmap_coord_y;
+        let draw_pos_left = Vec2i::new(label_padding / 2, draw_y);
+        let draw_pos_right = Vec2i::new(result_bitmap.width - label_padding / 2, draw_y);
+
+        result_bitmap.draw_text_aligned_in_point(
+            font,
+            &text,
+            1,
+            draw_pos_left,
+            Vec2i::zero(),
+            Some(TextAlignment {
+                horizontal: AlignmentHorizontal::Center,
+                vertical: AlignmentVertical::Center,
+                origin_is_baseline: false,
+                ignore_whitespace: false,
+            }),
+        );
+        result_bitmap.draw_text_aligned_in_point(
+            font,
+            &text,
+            1,
+            draw_pos_right,
+            Vec2i::zero(),
+            Some(TextAlignment {
+                horizontal: AlignmentHorizontal::Center,
+                vertical: AlignmentVertical::Center,
+                origin_is_baseline: false,
+                ignore_whitespace: false,
+            }),
+        );
+    }
+
+    result_bitmap
+}
+
+/// Creates a single pattern page. Stitches that are completed according to the progress map are
+/// faded and hatched.
+pub fn create_cross_stitch_pattern(
+    context: &PatternRenderContext,
+    resources: &Resources,
+    pattern_type: PatternType,
+) -> Bitmap {
+    let PatternRenderContext {
+        bitmap,
+        stitch_kinds,
+        backstitches,
+        progress,
+        color_mappings,
+        fabric_color,
+        segment_index,
+        logical_first_coordinate,
+        overlap_left,
+        overlap_top,
+        add_origin_grid_bars,
+    } = *context;
+    let (logical_first_coordinate_x, logical_first_coordinate_y) =
+        (logical_first_coordinate.x, logical_first_coordinate.y);
+    let tile_size = resources.tile_size;
+    let add_thick_ten_grid = !pattern_type.is_paint_by_numbers();
+    let add_origin_grid_bars = add_origin_grid_bars && !pattern_type.is_paint_by_numbers();
+    let symbol_mask_color = if pattern_type.is_paint_by_numbers() {
+        PixelRGBA::transparent()
+    } else {
+        PixelRGBA::white()
+    };
+
+    let (colorize, add_symbol, use_alphanum) = match pattern_type {
+        PatternType::BlackAndWhite => (false, true, false),
+        PatternType::Colorized => (true, true, false),
+        PatternType::ColorizedNoSymbols => (true, false, false),
+        PatternType::PaintByNumbers => (false, true, true),
+        PatternType::PaintByNumbersRegions => (false, true, true),
+    };
+    // NOTE: Region patterns are meant for painting, so they show neither the stitch grid,
+    //       fractional stitches nor backstitches
+    let color_regions = if pattern_type == PatternType::PaintByNumbersRegions {
+        Some(ColorRegions::from_image(bitmap))
+    } else {
+        None
+    };
+
+    let mut scaled_bitmap = Bitmap::new(
+        (tile_size * bitmap.width) as u32,
+        (tile_size * bitmap.height) as u32,
+    );
+    let scaled_bitmap_width = scaled_bitmap.width;
+    let scaled_bitmap_height = scaled_bitmap.height;
+
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let color = bitmap.get(x, y);
+            let stitch_kind = stitch_kinds.get(x, y);
+
+            // Colorize pixels
+            if color.a != 0 && stitch_kind.is_fractional() && color_regions.is_none() {
+                // NOTE: Fractional stitches are drawn as thick lines along their legs. Without
+                //       colors we still draw them in grey so that their shape is visible
+                let (leg_color, empty_color) = if colorize {
+                    (color, fabric_color)
+                } else {
+                    (COLOR_FRACTIONAL_STITCH_UNCOLORIZED, PixelRGBA::white())
+                };
+                for tile_y in 0..tile_size {
+                    for tile_x in 0..tile_size {
+                        let covered = stitch_kind.covers_pixel(
+                            tile_x,
+                            tile_y,
+                            tile_size,
+                            tile_size,
+                            Some(tile_size / 2),
+                        );
+                        scaled_bitmap.set(
+                            tile_size * x + tile_x,
+                            tile_size * y + tile_y,
+                            if covered { leg_color } else { empty_color },
+                        );
+                    }
+                }
+            } else if colorize {
+                scaled_bitmap.draw_rect_filled(
+                    tile_size * x,
+                    tile_size * y,
+                    tile_size,
+                    tile_size,
+                    if color.a == 0 { fabric_color } else { color },
+                );
+            } else {
+                scaled_bitmap.draw_rect_filled(
+                    tile_size * x,
+                    tile_size * y,
+                    tile_size,
+                    tile_size,
+                    PixelRGBA::white(),
+                );
+            }
+
+            // Add symbol
+            let is_label_cell = color_regions
+                .as_ref()
+                .map(|color_regions| color_regions.is_label_cell(x, y))
+                .unwrap_or(true);
+            if add_symbol && color.a != 0 && is_label_cell {
+                let symbol = if use_alphanum {
+                    &color_mappings.get(&color).unwrap().symbol_alphanum
+                } else {
+                    &color_mappings.get(&color).unwrap().symbol
+                };
+
+                blit_symbol(
+                    symbol,
+                    &mut scaled_bitmap,
+                    Vec2i::new(tile_size * x, tile_size * y),
+                    symbol_mask_color,
+                );
+            }
+        }
+    }
+
+    // Shade stitches that are repeated from previous pattern parts
+    if overlap_left > 0 {
+        bitmap_shade_rect(
+            &mut scaled_bitmap,
+            0,
+            0,
+            tile_size * overlap_left,
+            scaled_bitmap_height,
+        );
+    }
+    if overlap_top > 0 {
+        // NOTE: We skip the top-left corner if it was already shaded above
+        bitmap_shade_rect(
+            &mut scaled_bitmap,
+            tile_size * overlap_left,
+            0,
+            scaled_bitmap_width,
+            tile_size * overlap_top,
+        );
+    }
+
+    // Mark stitches that are already done
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            if bitmap.get(x, y).a != 0 && progress.is_completed(x, y) {
+                bitmap_hatch_rect(
+                    &mut scaled_bitmap,
+                    tile_size * x,
+                    tile_size * y,
+                    tile_size,
+                    tile_size,
+                    i32::max(3, tile_size / 4),
+                );
+            }
+        }
+    }
+
+    // Add 1x1 grid or the outlines of the color regions
+    if let Some(color_regions) = &color_regions {
+        for (start, end) in color_regions.outline_edges() {
+            scaled_bitmap.draw_rect_filled_safely(
+                tile_size * start.x - 1,
+                tile_size * start.y - 1,
+                tile_size * (end.x - start.x) + 2,
+                tile_size * (end.y - start.y) + 2,
+                COLOR_REGION_OUTLINE,
+            );
+        }
+    } else {
+        for x in 0..bitmap.width {
+            scaled_bitmap.draw_rect_filled(
+                tile_size * x,
+                0,
+                1,
+                scaled_bitmap_height,
+                COLOR_GRID_THIN,
+            );
+        }
+        for y in 0..bitmap.height {
+            scaled_bitmap.draw_rect_filled(
+                0,
+                tile_size * y,
+                scaled_bitmap_width,
+                1,
+                COLOR_GRID_THIN,
+            );
+        }
+        // Close 1x1 grid line on bottom-right bitmap border
+        scaled_bitmap.draw_rect_filled(
+            scaled_bitmap_width - 1,
+            0,
+            1,
+            scaled_bitmap_height,
+            COLOR_GRID_THIN,
+        );
+        scaled_bitmap.draw_rect_filled(
+            0,
+            scaled_bitmap_height - 1,
+            scaled_bitmap_width,
+            1,
+            COLOR_GRID_THIN,
+        );
+    }
+
+    // Add 10x10 grid
+    if add_thick_ten_grid {
+        for bitmap_x in 0..bitmap.width {
+            let logical_x = logical_first_coordinate_x + bitmap_x;
+            if logical_x % 10 == 0 {
+                scaled_bitmap.draw_rect_filled(
+                    tile_size * bitmap_x,
+                    0,
+                    2,
+                    scaled_bitmap_height,
+                    COLOR_GRID_THICK,
+                );
+            }
+        }
+        for bitmap_y in 0..bitmap.height {
+            let logical_y = logical_first_coordinate_y + bitmap_y;
+            if logical_y % 10 == 0 {
+                scaled_bitmap.draw_rect_filled(
+                    0,
+                    tile_size * bitmap_y,
+                    scaled_bitmap_width,
+                    2,
+                    COLOR_GRID_THICK,
+                );
+            }
+        }
+        // Close 10x10 grid line on bottom-right bitmap border if necessary
+        if (logical_first_coordinate_x + bitmap.width) % 10 == 0 {
+            scaled_bitmap.draw_rect_filled(
+                scaled_bitmap_width - 2,
+                0,
+                2,
+                scaled_bitmap_height,
+                COLOR_GRID_THICK,
+            );
+        }
+        if (logical_first_coordinate_y + bitmap.height) % 10 == 0 {
+            scaled_bitmap.draw_rect_filled(
+                0,
+                scaled_bitmap_height - 2,
+                scaled_bitmap_width,
+                2,
+                COLOR_GRID_THICK,
+            );
+        }
+    }
+
+    // Add backstitches
+    // NOTE: Without colors all backstitches are black. With colors they get a black border so that
+    //       they are also visible on stitches of the same color
+    if color_regions.is_none() {
+        let backstitch_thickness = i32::max(2, tile_size / 6);
+        for backstitch in backstitches {
+            let start = Vec2i::new(
+                tile_size * backstitch.start.x,
+                tile_size * backstitch.start.y,
+            );
+            let end = Vec2i::new(tile_size * backstitch.end.x, tile_size * backstitch.end.y);
+            if colorize {
+                bitmap_draw_line_thick(
+                    &mut scaled_bitmap,
+                    start,
+                    end,
+                    backstitch_thickness + 2,
+                    PixelRGBA::black(),
+                );
+                bitmap_draw_line_thick(
+                    &mut scaled_bitmap,
+                    start,
+                    end,
+                    backstitch_thickness,
+                    backstitch.color,
+                );
+            } else {
+                bitmap_draw_line_thick(
+                    &mut scaled_bitmap,
+                    start,
+                    end,
+                    backstitch_thickness,
+                    PixelRGBA::black(),
+                );
+            }
+        }
+    }
+
+    // Add origin grid
+    if add_origin_grid_bars {
+        let origin_bitmap_coord_x = -logical_first_coordinate_x;
+        if 0 < origin_bitmap_coord_x && origin_bitmap_coord_x < bitmap.width {
+            draw_origin_line_vertical(&mut scaled_bitmap, tile_size * origin_bitmap_coord_x);
+        }
+
+        let origin_bitmap_coord_y = -logical_first_coordinate_y;
+        if 0 < origin_bitmap_coord_y && origin_bitmap_coord_y < bitmap.height {
+            draw_origin_line_horizontal(&mut scaled_bitmap, tile_size * origin_bitmap_coord_y);
+        }
+
+        // NOTE: If our origin grid is located on the edge of our image we want to extend our image
+        //       so that the origin grid is drawn more clearly visible
+        let needs_grid_left = logical_first_coordinate_x == 0;
+        let needs_grid_top = logical_first_coordinate_y == 0;
+        let needs_grid_right = logical_first_coordinate_x + bitmap.width == 0;
+        let needs_grid_bottom = logical_first_coordinate_y + bitmap.height == 0;
+
+        let padding_left = if needs_grid_left { 2 } else { 0 };
+        let padding_top = if needs_grid_top { 2 } else { 0 };
+        let padding_right = if needs_grid_right { 2 } else { 0 };
+        let padding_bottom = if needs_grid_bottom { 2 } else { 0 };
+
+        scaled_bitmap.extend(
+            padding_left,
+            padding_top,
+            padding_right,
+            padding_bottom,
+            PixelRGBA::white(),
+        );
+
+        if needs_grid_left {
+            draw_origin_line_vertical(&mut scaled_bitmap, 2);
+        }
+        if needs_grid_right {
+            draw_origin_line_vertical(&mut scaled_bitmap, scaled_bitmap_width);
+        }
+        if needs_grid_top {
+            draw_origin_line_horizontal(&mut scaled_bitmap, 2);
+        }
+        if needs_grid_bottom {
+            draw_origin_line_horizontal(&mut scaled_bitmap, scaled_bitmap_height);
+        }
+    }
+
+    // Add 10-grid labels
+    let final_bitmap = if add_thick_ten_grid {
+        // NOTE: At this point the scaled bitmap might not be an exact multiple of the original
+        //       bitmap because we may have padded it while drawing the origin grid bars. Therefore
+        //       the placement of the labels might be incorrectly shifted by two pixels. This is
+        //       okay because it is not really visible and the code complexity to fix this is not
+        //       worth it.
+        place_grid_labels_in_pattern(
+            &scaled_bitmap,
+            tile_size,
+            &resources.font,
+            logical_first_coordinate_x,
+            logical_first_coordinate_y,
+        )
+    } else {
+        scaled_bitmap
+    };
+
+    // Add segment index indicator if necessary
+    if let Some(segment_index) = segment_index {
+        let text_bitmap = Bitmap::create_from_text(
+            &resources.font_big,
+            &format!("\n Pattern Part {} \n", segment_index),
+            1,
+            PixelRGBA::white(),
+        );
+        text_bitmap.glued_to(
+            &final_bitmap,
+            GluePosition::TopCenter,
+            0,
+            PixelRGBA::white(),
+        )
+    } else {
+        final_bitmap
+    }
+}
+
+/// Creates all pattern types for the given context. Paint by numbers patterns are optional.
+fn create_cross_stitch_pattern_set(
+    context: &PatternRenderContext,
+    resources: &Resources,
+    create_paint_by_number_set: bool,
+) -> Vec<PatternPage> {
+    let mut pattern_colorized = None;
+    let mut pattern_black_and_white = None;
+    let mut pattern_colorized_no_symbols = None;
+    let mut pattern_paint_by_numbers = None;
+    let mut pattern_paint_by_numbers_regions = None;
+
+    let create_pattern = |pattern_type| {
+        Some(create_cross_stitch_pattern(
+            context,
+            resources,
+            pattern_type,
+        ))
+    };
+    rayon::scope(|scope| {
+        scope.spawn(|_| pattern_colorized = create_pattern(PatternType::Colorized));
+        scope.spawn(|_| pattern_black_and_white = create_pattern(PatternType::BlackAndWhite));
+        scope.spawn(|_| {
+            pattern_colorized_no_symbols = create_pattern(PatternType::ColorizedNoSymbols)
+        });
+        if create_paint_by_number_set {
+            scope.spawn(|_| pattern_paint_by_numbers = create_pattern(PatternType::PaintByNumbers));
+            scope.spawn(|_| {
+                pattern_paint_by_numbers_regions =
+                    create_pattern(PatternType::PaintByNumbersRegions)
+            });
+        }
+    });
+
+    vec![
+        (PatternType::Colorized, pattern_colorized),
+        (PatternType::BlackAndWhite, pattern_black_and_white),
+        (
+            PatternType::ColorizedNoSymbols,
+            pattern_colorized_no_symbols,
+        ),
+        (PatternType::PaintByNumbers, pattern_paint_by_numbers),
+        (
+            PatternType::PaintByNumbersRegions,
+            pattern_paint_by_numbers_regions,
+        ),
+    ]
+    .into_iter()
+    .filter_map(|(pattern_type, bitmap)| {
+        bitmap.map(|bitmap| PatternPage {
+            pattern_type,
+            segment_index: context.segment_index,
+            bitmap,
+        })
+    })
+    .collect()
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Image analysis
+
+/// Assigns symbols, floss and colorized stitch preview tiles to every color of the image.
+/// Predefined color mappings (i.e. from an imported pattern) keep their symbol and floss.
+/// Fails if we don't have enough symbols for all colors.
+pub fn create_color_mappings_from_image(
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    resources: &Resources,
+    floss_catalog: Option<&FlossCatalog>,
+    predefined_color_mappings: Option<&IndexMap<PixelRGBA, ColorInfo>>,
+) -> Result<IndexMap<PixelRGBA, ColorInfo>> {
+    check_layer_dimensions(image, stitch_kinds, None)?;
+
+    let symbols = &resources.symbols;
+    let symbols_alphanum = &resources.symbols_alphanum;
+    let stitch_images_premultiplied_alpha = &resources.stitch_images_premultiplied_alpha;
+    let stitch_images_luminance_premultiplied_alpha =
+        &resources.stitch_images_luminance_premultiplied_alpha;
+
+    let mut color_mappings = image_extract_colors_and_counts(image);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let color = image.get(x, y);
+            if let Some(entry) = color_mappings.get_mut(&color) {
+                match stitch_kinds.get(x, y) {
+                    StitchKind::Full => {}
+                    StitchKind::HalfRising | StitchKind::HalfFalling => entry.count_half += 1,
+                    StitchKind::Quarter(_) => entry.count_quarter += 1,
+                    StitchKind::ThreeQuarter(_) => entry.count_three_quarter += 1,
+                }
+            }
+        }
+    }
+
+    // Predefined symbols, labels and floss i.e. from an imported pattern
+    if let Some(predefined_color_mappings) = predefined_color_mappings {
+        color_mappings_apply_predefined(&mut color_mappings, predefined_color_mappings);
+    } else {
+        color_mappings_assign_symbols(&mut color_mappings, resources);
+    }
+
+    // Floss
+    if let Some(floss_catalog) = floss_catalog {
+        for entry in color_mappings.values_mut() {
+            if entry.floss.is_none() {
+                entry.floss = Some(floss_catalog.find_closest(entry.color).clone());
+            }
+        }
+    }
+
+    let symbol_count_needed = color_mappings
+        .values()
+        .map(|entry| entry.symbol_index + 1)
+        .max()
+        .unwrap_or(0);
+    if symbols.len() < symbol_count_needed {
+        return Err(Error::TooManyColors {
+            color_count: color_mappings.len(),
+            symbol_count: symbols.len(),
+        });
+    }
+    let label_count_needed = color_mappings
+        .values()
+        .map(|entry| entry.label_index + 1)
+        .max()
+        .unwrap_or(0);
+    if symbols_alphanum.len() < label_count_needed {
+        return Err(Error::TooManyColors {
+            color_count: color_mappings.len(),
+            symbol_count: symbols_alphanum.len(),
+        });
+    }
+
+    // Stitch symbols
+    for entry in color_mappings.values_mut() {
+        entry.symbol = symbols[entry.symbol_index].clone();
+    }
+
+    // Alphanum symbols
+    for entry in color_mappings.values_mut() {
+        entry.symbol_alphanum = symbols_alphanum[entry.label_index].clone();
+    }
+
+    // Colorized stitch tiles
+    for entry in color_mappings.values_mut() {
+        let color = entry.color;
+        if color.a != 0 {
+            for (stitch_image_premultipllied, stitch_image_luminance_premultiplied) in
+                stitch_images_premultiplied_alpha
+                    .iter()
+                    .zip(stitch_images_luminance_premultiplied_alpha.iter())
+            {
+                let mut stitch = stitch_image_premultipllied.clone();
+
+                let screen_layer = Bitmap::new_filled(
+                    stitch_image_premultipllied.width as u32,
+                    stitch_image_premultipllied.height as u32,
+                    PixelRGBA::new(105, 109, 128, 255),
+                )
+                .to_premultiplied_alpha();
+                screen_layer.blit_to_alpha_blended_premultiplied(
+                    &mut stitch,
+                    Vec2i::zero(),
+                    false,
+                    ColorBlendMode::Screen,
+                );
+
+                let color_layer = Bitmap::new_filled(
+                    stitch_image_premultipllied.width as u32,
+                    stitch_image_premultipllied.height as u32,
+                    color,
+                )
+                .to_premultiplied_alpha();
+                color_layer.blit_to_alpha_blended_premultiplied(
+                    &mut stitch,
+                    Vec2i::zero(),
+                    false,
+                    ColorBlendMode::Multiply,
+                );
+
+                let mut luminosity_layer = stitch_image_luminance_premultiplied.clone();
+                let percent = (color.r as f32 + color.g as f32 + color.b as f32) / (3.0 * 255.0);
+                for pixel in luminosity_layer.data.iter_mut() {
+                    pixel.r /= 6 + (8.0 * percent * percent) as u8;
+                    pixel.g /= 6 + (8.0 * percent * percent) as u8;
+                    pixel.b /= 6 + (8.0 * percent * percent) as u8;
+                    pixel.a /= 6 + (8.0 * percent * percent) as u8;
+                }
+                luminosity_layer.blit_to_alpha_blended_premultiplied(
+                    &mut stitch,
+                    Vec2i::zero(),
+                    false,
+                    ColorBlendMode::Luminosity,
+                );
+
+                entry
+                    .stitches_premultiplied
+                    .push(stitch.masked_by_premultiplied_alpha(stitch_image_premultipllied));
+            }
+        }
+    }
+
+    Ok(color_mappings)
+}
+
+/// Takes the symbols, labels and floss of all colors that the predefined color mappings know (i.e.
+/// the palette that all frames share or an imported pattern). All other colors get the first
+/// unused symbols and labels.
+fn color_mappings_apply_predefined(
+    color_mappings: &mut IndexMap<PixelRGBA, ColorInfo>,
+    predefined_color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+) {
+    let mut used_symbol_indices = HashSet::new();
+    let mut used_label_indices = HashSet::new();
+    for entry in color_mappings.values_mut() {
+        entry.symbol_index = usize::MAX;
+        entry.label_index = usize::MAX;
+        if let Some(predefined) = predefined_color_mappings.get(&entry.color) {
+            entry.floss = predefined.floss.clone();
+            if used_symbol_indices.insert(predefined.symbol_index) {
+                entry.symbol_index = predefined.symbol_index;
+            }
+            if used_label_indices.insert(predefined.label_index) {
+                entry.label_index = predefined.label_index;
+            }
+        }
+    }
+
+    // NOTE: We can unwrap here because the iterators are endless
+    let mut free_symbol_indices = (0..).filter(|index| !used_symbol_indices.contains(index));
+    let mut free_label_indices = (0..).filter(|index| !used_label_indices.contains(index));
+    for entry in color_mappings.values_mut() {
+        if entry.symbol_index == usize::MAX {
+            entry.symbol_index = free_symbol_indices.next().unwrap();
+        }
+        if entry.label_index == usize::MAX {
+            entry.label_index = free_label_indices.next().unwrap();
+        }
+    }
+}
+
+/// Gives the colors that are easily confused with each other symbols that look very different.
+/// The result is deterministic. If there are more colors than symbols the symbols are left as they
+/// are, so that `create_color_mappings_from_image` can report the missing symbols.
+///
+/// NOTE: This only concerns the stitch symbols. The paint-by-numbers labels follow the legend order.
+pub fn color_mappings_assign_symbols(
+    color_mappings: &mut IndexMap<PixelRGBA, ColorInfo>,
+    resources: &Resources,
+) {
+    let symbol_count = resources.symbols.len();
+    if color_mappings.len() > symbol_count {
+        return;
+    }
+    // NOTE: We prefer the provided symbols and only use generated ones if we run out
+    let symbol_count = symbol_count.min(usize::max(
+        color_mappings.len(),
+        resources.symbols_provided_count,
+    ));
+    let colors: Vec<PixelRGBA> = color_mappings.keys().cloned().collect();
+    let symbol_indices =
+        symbol_assignment::assign_symbols(&colors, &resources.symbol_dissimilarities, symbol_count);
+    for (entry, symbol_index) in color_mappings.values_mut().zip(symbol_indices) {
+        entry.symbol_index = symbol_index;
+    }
+}
+
+/// Counts the completed stitches of every color according to the progress map
+pub fn color_mappings_count_completed(
+    color_mappings: &mut IndexMap<PixelRGBA, ColorInfo>,
+    image: &Bitmap,
+    progress: &ProgressMap,
+) {
+    for info in color_mappings.values_mut() {
+        info.count_completed = 0;
+    }
+    for y in 0..image.height {
+        for x in 0..image.width {
+            if !progress.is_completed(x, y) {
+                continue;
+            }
+            if let Some(info) = color_mappings.get_mut(&image.get(x, y)) {
+                info.count_completed += 1;
+            }
+        }
+    }
+}
+
+pub fn image_extract_colors_and_counts(image: &Bitmap) -> IndexMap<PixelRGBA, ColorInfo> {
+    let mut color_mappings = IndexMap::new();
+    for pixel in &image.data {
+        if pixel.a == 0 {
+            // Ignore transparent regions
+            continue;
+        }
+
+        let entry = color_mappings.entry(*pixel).or_insert_with(|| ColorInfo {
+            color: *pixel,
+            count: 0,
+            count_half: 0,
+            count_quarter: 0,
+            count_three_quarter: 0,
+            count_completed: 0,
+            symbol_index: 0,
+            label_index: 0,
+            symbol: Bitmap::new_empty(),
+            symbol_alphanum: Bitmap::new_empty(),
+            stitches_premultiplied: Vec::new(),
+            floss: None,
+        });
+        entry.count += 1;
+    }
+
+    // This makes color ramps on the legend more pretty
+    color_mappings.sort_by(|color_a, _info_a, color_b, _info_b| {
+        PixelRGBA::compare_by_hue_luminosity_saturation(color_a, color_b)
+    });
+    for (legend_index, entry) in color_mappings.values_mut().enumerate() {
+        entry.symbol_index = legend_index;
+        entry.label_index = legend_index;
+    }
+
+    color_mappings
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Legend creation
+
+fn create_pattern_page_layout(font: &BitmapFont, layout_indices: &[Vec2i]) -> Bitmap {
+    let caption_image =
+        Bitmap::create_from_text(font, "\n\nPattern parts overview:\n", 1, PixelRGBA::white());
+
+    let page_count = layout_indices.len();
+    // NOTE: Indexes begin at 0 therefore we add 1
+    let num_rows = 1 + layout_indices.iter().map(|v| v.y).max().unwrap();
+    let num_columns = 1 + layout_indices.iter().map(|v| v.x).max().unwrap();
+
+    let page_tile_dim = {
+        // NOTE: We want to have a 1px visual gap between page tiles therefore we add 1
+        let page_tile_width = 1 + font
+            .get_text_bounding_rect(&format!(" {} ", page_count), 1, false)
+            .dim
+            .x;
+        let page_tile_height = 1 + (page_tile_width as f32 * (9.0 / 6.0)) as i32;
+        Vec2i::new(page_tile_width, page_tile_height)
+    };
+
+    let image_width = num_columns * page_tile_dim.x;
+    let image_height = num_rows * page_tile_dim.y;
+    let mut image = Bitmap::new_filled(image_width as u32, image_height as u32, PixelRGBA::white());
+    for (page_index, pos_index) in layout_indices.iter().enumerate() {
+        let pos = *pos_index * page_tile_dim;
+        image.draw_rect(
+            pos.x,
+            pos.y,
+            page_tile_dim.x - 1,
+            page_tile_dim.y - 1,
+            PixelRGBA::black(),
+        );
+        image.draw_text_aligned_in_point(
+            font,
+            &(page_index + 1).to_string(),
+            1,
+            pos + page_tile_dim / 2,
+            Vec2i::zero(),
+            Some(TextAlignment {
+                horizontal: AlignmentHorizontal::Center,
+                vertical: AlignmentVertical::Center,
+                origin_is_baseline: false,
+                ignore_whitespace: false,
+            }),
+        );
+    }
+
+    caption_image.glued_to(&image, GluePosition::TopLeft, 0, PixelRGBA::white())
+}
+
+/// Example: "6 full, 3 half, 1 quarter"
+fn fractional_stitch_counts_text(
+    count_full: usize,
+    count_half: usize,
+    count_quarter: usize,
+    count_three_quarter: usize,
+) -> String {
+    [
+        (count_full, "full"),
+        (count_half, "half"),
+        (count_quarter, "quarter"),
+        (count_three_quarter, "three-quarter"),
+    ]
+    .iter()
+    .filter(|(count, _name)| *count > 0)
+    .map(|(count, name)| format!("{} {}", count, name))
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+fn create_legend_entry(
+    font: &BitmapFont,
+    info: &ColorInfo,
+    tile_size: i32,
+    thread_usage_settings: &ThreadUsageSettings,
+    bead_settings: Option<&BeadSettings>,
+    show_progress: bool,
+) -> Bitmap {
+    // Draw color and symbol mapping
+    let mut color_symbol_map =
+        Bitmap::new_filled(2 * tile_size as u32, tile_size as u32, PixelRGBA::white());
+    color_symbol_map.draw_rect_filled(0, 0, tile_size, tile_size, info.color);
+    color_symbol_map.draw_rect(
+        0,
+        0,
+        tile_size,
+        tile_size,
+        PixelRGBA::from_color(Color::black()),
+    );
+    blit_symbol(
+        &info.symbol,
+        &mut color_symbol_map,
+        Vec2i::filled_x(tile_size),
+        PixelRGBA::white(),
+    );
+    color_symbol_map.draw_rect(
+        tile_size,
+        0,
+        tile_size,
+        tile_size,
+        PixelRGBA::from_color(Color::black()),
+    );
+
+    // Add stitches, thread usage and floss info
+    let info_text = format!(
+        " {}      ",
+        legend_entry_text(info, thread_usage_settings, bead_settings, show_progress)
+    );
+    let stitches_info = Bitmap::create_from_text(font, &info_text, 1, PixelRGBA::white());
+    stitches_info.glued_to(
+        &color_symbol_map,
+        GluePosition::RightCenter,
+        0,
+        PixelRGBA::white(),
+    )
+}
+
+/// Example: "120 stitches (118 full, 2 half) - 3.2m (1 skein) - DMC 310 Black"
+///
+/// With `show_progress` the remaining stitches are added, i.e. "120 stitches - 45 remaining - ..."
+pub(crate) fn legend_entry_text(
+    info: &ColorInfo,
+    thread_usage_settings: &ThreadUsageSettings,
+    bead_settings: Option<&BeadSettings>,
+    show_progress: bool,
+) -> String {
+    let mut info_text = if let Some(bead_settings) = bead_settings {
+        let bag_count = bead_settings.bag_count(info.count);
+        format!(
+            "{} beads{} - {} bag{}",
+            info.count,
+            remaining_text(info.count_remaining(), show_progress),
+            bag_count,
+            if bag_count == 1 { "" } else { "s" }
+        )
+    } else {
+        let thread_usage = thread_usage_settings.estimate(info.count_full_equivalent());
+        let stitches_text = if info.has_fractional_stitches() {
+            format!(
+                "{} stitches ({})",
+                info.count,
+                fractional_stitch_counts_text(
+                    info.count_full(),
+                    info.count_half,
+                    info.count_quarter,
+                    info.count_three_quarter
+                )
+            )
+        } else {
+            format!("{} stitches", info.count)
+        };
+        format!(
+            "{}{} - {}",
+            stitches_text,
+            remaining_text(info.count_remaining(), show_progress),
+            thread_usage_text(&thread_usage)
+        )
+    };
+    if let Some(floss) = &info.floss {
+        info_text += &format!(" - {}", floss.display_name());
+    }
+    info_text
+}
+
+fn create_backstitch_legend_entry(
+    font: &BitmapFont,
+    info: &BackstitchInfo,
+    tile_size: i32,
+    thread_usage_settings: &ThreadUsageSettings,
+) -> Bitmap {
+    // Draw a backstitch sample the same way as on the colorized patterns
+    let mut backstitch_sample =
+        Bitmap::new_filled(2 * tile_size as u32, tile_size as u32, PixelRGBA::white());
+    let backstitch_thickness = i32::max(2, tile_size / 6);
+    let start = Vec2i::new(tile_size / 4, tile_size / 2);
+    let end = Vec2i::new(2 * tile_size - tile_size / 4, tile_size / 2);
+    bitmap_draw_line_thick(
+        &mut backstitch_sample,
+        start,
+        end,
+        backstitch_thickness + 2,
+        PixelRGBA::black(),
+    );
+    bitmap_draw_line_thick(
+        &mut backstitch_sample,
+        start,
+        end,
+        backstitch_thickness,
+        info.color,
+    );
+
+    // Add stitches, thread usage and floss info
+    let info_text = format!(
+        " {}      ",
+        backstitch_legend_entry_text(info, thread_usage_settings)
+    );
+    let stitches_info = Bitmap::create_from_text(font, &info_text, 1, PixelRGBA::white());
+    stitches_info.glued_to(
+        &backstitch_sample,
+        GluePosition::RightCenter,
+        0,
+        PixelRGBA::white(),
+    )
+}
+
+/// Example: "Backstitch - 40 stitches - 0.9m (1 skein) - DMC 310 Black"
+pub(crate) fn backstitch_legend_entry_text(
+    info: &BackstitchInfo,
+    thread_usage_settings: &ThreadUsageSettings,
+) -> String {
+    let thread_usage = thread_usage_settings.estimate_backstitch(info.length);
+    let mut info_text = format!(
+        "Backstitch - {} stitches - {}",
+        info.count,
+        thread_usage_text(&thread_usage)
+    );
+    if let Some(floss) = &info.floss {
+        info_text += &format!(" - {}", floss.display_name());
+    }
+    info_text
+}
+
+/// Example: " - 45 remaining" or " - done"
+fn remaining_text(remaining_count: usize, show_progress: bool) -> String {
+    if !show_progress {
+        String::new()
+    } else if remaining_count == 0 {
+        " - done".to_owned()
+    } else {
+        format!(" - {} remaining", remaining_count)
+    }
+}
+
+/// Returns whether any stitch is marked as completed, in which case the legend shows the progress
+pub(crate) fn color_mappings_have_progress(
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+) -> bool {
+    color_mappings.values().any(|info| info.count_completed > 0)
+}
+
+/// Example: "3.2m (1 skein)"
+fn thread_usage_text(thread_usage: &ThreadUsage) -> String {
+    format!(
+        "{:.1}m ({} skein{})",
+        thread_usage.length_meters,
+        thread_usage.skein_count,
+        if thread_usage.skein_count == 1 {
+            ""
+        } else {
+            "s"
+        }
+    )
+}
+
+/// Lists the finished size, the fabric cut size and a hoop or frame for all common fabrics
+fn finished_sizes_text(image_dimensions: Vec2i, fabric_margin_centimeters: i32) -> String {
+    let mut text = format!(
+        "Finished size (fabric cut with {}cm margins):\n\n",
+        fabric_margin_centimeters
+    );
+    for fabric in fabric::FABRICS.iter() {
+        let size = fabric.finished_size(
+            image_dimensions.x,
+            image_dimensions.y,
+            fabric_margin_centimeters,
+        );
+        text += &format!(
+            "  {:<28} {:.1}x{:.1}cm ({:.1}x{:.1}in) - cut {:.0}x{:.0}cm - {}\n",
+            format!("{}:", fabric.name),
+            size.width_centimeters,
+            size.height_centimeters,
+            size.width_inches,
+            size.height_inches,
+            // NOTE: We rather cut a bit too much fabric than too little
+            size.cut_width_centimeters.ceil(),
+            size.cut_height_centimeters.ceil(),
+            size.frame.display_name()
+        );
+    }
+    text + "\n"
+}
+
+/// The size, color, stitch and thread statistics at the top of the legend
+pub(crate) fn legend_stats_text(
+    image_dimensions: Vec2i,
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    backstitch_infos: &IndexMap<PixelRGBA, BackstitchInfo>,
+    thread_usage_settings: &ThreadUsageSettings,
+    fabric_margin_centimeters: i32,
+    bead_settings: Option<&BeadSettings>,
+) -> String {
+    let color_count = color_mappings.len();
+    let stitch_count = color_mappings
+        .values()
+        .fold(0, |acc, entry| acc + entry.count);
+
+    let mut stats_text = format!(
+        "Size:     {}x{}\n\nColors:   {}\n\n",
+        image_dimensions.x, image_dimensions.y, color_count
+    );
+    if color_mappings_have_progress(color_mappings) {
+        let completed_count: usize = color_mappings
+            .values()
+            .map(|entry| entry.count_completed)
+            .sum();
+        // NOTE: We round down so that we only reach 100% when everything is done
+        stats_text += &format!(
+            "Progress: {}% complete - {} {} remaining\n\n",
+            100 * completed_count / stitch_count,
+            stitch_count - completed_count,
+            if bead_settings.is_some() {
+                "beads"
+            } else {
+                "stitches"
+            }
+        );
+    }
+    if let Some(bead_settings) = bead_settings {
+        stats_text += &format!(
+            "Beads:    {}\n\nBoards:   {} ({}x{})\n\n",
+            stitch_count,
+            bead_settings.pegboard_count(image_dimensions.x, image_dimensions.y),
+            bead_settings.pegboard_width,
+            bead_settings.pegboard_height
+        );
+        if let Some(bead) = color_mappings
+            .values()
+            .find_map(|entry| entry.floss.as_ref())
+        {
+            stats_text += &format!("Brand:    {}\n\n", bead.catalog_name);
+        }
+        let bag_count: usize = color_mappings
+            .values()
+            .map(|entry| bead_settings.bag_count(entry.count))
+            .sum();
+        stats_text += &format!(
+            "Bags:     {} (bags of {})\n\n",
+            bag_count, bead_settings.bag_size
+        );
+    } else {
+        stats_text += &format!("Stitches: {}\n\n", stitch_count);
+        if color_mappings
+            .values()
+            .any(|entry| entry.has_fractional_stitches())
+        {
+            let sum_counts = |count_of_entry: fn(&ColorInfo) -> usize| -> usize {
+                color_mappings.values().map(count_of_entry).sum()
+            };
+            stats_text += &format!(
+                "          {}\n\n",
+                fractional_stitch_counts_text(
+                    sum_counts(|entry| entry.count_full()),
+                    sum_counts(|entry| entry.count_half),
+                    sum_counts(|entry| entry.count_quarter),
+                    sum_counts(|entry| entry.count_three_quarter),
+                )
+            );
+        }
+        if !backstitch_infos.is_empty() {
+            let backstitch_count: usize = backstitch_infos.values().map(|entry| entry.count).sum();
+            stats_text += &format!("          {} backstitches\n\n", backstitch_count);
+        }
+        if let Some(floss) = color_mappings
+            .values()
+            .find_map(|entry| entry.floss.as_ref())
+        {
+            stats_text += &format!("Floss:    {}\n\n", floss.catalog_name);
+        }
+        let (thread_length_meters, skein_count) = color_mappings
+            .values()
+            .map(|entry| thread_usage_settings.estimate(entry.count_full_equivalent()))
+            .chain(
+                backstitch_infos
+                    .values()
+                    .map(|entry| thread_usage_settings.estimate_backstitch(entry.length)),
+            )
+            .fold((0.0, 0), |(length_meters, skein_count), thread_usage| {
+                (
+                    length_meters + thread_usage.length_meters,
+                    skein_count + thread_usage.skein_count,
+                )
+            });
+        stats_text += &format!(
+            "Thread:   {:.1}m in {} skeins ({})\n\n",
+            thread_length_meters,
+            skein_count,
+            thread_usage_settings.display_name()
+        );
+        stats_text += &finished_sizes_text(image_dimensions, fabric_margin_centimeters);
+    }
+    stats_text + "\n"
+}
+
+fn create_legend_block(entries: &[Bitmap], tile_size: i32) -> Bitmap {
+    Bitmap::glue_together_multiple(
+        entries,
+        GluePosition::BottomLeft,
+        tile_size,
+        PixelRGBA::white(),
+    )
+}
+
+fn create_cross_stitch_legend(
+    image_dimensions: Vec2i,
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    backstitch_infos: &IndexMap<PixelRGBA, BackstitchInfo>,
+    font: &BitmapFont,
+    tile_size: i32,
+    segment_layout_indices: &[Vec2i],
+    options: &PatternOptions,
+) -> Bitmap {
+    let thread_usage_settings = &options.thread_usage;
+    let bead_settings = options.beads.as_ref();
+    let mut legend = {
+        // Create color and stitch stats
+        let stats_bitmap = {
+            let stats_text = legend_stats_text(
+                image_dimensions,
+                color_mappings,
+                backstitch_infos,
+                thread_usage_settings,
+                options.fabric_margin_centimeters,
+                bead_settings,
+            );
+            Bitmap::create_from_text(font, &stats_text, 1, PixelRGBA::white())
+        };
+
+        // Create color mapping blocks
+        let blocks = {
+            // NOTE: Backstitches come after all colors
+            let show_progress = color_mappings_have_progress(color_mappings);
+            let entries: Vec<Bitmap> = color_mappings
+                .values()
+                .map(|info| {
+                    create_legend_entry(
+                        font,
+                        info,
+                        tile_size,
+                        thread_usage_settings,
+                        bead_settings,
+                        show_progress,
+                    )
+                })
+                .chain(backstitch_infos.values().map(|info| {
+                    create_backstitch_legend_entry(font, info, tile_size, thread_usage_settings)
+                }))
+                .collect();
+            let block_bitmaps: Vec<Bitmap> = entries
+                .chunks(LEGEND_BLOCK_ENTRY_COUNT)
+                .map(|chunk| create_legend_block(chunk, tile_size))
+                .collect();
+            let num_columns = block_bitmaps.len().max(4);
+            let block_rows: Vec<Bitmap> = block_bitmaps
+                .chunks(num_columns)
+                .map(|chunk| {
+                    Bitmap::glue_together_multiple(
+                        chunk,
+                        GluePosition::RightTop,
+                        tile_size,
+                        PixelRGBA::white(),
+                    )
+                })
+                .collect();
+            Bitmap::glue_together_multiple(
+                &block_rows,
+                GluePosition::BottomLeft,
+                tile_size,
+                PixelRGBA::white(),
+            )
+            .extended(0, 0, 0, (1.5 * tile_size as f32) as i32, PixelRGBA::white())
+        };
+
+        Bitmap::glue_a_to_b(
+            &stats_bitmap,
+            &blocks,
+            GluePosition::TopLeft,
+            0,
+            PixelRGBA::white(),
+        )
+    };
+
+    // Add page layout order if necessary
+    if segment_layout_indices.len() > 1 {
+        let page_layout_image = create_pattern_page_layout(font, segment_layout_indices);
+
+        legend = legend.glued_to(
+            &page_layout_image,
+            GluePosition::TopLeft,
+            0,
+            PixelRGBA::white(),
+        );
+
+        // Draw separating line between colors and page order layout
+        for x in 0..legend.width {
+            legend.set(
+                x,
+                legend.height - page_layout_image.height,
+                PixelRGBA::black(),
+            );
+        }
+    }
+
+    let padding = tile_size;
+    legend.extended(padding, padding, padding, padding, PixelRGBA::white())
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Thread usage report
+
+/// Creates a `.csv` table with the estimated thread usage of every color and of the whole design.
+/// Backstitch colors get their own rows.
+pub fn create_thread_usage_report(
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    backstitch_infos: &IndexMap<PixelRGBA, BackstitchInfo>,
+    thread_usage_settings: &ThreadUsageSettings,
+) -> String {
+    let mut report = format!(
+        "# Estimated thread usage for {}\n",
+        thread_usage_settings.display_name()
+    );
+    // NOTE: The fractional stitch columns come last so that older spreadsheets still work
+    report += "color,floss,stitches,length_meters,skeins,half,quarter,three_quarter\n";
+
+    let mut total_stitch_count = 0;
+    let mut total_half_count = 0;
+    let mut total_quarter_count = 0;
+    let mut total_three_quarter_count = 0;
+    let mut total_length_meters = 0.0;
+    let mut total_skein_count = 0;
+    for info in color_mappings.values() {
+        let thread_usage = thread_usage_settings.estimate(info.count_full_equivalent());
+        report += &format!(
+            "{},{},{},{:.2},{},{},{},{}\n",
+            color_to_hex_string(info.color),
+            info.floss
+                .as_ref()
+                .map(|floss| floss.display_name())
+                .unwrap_or_default(),
+            info.count,
+            thread_usage.length_meters,
+            thread_usage.skein_count,
+            info.count_half,
+            info.count_quarter,
+            info.count_three_quarter
+        );
+        total_stitch_count += info.count;
+        total_half_count += info.count_half;
+        total_quarter_count += info.count_quarter;
+        total_three_quarter_count += info.count_three_quarter;
+        total_length_meters += thread_usage.length_meters;
+        total_skein_count += thread_usage.skein_count;
+    }
+    for info in backstitch_infos.values() {
+        let thread_usage = thread_usage_settings.estimate_backstitch(info.length);
+        report += &format!(
+            "{} backstitch,{},{},{:.2},{},,,\n",
+            color_to_hex_string(info.color),
+            info.floss
+                .as_ref()
+                .map(|floss| floss.display_name())
+                .unwrap_or_default(),
+            info.count,
+            thread_usage.length_meters,
+            thread_usage.skein_count
+        );
+        total_stitch_count += info.count;
+        total_length_meters += thread_usage.length_meters;
+        total_skein_count += thread_usage.skein_count;
+    }
+    report += &format!(
+        "total,,{},{:.2},{},{},{},{}\n",
+        total_stitch_count,
+        total_length_meters,
+        total_skein_count,
+        total_half_count,
+        total_quarter_count,
+        total_three_quarter_count
+    );
+
+    report
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Bead usage report
+
+/// Creates a `.csv` table with the number of beads and bags of every color and of the whole
+/// design
+pub fn create_bead_usage_report(
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    bead_settings: &BeadSettings,
+) -> String {
+    let mut report = format!("# Bead usage for {}\n", bead_settings.display_name());
+    report += "color,bead,beads,bags\n";
+
+    let mut total_bead_count = 0;
+    let mut total_bag_count = 0;
+    for info in color_mappings.values() {
+        let bag_count = bead_settings.bag_count(info.count);
+        report += &format!(
+            "{},{},{},{}\n",
+            color_to_hex_string(info.color),
+            info.floss
+                .as_ref()
+                .map(|bead| bead.display_name())
+                .unwrap_or_default(),
+            info.count,
+            bag_count
+        );
+        total_bead_count += info.count;
+        total_bag_count += bag_count;
+    }
+    report += &format!("total,,{},{}\n", total_bead_count, total_bag_count);
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_keep_labels_and_symbols_of_the_shared_palette() {
+        let colors = [
+            PixelRGBA::new(220, 20, 20, 255),
+            PixelRGBA::new(20, 220, 20, 255),
+            PixelRGBA::new(20, 20, 220, 255),
+        ];
+        let frame_1 = Bitmap::new_from_buffer(3, 1, colors.to_vec());
+        let shared_color_mappings = image_extract_colors_and_counts(&frame_1);
+
+        // NOTE: The second frame lacks the first color of the shared legend
+        let shared_colors: Vec<PixelRGBA> = shared_color_mappings.keys().cloned().collect();
+        let frame_2 = Bitmap::new_from_buffer(2, 1, shared_colors[1..].to_vec());
+        let mut color_mappings = image_extract_colors_and_counts(&frame_2);
+        assert_eq!(color_mappings[&shared_colors[2]].label_index, 1);
+
+        color_mappings_apply_predefined(&mut color_mappings, &shared_color_mappings);
+        assert_eq!(color_mappings.len(), 2);
+        for (color, info) in &color_mappings {
+            let shared_info = &shared_color_mappings[color];
+            assert_eq!(info.label_index, shared_info.label_index);
+            assert_eq!(info.symbol_index, shared_info.symbol_index);
+        }
+        assert_eq!(color_mappings[&shared_colors[2]].label_index, 2);
+    }
+
+    #[test]
+    fn pattern_options_validate_rejects_unusable_segments() {
+        assert!(PatternOptions::default().validate().is_ok());
+
+        let options =
+            |segment_width: i32, segment_height: i32, segment_overlap: i32| PatternOptions {
+                segment_width,
+                segment_height,
+                segment_overlap,
+                ..PatternOptions::default()
+            };
+        assert!(options(10, 20, 9).validate().is_ok());
+        assert!(options(10, 20, 10).validate().is_err());
+        assert!(options(20, 10, 10).validate().is_err());
+        assert!(options(10, 20, -1).validate().is_err());
+        assert!(options(0, 20, 0).validate().is_err());
+
+        let beads_options = PatternOptions {
+            beads: Some(BeadSettings {
+                pegboard_width: 0,
+                ..BeadSettings::default()
+            }),
+            ..PatternOptions::default()
+        };
+        assert!(beads_options.validate().is_err());
+    }
+
+    #[test]
+    fn layers_must_have_the_image_size() {
+        let image = Bitmap::new(4, 3);
+        let stitch_kinds = StitchKindMap::new_full(4, 3);
+        let progress = ProgressMap::new_empty(4, 3);
+        assert!(check_layer_dimensions(&image, &stitch_kinds, Some(&progress)).is_ok());
+
+        let stitch_kinds_too_small = StitchKindMap::new_full(3, 3);
+        assert!(check_layer_dimensions(&image, &stitch_kinds_too_small, None).is_err());
+
+        let progress_too_big = ProgressMap::new_empty(4, 4);
+        assert!(check_layer_dimensions(&image, &stitch_kinds, Some(&progress_too_big)).is_err());
+    }
+
+    #[test]
+    fn alphanumeric_labels_count_up_through_longer_labels() {
+        assert_eq!(alphanumeric_symbol_label(0), "1");
+        assert_eq!(alphanumeric_symbol_label(34), "Z");
+        assert_eq!(alphanumeric_symbol_label(35), "11");
+        assert_eq!(alphanumeric_symbol_label(35 + 35 * 35 - 1), "ZZ");
+        assert_eq!(alphanumeric_symbol_label(35 + 35 * 35), "111");
+
+        let mut labels = std::collections::HashSet::new();
+        for symbol_index in 0..5000 {
+            let label = alphanumeric_symbol_label(symbol_index);
+            assert_eq!(
+                alphanumeric_symbol_label_to_index(&label),
+                Some(symbol_index)
+            );
+            assert!(labels.insert(label));
+        }
+    }
+}
@@ -1,54 +1,132 @@
 #![windows_subsystem = "windows"]
 
+use pixie_stitch::background::{self, KeyColor};
+use pixie_stitch::backstitch;
+use pixie_stitch::beads::BeadSettings;
+use pixie_stitch::color_quantization::{self, color_from_hex_string, ColorReduction};
+use pixie_stitch::floss::FlossCatalog;
+use pixie_stitch::image_formats::{self, ImageFormat};
+use pixie_stitch::pdf::{self, PageSize};
+use pixie_stitch::pixel_scale;
+use pixie_stitch::progress::{self, ProgressMap};
+use pixie_stitch::stitch_kinds::{self, StitchKindMap};
+use pixie_stitch::*;
+use pixie_stitch::{aseprite, oxs, svg};
+
+use cottontail::core::PathHelper;
 use cottontail::core::*;
-use cottontail::image::{bitmap::*, color::hsl, font::*};
-use cottontail::math::*;
-use cottontail::{core::PathHelper, image::ColorBlendMode};
+use cottontail::image::{bitmap::*, color::hsl};
 
 use gif::SetParameter;
 use indexmap::IndexMap;
 use rayon::prelude::*;
-use winapi;
-
-use std::fs::File;
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-// Constants
-
-const TILE_SIZE: i32 = 16;
-const LEGEND_BLOCK_ENTRY_COUNT: usize = 5;
-const SPLIT_SEGMENT_WIDTH: i32 = 60;
-const SPLIT_SEGMENT_HEIGHT: i32 = 80;
-const COLOR_GRID_THIN: PixelRGBA = PixelRGBA::new(128, 128, 128, 255);
-const COLOR_GRID_THICK: PixelRGBA = PixelRGBA::new(64, 64, 64, 255);
-
-enum PatternType {
-    BlackAndWhite,
-    Colorized,
-    ColorizedNoSymbols,
-    PaintByNumbers,
+// Options
+
+/// Which frames of animated images we create patterns for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum FrameSelection {
+    /// Zero-based frame index
+    Single(usize),
+    All,
+}
+
+/// How we find out whether an image was exported at a multiple of its native resolution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PixelScaleSelection {
+    Auto,
+    /// A factor of 1 keeps the image as it is
+    Factor(i32),
 }
 
-struct Resources {
-    font: BitmapFont,
-    font_big: BitmapFont,
-    stitch_background_image_8x8_premultiplied_alpha: Bitmap,
+/// Where the output dirs of an image are created
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OutputRoot {
+    ExecutableDir,
+    /// Next to the source image
+    ImageDir,
+    Dir(String),
+}
+
+/// What to do if the output dir of an image already exists
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ExistingOutput {
+    /// Delete the old output dir
+    Replace,
+    /// Write into the next free `version_N` subdir of the old output dir
+    Version,
+    /// Write into a subdir of the old output dir named by the given time of the run
+    Timestamp(String),
+    /// Fail the image
+    Refuse,
 }
 
 #[derive(Clone)]
-struct ColorInfo {
-    pub color: PixelRGBA,
-    pub count: usize,
-    pub symbol: Bitmap,
-    pub symbol_alphanum: Bitmap,
-    pub stitches_premultiplied: Vec<Bitmap>,
+struct Options {
+    pub image_filepaths: Vec<String>,
+    pub tile_size: i32,
+    pub pattern: PatternOptions,
+    pub max_colors: Option<usize>,
+    pub frame_selection: FrameSelection,
+    pub pixel_scale: PixelScaleSelection,
+    /// Background colors that are left unstitched
+    pub key_colors: Vec<KeyColor>,
+    /// Pixels with a lower alpha are left unstitched
+    pub alpha_threshold: u8,
+    pub floss_catalog: Option<String>,
+    pub snap_to_floss: bool,
+    /// Bead catalog name. If given we create fusible bead patterns via `pattern.beads`
+    pub bead_catalog: Option<String>,
+    pub bead_settings: BeadSettings,
+    pub page_size: PageSize,
+    pub format_png: bool,
+    pub format_pdf: bool,
+    pub format_oxs: bool,
+    pub format_svg: bool,
+    /// Desktop notification when all images are done on Linux and macOS
+    pub desktop_notifications: bool,
+    pub output_root: OutputRoot,
+    pub existing_output: ExistingOutput,
+    /// The regions of these colors get outlined with backstitches
+    pub backstitch_outline_colors: Vec<PixelRGBA>,
+    pub backstitch_outline_color: PixelRGBA,
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options {
+            image_filepaths: Vec::new(),
+            tile_size: TILE_SIZE_DEFAULT,
+            pattern: PatternOptions::default(),
+            max_colors: None,
+            frame_selection: FrameSelection::Single(0),
+            pixel_scale: PixelScaleSelection::Auto,
+            key_colors: Vec::new(),
+            alpha_threshold: background::ALPHA_THRESHOLD_DEFAULT,
+            floss_catalog: None,
+            snap_to_floss: false,
+            bead_catalog: None,
+            bead_settings: BeadSettings::default(),
+            page_size: PageSize::A4,
+            format_png: true,
+            format_pdf: false,
+            format_oxs: false,
+            format_svg: false,
+            desktop_notifications: true,
+            output_root: OutputRoot::ExecutableDir,
+            existing_output: ExistingOutput::Version,
+            backstitch_outline_colors: Vec::new(),
+            backstitch_outline_color: PixelRGBA::black(),
+        }
+    }
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Paths
 
 fn get_executable_dir() -> String {
-    if let Some(executable_path) = std::env::current_exe().ok() {
+    if let Ok(executable_path) = std::env::current_exe() {
         path_without_filename(executable_path.to_string_borrowed_or_panic())
     } else {
         ".".to_owned()
@@ -61,10 +139,26 @@ fn get_executable_dir() -> String {
 /// output_dir_suffix: "centered"
 ///
 /// This returns:
-/// "C:\bin\example_image_centered"
-fn get_image_output_dir(image_filepath: &str, output_dir_suffix: &str) -> String {
+/// "C:\bin\example_image_centered" for `OutputRoot::ExecutableDir`
+/// "D:\images\example_image_centered" for `OutputRoot::ImageDir`
+fn get_image_output_dir(
+    image_filepath: &str,
+    output_dir_suffix: &str,
+    output_root: &OutputRoot,
+) -> String {
     let image_filename = path_to_filename_without_extension(image_filepath);
-    let output_dir_root = get_executable_dir();
+    let output_dir_root = match output_root {
+        OutputRoot::ExecutableDir => get_executable_dir(),
+        OutputRoot::ImageDir => {
+            let image_dir = path_without_filename(image_filepath);
+            if image_dir.is_empty() {
+                ".".to_owned()
+            } else {
+                image_dir
+            }
+        }
+        OutputRoot::Dir(dir) => dir.clone(),
+    };
     if output_dir_suffix.is_empty() {
         path_join(&output_dir_root, &image_filename)
     } else {
@@ -75,1367 +169,1128 @@ fn get_image_output_dir(image_filepath: &str, output_dir_suffix: &str) -> String
     }
 }
 
-// NOTE: This is for quicker testing to keep images open in imageviewer
-#[cfg(debug_assertions)]
-fn create_image_output_dir(image_filepath: &str, output_dir_suffix: &str) {
-    let output_dir = get_image_output_dir(image_filepath, output_dir_suffix);
-    if !path_exists(&output_dir) {
-        std::fs::create_dir_all(&output_dir)
-            .expect(&format!("Cannot create directory '{}'", &output_dir));
+/// Returns an error if existing output dirs are refused and one of the output dirs of the given
+/// image with the given suffixes already exists
+fn check_image_output_dirs_available(
+    image_filepath: &str,
+    output_dir_suffixes: &[&str],
+    options: &Options,
+) -> Result<()> {
+    if options.existing_output != ExistingOutput::Refuse {
+        return Ok(());
     }
-}
-
-#[cfg(not(debug_assertions))]
-fn create_image_output_dir(image_filepath: &str, output_dir_suffix: &str) {
-    let output_dir = get_image_output_dir(image_filepath, output_dir_suffix);
-    if path_exists(&output_dir) {
-        std::fs::remove_dir_all(&output_dir).expect(&format!(
-            "Cannot overwrite directory '{}': is a file from it still open?",
-            &output_dir
-        ));
+    for output_dir_suffix in output_dir_suffixes {
+        let output_dir =
+            get_image_output_dir(image_filepath, output_dir_suffix, &options.output_root);
+        if path_exists(&output_dir) {
+            return Err(Error::OutputDirExists { path: output_dir });
+        }
     }
-    std::fs::create_dir_all(&output_dir)
-        .expect(&format!("Cannot create directory '{}'", &output_dir));
+    Ok(())
 }
 
-fn get_image_output_filepath(image_filepath: &str, output_dir_suffix: &str) -> String {
-    let output_dir = get_image_output_dir(image_filepath, output_dir_suffix);
-    let image_filename = path_to_filename_without_extension(image_filepath);
-    path_join(&output_dir, &image_filename)
+/// Returns the first `version_N` subdir of the given dir that does not exist yet, starting at 1
+fn get_next_version_dir(output_dir: &str) -> String {
+    (1..)
+        .map(|version| path_join(output_dir, &format!("version_{}", version)))
+        .find(|version_dir| !path_exists(version_dir))
+        // NOTE: We can unwrap here because the iterator is endless
+        .unwrap()
 }
 
-// NOTE: THIS IS FOR INTERNAL TESTING
-#[cfg(debug_assertions)]
-fn get_image_filepaths_from_commandline() -> Vec<String> {
-    vec![
-        "examples/nathan.png".to_owned(),
-        "examples/nathan_big.gif".to_owned(),
-        "examples/pixie.png".to_owned(),
-        // "nachtlicht-pixel2.gif".to_owned(),
-        // "nachtlicht-pixel2wolke.png".to_owned(),
-    ]
+/// Returns the first subdir of the given dir named by the given timestamp that does not exist yet.
+/// If a previous run within the same second already used the timestamp we append "_2", "_3", ...
+fn get_next_timestamp_dir(output_dir: &str, timestamp: &str) -> String {
+    (1..)
+        .map(|index| {
+            if index == 1 {
+                path_join(output_dir, timestamp)
+            } else {
+                path_join(output_dir, &format!("{}_{}", timestamp, index))
+            }
+        })
+        .find(|timestamp_dir| !path_exists(timestamp_dir))
+        // NOTE: We can unwrap here because the iterator is endless
+        .unwrap()
 }
 
-#[cfg(not(debug_assertions))]
-fn get_image_filepaths_from_commandline() -> Vec<String> {
-    let mut args: Vec<String> = std::env::args().collect();
-
-    // NOTE: The first argument is the executable path
-    args.remove(0);
-
-    assert!(
-        !args.is_empty(),
-        "Please drag and drop one (or more) image(s) onto the executable"
-    );
-
-    args
+/// Returns the current UTC time like "2021-03-14_15-09-26"
+fn get_timestamp_now() -> String {
+    let seconds_since_epoch = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0);
+    timestamp_from_unix_seconds(seconds_since_epoch)
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Loading resources
-
-fn get_resource_dir_path() -> String {
-    let executable_dir_path = get_executable_dir();
-    let resource_dir_path = {
-        let candidate = path_join(&executable_dir_path, "resources");
-
-        if path_exists(&candidate) {
-            candidate
-        } else {
-            // There was no symbols dir in the executable dir. Lets try our current workingdir
-            "resources".to_owned()
-        }
+/// Returns the UTC time of the given seconds since 1970-01-01 like "2021-03-14_15-09-26"
+fn timestamp_from_unix_seconds(seconds_since_epoch: i64) -> String {
+    let days = seconds_since_epoch.div_euclid(86400);
+    let seconds_of_day = seconds_since_epoch.rem_euclid(86400);
+
+    // NOTE: This converts days since 1970-01-01 to a gregorian calendar date, see
+    //       http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_shifted = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_shifted + 2) / 5 + 1;
+    let month = if month_shifted < 10 {
+        month_shifted + 3
+    } else {
+        month_shifted - 9
     };
-
-    assert!(
-        path_exists(&resource_dir_path),
-        "Missing `resources` path in '{}'",
-        executable_dir_path
-    );
-
-    resource_dir_path
-}
-
-fn load_stitch_preview_images_premultiplied_alpha() -> (Vec<Bitmap>, Vec<Bitmap>, Bitmap) {
-    let resource_dir_path = get_resource_dir_path();
-    let background_tile_image_8x8 =
-        Bitmap::from_png_file_or_panic(&path_join(&resource_dir_path, "aida_8x8.png"))
-            .to_premultiplied_alpha();
-    let stitch_tile_images = ["stitch1.png", "stitch2.png", "stitch3.png"]
-        .iter()
-        .map(|filename| {
-            Bitmap::from_png_file_or_panic(&path_join(&resource_dir_path, filename))
-                .to_premultiplied_alpha()
-        })
-        .collect();
-    let stitch_tile_images_luminance = ["stitch1_lum.png", "stitch2_lum.png", "stitch3_lum.png"]
-        .iter()
-        .map(|filename| {
-            Bitmap::from_png_file_or_panic(&path_join(&resource_dir_path, filename))
-                .to_premultiplied_alpha()
-        })
-        .collect();
-    (
-        stitch_tile_images,
-        stitch_tile_images_luminance,
-        background_tile_image_8x8,
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        (seconds_of_day / 60) % 60,
+        seconds_of_day % 60
     )
 }
 
-pub fn load_fonts() -> (BitmapFont, BitmapFont) {
-    let mut font_regular = BitmapFont::new(
-        FONT_DEFAULT_TINY_NAME,
-        FONT_DEFAULT_TINY_TTF,
-        FONT_DEFAULT_TINY_PIXEL_HEIGHT,
-        FONT_DEFAULT_TINY_RASTER_OFFSET,
-        0,
-        0,
-        PixelRGBA::black(),
-        PixelRGBA::transparent(),
-    );
-    let mut font_big = BitmapFont::new(
-        FONT_DEFAULT_REGULAR_NAME,
-        FONT_DEFAULT_REGULAR_TTF,
-        2 * FONT_DEFAULT_REGULAR_PIXEL_HEIGHT,
-        FONT_DEFAULT_REGULAR_RASTER_OFFSET,
-        0,
-        0,
-        PixelRGBA::black(),
-        PixelRGBA::transparent(),
-    );
-
-    // NOTE: Because 0 looks like an 8 in this font on crappy printers we replace it with an O (big o)
-    let regular_o = font_regular
-        .glyphs
-        .get(&('O' as Codepoint))
-        .unwrap()
-        .clone();
-    let big_o = font_big.glyphs.get(&('O' as Codepoint)).unwrap().clone();
-    font_regular.glyphs.insert('0' as Codepoint, regular_o);
-    font_big.glyphs.insert('0' as Codepoint, big_o);
-
-    (font_regular, font_big)
-}
+/// Creates the output dir for the given image and returns the base path for all output files,
+/// i.e. "C:\bin\example_image_centered\example_image". What happens with an already existing
+/// output dir depends on `options.existing_output`.
+fn create_image_output_dir(
+    image_filepath: &str,
+    output_dir_suffix: &str,
+    options: &Options,
+) -> Result<String> {
+    let mut output_dir =
+        get_image_output_dir(image_filepath, output_dir_suffix, &options.output_root);
+    if path_exists(&output_dir) {
+        match &options.existing_output {
+            ExistingOutput::Replace => {
+                // NOTE: In debug builds we keep the dir for quicker testing to keep images open in
+                //       the imageviewer
+                if !cfg!(debug_assertions) {
+                    // NOTE: This fails most likely because a file from the directory is still open
+                    std::fs::remove_dir_all(&output_dir)
+                        .map_err(|error| Error::io(&output_dir, error))?;
+                }
+            }
+            ExistingOutput::Version => output_dir = get_next_version_dir(&output_dir),
+            ExistingOutput::Timestamp(timestamp) => {
+                output_dir = get_next_timestamp_dir(&output_dir, timestamp)
+            }
+            ExistingOutput::Refuse => return Err(Error::OutputDirExists { path: output_dir }),
+        }
+    }
+    std::fs::create_dir_all(&output_dir).map_err(|error| Error::io(&output_dir, error))?;
 
-fn collect_symbols() -> Vec<Bitmap> {
-    let resource_dir_path = get_resource_dir_path();
-    let symbols_filepaths = collect_files_by_extension_recursive(&resource_dir_path, ".png");
-    symbols_filepaths
-        .into_iter()
-        .filter(|filepath| {
-            path_to_filename_without_extension(filepath)
-                .parse::<u32>()
-                .is_ok()
-        })
-        .map(|symbol_filepath| Bitmap::from_png_file_or_panic(&symbol_filepath))
-        .collect()
+    let image_filename = path_to_filename_without_extension(image_filepath);
+    Ok(path_join(&output_dir, &image_filename))
 }
 
-fn create_alphanumeric_symbols(font: &BitmapFont) -> Vec<Bitmap> {
-    let mut symbols = Vec::new();
-    for c in "123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ".chars() {
-        let mut bitmap =
-            Bitmap::new_filled(TILE_SIZE as u32, TILE_SIZE as u32, PixelRGBA::transparent());
-        // NOTE: We can unwrap here because we own the font and know that all glyphs exist
-        let glyph_bitmap = font
-            .glyphs
-            .get(&(c as Codepoint))
-            .as_ref()
-            .unwrap()
-            .bitmap
-            .as_ref()
-            .unwrap();
-        let pos = Vec2i::new(
-            block_centered_in_block(glyph_bitmap.width, TILE_SIZE),
-            block_centered_in_block(glyph_bitmap.height, TILE_SIZE),
-        );
-        blit_symbol(glyph_bitmap, &mut bitmap, pos, PixelRGBA::transparent());
-        symbols.push(bitmap);
+/// Example:
+/// imagepath: "D:\images\walk_cycle.gif"
+/// frame_index: 2
+///
+/// This returns:
+/// "D:\images\walk_cycle_frame_3.gif"
+fn get_frame_filepath(image_filepath: &str, frame_index: usize) -> String {
+    let path = std::path::Path::new(image_filepath);
+    let mut frame_filename = format!(
+        "{}_frame_{}",
+        path_to_filename_without_extension(image_filepath),
+        frame_index + 1
+    );
+    if let Some(extension) = path.extension() {
+        frame_filename += ".";
+        frame_filename += &extension.to_string_lossy();
     }
-
-    symbols
+    path.with_file_name(frame_filename)
+        .to_string_lossy()
+        .into_owned()
 }
 
-fn open_image(image_filepath: &str) -> Bitmap {
-    if path_to_extension(&image_filepath).ends_with("gif") {
-        bitmap_create_from_gif_file(&image_filepath)
-    } else if path_to_extension(&image_filepath).ends_with("png") {
-        Bitmap::from_png_file_or_panic(&image_filepath)
-    } else {
-        panic!("We only support GIF or PNG images");
-    }
+/// Example: `images/cat.gif` with name `fractional` -> `images/cat_fractional.png`
+fn get_auxiliary_image_filepath(image_filepath: &str, name: &str) -> String {
+    let auxiliary_filename = format!(
+        "{}_{}.png",
+        path_to_filename_without_extension(image_filepath),
+        name
+    );
+    std::path::Path::new(image_filepath)
+        .with_file_name(auxiliary_filename)
+        .to_string_lossy()
+        .into_owned()
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-// Low level bitmap helper function
+// Commandline
+
+const USAGE: &str = "\
+Usage: pixie_stitch [OPTIONS] IMAGE...
+
+Creates cross-stitch patterns for the given images. Every image gets its own output directories
+next to the executable (see `--output-root`). Drag-and-dropping images onto the executable is
+the same as passing them without any options. Supported are PNG, GIF, BMP, TGA and Aseprite
+images as well as Open Cross Stitch `.oxs` files.
+
+Half, quarter and three-quarter stitches are read from a `fractional` layer of Aseprite files or
+from a mask image next to the image (i.e. `cat_fractional.png` for `cat.png`). Backstitches are
+read the same way from a `backstitch` layer or image and already stitched cells from a `progress`
+layer or image. See the README for details.
+
+Options:
+  -h, --help                   Print this help and exit
+  --tile-size <PIXELS>         Size of one stitch in the pattern images [default: 16, minimum: 10]
+  --segment-width <STITCHES>   Number of stitch columns per pattern part [default: 60]
+  --segment-height <STITCHES>  Number of stitch rows per pattern part [default: 80]
+  --segment-overlap <STITCHES> Number of stitch rows and columns of the previous pattern part
+                               that are repeated shaded on the next part [default: 0]
+  --max-colors <COUNT>         Reduce images with more colors to at most COUNT colors before
+                               creating patterns. A report of all merged colors is written to
+                               the output directories
+  --frame <NUMBER|all>         Frame of animated GIFs and Aseprite files to create patterns for.
+                               With `all` every frame gets its own patterns with shared colors
+                               and symbols
+                               [default: 1]
+  --pixel-scale <FACTOR|auto>  Images that were exported at a multiple of their native resolution
+                               (i.e. 4x or 8x) are reduced to one pixel per stitch. With `auto`
+                               the factor and offset are detected, a factor of 1 keeps the
+                               image as it is. Detection can mistake pixel art whose features
+                               are all at least two pixels wide for upscaled pixel art, in that
+                               case use `--pixel-scale 1` [default: auto]
+  --key-color <LIST>           Comma separated list of background colors (i.e. `#FF00FF`) that are
+                               left unstitched like transparent pixels. With `corner` the color of
+                               the top-left pixel is used
+  --alpha-threshold <ALPHA>    Pixels with an alpha below ALPHA (0-255) are left unstitched. More
+                               opaque pixels are blended onto the fabric color [default: 1]
+  --floss <CATALOG>            Match every color to the closest thread of a floss catalog and
+                               show it in the legend. Bundled catalogs: dmc, anchor. Additional
+                               catalogs can be added as `floss_<CATALOG>.csv` to `resources`
+  --snap-to-floss              Replace the image colors by the exact colors of their matched
+                               threads. Requires `--floss`
+  --beads <BRAND>              Create fusible bead patterns instead of cross stitch patterns. All
+                               colors are replaced by the closest bead of the brand and every
+                               pegboard gets its own pattern part. Bundled brands: hama, perler,
+                               artkal. Additional brands can be added as `beads_<BRAND>.csv` to
+                               `resources`
+  --pegboard <SIZE>            Pegboard size for `--beads` like `29` or `28x28` [default: 29x29]
+  --bead-bag-size <COUNT>      Number of beads per bag for the bag counts of `--beads`
+                               [default: 1000]
+  --fabric-count <COUNT>       Stitches per inch of the fabric used for the thread estimates in
+                               the legend [default: 14]
+  --strands <COUNT>            Number of strands stitched with at once [default: 2, maximum: 6]
+  --waste-percent <PERCENT>    Extra thread added to the estimates for tails and travelling
+                               between stitches [default: 20]
+  --fabric-margin <CM>         Fabric added on every side of the design for the fabric cut sizes
+                               in the legend [default: 5]
+  --fabric-color <COLOR>       Color of the fabric that shows through unstitched cells of the
+                               colorized patterns and the preview [default: #FFFFFF]
+  --outputs <LIST>             Comma separated list of outputs to create
+                               [default: plain,centered,preview]
+                               plain:    patterns with the origin in the top-left corner
+                               centered: patterns with the origin in the image center
+                               preview:  realistic preview of the finished stitching
+  --formats <LIST>             Comma separated list of file formats for the patterns and legend
+                               [default: png]
+                               png: one image per pattern, pattern part and legend
+                               pdf: one printable document containing the legend and all
+                                    pattern parts
+                               oxs: Open Cross Stitch file for other cross stitch applications
+                               svg: like png but as vector images that stay sharp at any zoom
+                                    level and print size
+  --page-size <SIZE>           Page size of pdf documents: a4 or letter [default: a4]
+  --output-root <LOCATION>     Where the output directories are created: `executable` for next to
+                               the executable, `image` for next to each image or the path of
+                               any other directory [default: executable]
+  --existing-output <MODE>     What to do if an output directory already exists [default: version]
+                               version:   keep it and write into its next free `version_N`
+                                          subdirectory
+                               replace:   delete it and create it anew
+                               timestamp: keep it and write into a subdirectory named by the
+                                          date and time (UTC) of the run
+                               refuse:    keep it and skip the image with an error
+  --backstitch-outline <LIST>  Comma separated list of hex colors (i.e. `#FF8800,#000000`) whose
+                               regions get outlined with backstitches
+  --backstitch-color <COLOR>   Thread color of the generated outlines [default: #000000]
+  --no-notifications           Don't show a desktop notification when all images are done.
+                               Only used on Linux (via `notify-send`) and macOS
+";
+
+enum CommandlineAction {
+    Run(Box<Options>),
+    ShowUsage,
+}
 
-fn blit_symbol(symbol_bitmap: &Bitmap, image: &mut Bitmap, pos: Vec2i, mask_color: PixelRGBA) {
-    let symbol_rect = symbol_bitmap.rect();
+// NOTE: THIS IS FOR INTERNAL TESTING
+#[cfg(debug_assertions)]
+fn get_default_image_filepaths() -> Vec<String> {
+    vec![
+        "examples/nathan.png".to_owned(),
+        "examples/nathan_big.gif".to_owned(),
+        "examples/pixie.png".to_owned(),
+        // "nachtlicht-pixel2.gif".to_owned(),
+        // "nachtlicht-pixel2wolke.png".to_owned(),
+    ]
+}
 
-    assert!(pos.x >= 0);
-    assert!(pos.y >= 0);
-    assert!(pos.x + symbol_rect.width() <= image.width);
-    assert!(pos.y + symbol_rect.height() <= image.height);
+#[cfg(not(debug_assertions))]
+fn get_default_image_filepaths() -> Vec<String> {
+    Vec::new()
+}
 
-    let dest_color = image.get(pos.x, pos.y);
-    let relative_luminance = Color::from_pixelrgba(dest_color).to_relative_luminance();
-    let blit_color = if relative_luminance > 0.2 {
-        PixelRGBA::black()
+/// Returns the value of an option that was either given inline as `--name=value` or as the next
+/// argument `--name value`
+fn commandline_option_value(
+    option_name: &str,
+    inline_value: Option<&str>,
+    remaining_args: &mut std::slice::Iter<String>,
+) -> std::result::Result<String, String> {
+    if let Some(value) = inline_value {
+        Ok(value.to_owned())
     } else {
-        PixelRGBA::white()
-    };
-
-    for y in 0..symbol_rect.height() {
-        for x in 0..symbol_rect.width() {
-            let symbol_pixel_color = symbol_bitmap.get(x, y);
-            // NOTE: We assume the symbols-images are black on white backround. We don't want to
-            //       draw the white background so we treat it as transparent
-            if symbol_pixel_color != mask_color {
-                image.set(pos.x + x, pos.y + y, blit_color);
-            }
-        }
+        remaining_args
+            .next()
+            .cloned()
+            .ok_or_else(|| format!("Missing value for option '{}'", option_name))
     }
 }
 
-fn bitmap_create_from_gif_file(image_filepath: &str) -> Bitmap {
-    let mut decoder = gif::Decoder::new(
-        File::open(image_filepath).expect(&format!("Cannot open file '{}'", image_filepath)),
-    );
-
-    decoder.set(gif::ColorOutput::RGBA);
-    let mut decoder = decoder
-        .read_info()
-        .expect(&format!("Cannot decode file '{}'", image_filepath));
-    let frame = decoder
-        .read_next_frame()
-        .expect(&format!(
-            "Cannot decode first frame in '{}'",
-            image_filepath
-        ))
-        .expect(&format!("No frame found in '{}'", image_filepath));
-    let buffer: Vec<PixelRGBA> = frame
-        .buffer
-        .chunks_exact(4)
-        .into_iter()
-        .map(|color| PixelRGBA::new(color[0], color[1], color[2], color[3]))
-        .collect();
-    Bitmap::new_from_buffer(frame.width as u32, frame.height as u32, buffer)
-}
-
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Pattern creation
-
-fn draw_origin_line_vertical(bitmap: &mut Bitmap, pos_x: i32) {
-    bitmap.draw_rect_filled_safely(pos_x - 2, 0, 4, bitmap.height, PixelRGBA::black());
-    bitmap.draw_rect_filled_safely(pos_x - 1, 0, 2, bitmap.height, PixelRGBA::white());
+fn commandline_option_value_i32(
+    option_name: &str,
+    value: &str,
+    min_value: i32,
+) -> std::result::Result<i32, String> {
+    let result = value.trim().parse::<i32>().map_err(|_| {
+        format!(
+            "Invalid value '{}' for option '{}': expected a whole number",
+            value, option_name
+        )
+    })?;
+    if result < min_value {
+        return Err(format!(
+            "Invalid value '{}' for option '{}': must be at least {}",
+            value, option_name, min_value
+        ));
+    }
+    Ok(result)
 }
 
-fn draw_origin_line_horizontal(bitmap: &mut Bitmap, pos_y: i32) {
-    bitmap.draw_rect_filled_safely(0, pos_y - 2, bitmap.width, 4, PixelRGBA::black());
-    bitmap.draw_rect_filled_safely(0, pos_y - 1, bitmap.width, 2, PixelRGBA::white());
+/// Parses colors like `#FF8800` or `ff8800`
+fn commandline_option_value_color(
+    option_name: &str,
+    value: &str,
+) -> std::result::Result<PixelRGBA, String> {
+    color_from_hex_string(value).ok_or_else(|| {
+        format!(
+            "Invalid color '{}' for option '{}': expected a hex color like #FF8800",
+            value, option_name
+        )
+    })
 }
 
-/// NOTE: This assumes that the scaled bitmap width and height are a roughly a multiple of
-///       grid_cell_size
-fn place_grid_labels_in_pattern(
-    scaled_bitmap: &Bitmap,
-    grid_cell_size: i32,
-    font: &BitmapFont,
-    logical_first_coordinate_x: i32,
-    logical_first_coordinate_y: i32,
-) -> Bitmap {
-    let grid_width = scaled_bitmap.width / grid_cell_size;
-    let grid_height = scaled_bitmap.height / grid_cell_size;
-
-    let logical_last_coordinate_x = logical_first_coordinate_x + grid_width;
-    let logical_last_coordinate_y = logical_first_coordinate_y + grid_height;
-
-    // Determine how much image-padding we need by calculating the maximum label text dimension
-    let label_padding = {
-        let max_logical_coordinates = [
-            logical_first_coordinate_x,
-            logical_first_coordinate_y,
-            logical_last_coordinate_x,
-            logical_last_coordinate_y,
-        ];
-        let max_text_charcount = max_logical_coordinates
-            .iter()
-            .map(|max_coordinate| max_coordinate.to_string().len())
-            .max()
-            .unwrap();
-
-        font.horizontal_advance_max * (max_text_charcount + 4) as i32
-    };
+/// Returns an error message if the arguments are invalid
+///
+/// NOTE: The first argument is expected to be the executable path
+fn parse_commandline(args: &[String]) -> std::result::Result<CommandlineAction, String> {
+    let mut options = Options::default();
 
-    let mut result_bitmap = scaled_bitmap.extended(
-        label_padding,
-        label_padding,
-        label_padding,
-        label_padding,
-        PixelRGBA::white(),
-    );
+    let mut remaining_args = args.iter();
+    remaining_args.next();
 
-    // Determine all x label positions
-    let label_coords_x = {
-        let mut result = Vec::new();
-        for bitmap_coord_x in 0..(grid_width + 1) {
-            let logical_coord_x = logical_first_coordinate_x + bitmap_coord_x;
-            if logical_coord_x % 10 == 0 {
-                result.push((bitmap_coord_x, logical_coord_x));
-            }
+    while let Some(arg) = remaining_args.next() {
+        if arg == "-h" || arg == "--help" {
+            return Ok(CommandlineAction::ShowUsage);
         }
-
-        // Add label for first and last horizontal grid pixel so that we don't mix up a remaining
-        // 7, 8 or 9 pixel block with a 10 block
-        let pixel_count_in_first_block_horizontal = i32::abs(
-            ceil_to_multiple_of_target_i32(logical_first_coordinate_x, 10)
-                - logical_first_coordinate_x,
-        );
-        if pixel_count_in_first_block_horizontal > 3 {
-            result.push((0, logical_first_coordinate_x));
+        if arg == "--" {
+            // Everything after a `--` is an image path, even if it starts with dashes
+            options
+                .image_filepaths
+                .extend(remaining_args.by_ref().cloned());
+            break;
         }
-        let pixel_count_in_last_block_horizontal = i32::abs(
-            floor_to_multiple_of_target_i32(logical_last_coordinate_x, 10)
-                - logical_last_coordinate_x,
-        );
-        if pixel_count_in_last_block_horizontal > 3 {
-            result.push((grid_width, logical_last_coordinate_x));
+        if !arg.starts_with("--") {
+            options.image_filepaths.push(arg.clone());
+            continue;
         }
 
-        result
-    };
-
-    // Draw x labels
-    for (bitmap_coord_x, logical_coord_x) in label_coords_x {
-        let text = logical_coord_x.to_string();
-        let draw_x = label_padding + grid_cell_size * bitmap_coord_x;
-        let draw_pos_top = Vec2i::new(draw_x, label_padding / 2);
-        let draw_pos_bottom = Vec2i::new(draw_x, result_bitmap.height - label_padding / 2);
-
-        result_bitmap.draw_text_aligned_in_point(
-            font,
-            &text,
-            1,
-            draw_pos_top,
-            Vec2i::zero(),
-            Some(TextAlignment {
-                horizontal: AlignmentHorizontal::Center,
-                vertical: AlignmentVertical::Center,
-                origin_is_baseline: false,
-                ignore_whitespace: false,
-            }),
-        );
-        result_bitmap.draw_text_aligned_in_point(
-            font,
-            &text,
-            1,
-            draw_pos_bottom,
-            Vec2i::zero(),
-            Some(TextAlignment {
-                horizontal: AlignmentHorizontal::Center,
-                vertical: AlignmentVertical::Center,
-                origin_is_baseline: false,
-                ignore_whitespace: false,
-            }),
-        );
-    }
-
-    // Determine all y label positions
-    let label_coords_y = {
-        let mut result = Vec::new();
-        for bitmap_coord_y in 0..(grid_height + 1) {
-            let logical_coord_y = logical_first_coordinate_y + bitmap_coord_y;
-            if logical_coord_y % 10 == 0 {
-                result.push((bitmap_coord_y, logical_coord_y));
+        let (option_name, inline_value) = match arg.find('=') {
+            Some(separator_pos) => (&arg[..separator_pos], Some(&arg[separator_pos + 1..])),
+            None => (arg.as_str(), None),
+        };
+        // Options without value
+        match option_name {
+            "--snap-to-floss" => {
+                options.snap_to_floss = true;
+                continue;
             }
+            "--no-notifications" => {
+                options.desktop_notifications = false;
+                continue;
+            }
+            _ => {}
         }
 
-        // Add label for first and last vertical grid pixel so that we don't mix up a remaining
-        // 7, 8 or 9 pixel block with a 10 block
-        let pixel_count_in_first_block_vertical = i32::abs(
-            ceil_to_multiple_of_target_i32(logical_first_coordinate_y, 10)
-                - logical_first_coordinate_y,
-        );
-        if pixel_count_in_first_block_vertical > 3 {
-            result.push((0, logical_first_coordinate_y));
-        }
-        let pixel_count_in_last_block_vertical = i32::abs(
-            floor_to_multiple_of_target_i32(logical_last_coordinate_y, 10)
-                - logical_last_coordinate_y,
-        );
-        if pixel_count_in_last_block_vertical > 3 {
-            result.push((grid_height, logical_last_coordinate_y));
-        }
-
-        result
-    };
-
-    // Draw y labels
-    for (bitmap_coord_y, logical_coord_y) in label_coords_y {
-        // NOTE: In pixel space our y-coordinates are y-down. We want cartesian y-up so we negate y
-        let text = (-logical_coord_y).to_string();
-        let draw_y = label_padding + grid_cell_size * bitmap_coord_y;
-        let draw_pos_left = Vec2i::new(label_padding / 2, draw_y);
-        let draw_pos_right = Vec2i::new(result_bitmap.width - label_padding / 2, draw_y);
-
-        result_bitmap.draw_text_aligned_in_point(
-            font,
-            &text,
-            1,
-            draw_pos_left,
-            Vec2i::zero(),
-            Some(TextAlignment {
-                horizontal: AlignmentHorizontal::Center,
-                vertical: AlignmentVertical::Center,
-                origin_is_baseline: false,
-                ignore_whitespace: false,
-            }),
-        );
-        result_bitmap.draw_text_aligned_in_point(
-            font,
-            &text,
-            1,
-            draw_pos_right,
-            Vec2i::zero(),
-            Some(TextAlignment {
-                horizontal: AlignmentHorizontal::Center,
-                vertical: AlignmentVertical::Center,
-                origin_is_baseline: false,
-                ignore_whitespace: false,
-            }),
-        );
-    }
-
-    result_bitmap
-}
-
-fn create_cross_stitch_pattern(
-    bitmap: &Bitmap,
-    font_grid_label: &BitmapFont,
-    font_segment_index_indicator: &BitmapFont,
-    image_filepath: &str,
-    output_filename_suffix: &str,
-    output_dir_suffix: &str,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-    segment_index: Option<usize>,
-    logical_first_coordinate_x: i32,
-    logical_first_coordinate_y: i32,
-    pattern_type: PatternType,
-    add_thick_ten_grid: bool,
-    add_origin_grid_bars: bool,
-    symbol_mask_color: PixelRGBA,
-) {
-    let (colorize, add_symbol, use_alphanum) = match pattern_type {
-        PatternType::BlackAndWhite => (false, true, false),
-        PatternType::Colorized => (true, true, false),
-        PatternType::ColorizedNoSymbols => (true, false, false),
-        PatternType::PaintByNumbers => (false, true, true),
-    };
-
-    let mut scaled_bitmap = Bitmap::new(
-        (TILE_SIZE * bitmap.width) as u32,
-        (TILE_SIZE * bitmap.height) as u32,
-    );
-    let scaled_bitmap_width = scaled_bitmap.width;
-    let scaled_bitmap_height = scaled_bitmap.height;
-
-    for y in 0..bitmap.height {
-        for x in 0..bitmap.width {
-            let color = bitmap.get(x, y);
-
-            // Colorize pixels
-            if colorize {
-                scaled_bitmap.draw_rect_filled(
-                    TILE_SIZE * x,
-                    TILE_SIZE * y,
-                    TILE_SIZE,
-                    TILE_SIZE,
-                    if color.a == 0 {
-                        PixelRGBA::white()
-                    } else {
-                        color
-                    },
-                );
-            } else {
-                scaled_bitmap.draw_rect_filled(
-                    TILE_SIZE * x,
-                    TILE_SIZE * y,
-                    TILE_SIZE,
-                    TILE_SIZE,
-                    PixelRGBA::white(),
-                );
+        let value = commandline_option_value(option_name, inline_value, &mut remaining_args)?;
+        match option_name {
+            "--tile-size" => {
+                options.tile_size =
+                    commandline_option_value_i32(option_name, &value, TILE_SIZE_MIN)?
             }
-
-            // Add symbol
-            if add_symbol && color.a != 0 {
-                let symbol = if use_alphanum {
-                    &color_mappings.get(&color).unwrap().symbol_alphanum
+            "--segment-width" => {
+                options.pattern.segment_width =
+                    commandline_option_value_i32(option_name, &value, 1)?
+            }
+            "--segment-height" => {
+                options.pattern.segment_height =
+                    commandline_option_value_i32(option_name, &value, 1)?
+            }
+            "--segment-overlap" => {
+                options.pattern.segment_overlap =
+                    commandline_option_value_i32(option_name, &value, 0)?
+            }
+            "--max-colors" => {
+                options.max_colors =
+                    Some(commandline_option_value_i32(option_name, &value, 1)? as usize)
+            }
+            "--fabric-count" => {
+                options.pattern.thread_usage.fabric_count =
+                    commandline_option_value_i32(option_name, &value, 1)?
+            }
+            "--strands" => {
+                options.pattern.thread_usage.strand_count =
+                    commandline_option_value_i32(option_name, &value, 1)?;
+                if options.pattern.thread_usage.strand_count > 6 {
+                    return Err(format!(
+                        "Invalid value '{}' for option '{}': must be at most 6",
+                        value, option_name
+                    ));
+                }
+            }
+            "--waste-percent" => {
+                options.pattern.thread_usage.waste_percent =
+                    commandline_option_value_i32(option_name, &value, 0)?
+            }
+            "--fabric-margin" => {
+                options.pattern.fabric_margin_centimeters =
+                    commandline_option_value_i32(option_name, &value, 0)?
+            }
+            "--fabric-color" => {
+                options.pattern.fabric_color = commandline_option_value_color(option_name, &value)?
+            }
+            "--frame" => {
+                options.frame_selection = if value.trim() == "all" {
+                    FrameSelection::All
                 } else {
-                    &color_mappings.get(&color).unwrap().symbol
+                    let frame_number = commandline_option_value_i32(option_name, &value, 1)?;
+                    FrameSelection::Single(frame_number as usize - 1)
+                }
+            }
+            "--pixel-scale" => {
+                options.pixel_scale = if value.trim() == "auto" {
+                    PixelScaleSelection::Auto
+                } else {
+                    PixelScaleSelection::Factor(commandline_option_value_i32(
+                        option_name,
+                        &value,
+                        1,
+                    )?)
+                }
+            }
+            "--key-color" => {
+                options.key_colors = value
+                    .split(',')
+                    .map(|color_text| {
+                        if color_text.trim() == "corner" {
+                            Ok(KeyColor::CornerPixel)
+                        } else {
+                            commandline_option_value_color(option_name, color_text)
+                                .map(KeyColor::Color)
+                        }
+                    })
+                    .collect::<std::result::Result<Vec<KeyColor>, String>>()?
+            }
+            "--alpha-threshold" => {
+                let alpha_threshold = commandline_option_value_i32(option_name, &value, 0)?;
+                if alpha_threshold > 255 {
+                    return Err(format!(
+                        "Invalid value '{}' for option '{}': must be at most 255",
+                        value, option_name
+                    ));
+                }
+                options.alpha_threshold = alpha_threshold as u8;
+            }
+            "--floss" => options.floss_catalog = Some(value.trim().to_lowercase()),
+            "--beads" => options.bead_catalog = Some(value.trim().to_lowercase()),
+            "--pegboard" => {
+                let value_lowercase = value.trim().to_lowercase();
+                let (width_text, height_text) = match value_lowercase.find('x') {
+                    Some(separator_pos) => (
+                        &value_lowercase[..separator_pos],
+                        &value_lowercase[separator_pos + 1..],
+                    ),
+                    None => (value_lowercase.as_str(), value_lowercase.as_str()),
                 };
-
-                blit_symbol(
-                    symbol,
-                    &mut scaled_bitmap,
-                    Vec2i::new(TILE_SIZE * x, TILE_SIZE * y),
-                    symbol_mask_color,
-                );
+                options.bead_settings.pegboard_width =
+                    commandline_option_value_i32(option_name, width_text, 1)?;
+                options.bead_settings.pegboard_height =
+                    commandline_option_value_i32(option_name, height_text, 1)?;
             }
-        }
-    }
-
-    // Add 1x1 grid
-    for x in 0..bitmap.width {
-        scaled_bitmap.draw_rect_filled(TILE_SIZE * x, 0, 1, scaled_bitmap_height, COLOR_GRID_THIN);
-    }
-    for y in 0..bitmap.height {
-        scaled_bitmap.draw_rect_filled(0, TILE_SIZE * y, scaled_bitmap_width, 1, COLOR_GRID_THIN);
-    }
-    // Close 1x1 grid line on bottom-right bitmap border
-    scaled_bitmap.draw_rect_filled(
-        scaled_bitmap_width - 1,
-        0,
-        1,
-        scaled_bitmap_height,
-        COLOR_GRID_THIN,
-    );
-    scaled_bitmap.draw_rect_filled(
-        0,
-        scaled_bitmap_height - 1,
-        scaled_bitmap_width,
-        1,
-        COLOR_GRID_THIN,
-    );
-
-    // Add 10x10 grid
-    if add_thick_ten_grid {
-        for bitmap_x in 0..bitmap.width {
-            let logical_x = logical_first_coordinate_x + bitmap_x;
-            if logical_x % 10 == 0 {
-                scaled_bitmap.draw_rect_filled(
-                    TILE_SIZE * bitmap_x,
-                    0,
-                    2,
-                    scaled_bitmap_height,
-                    COLOR_GRID_THICK,
-                );
+            "--bead-bag-size" => {
+                options.bead_settings.bag_size =
+                    commandline_option_value_i32(option_name, &value, 1)? as usize
             }
-        }
-        for bitmap_y in 0..bitmap.height {
-            let logical_y = logical_first_coordinate_y + bitmap_y;
-            if logical_y % 10 == 0 {
-                scaled_bitmap.draw_rect_filled(
-                    0,
-                    TILE_SIZE * bitmap_y,
-                    scaled_bitmap_width,
-                    2,
-                    COLOR_GRID_THICK,
-                );
+            "--outputs" => {
+                options.pattern.create_patterns = false;
+                options.pattern.create_patterns_centered = false;
+                options.pattern.create_preview = false;
+                for output_name in value.split(',').map(|name| name.trim()) {
+                    match output_name {
+                        "plain" => options.pattern.create_patterns = true,
+                        "centered" => options.pattern.create_patterns_centered = true,
+                        "preview" => options.pattern.create_preview = true,
+                        _ => {
+                            return Err(format!(
+                                "Unknown output '{}' for option '{}'",
+                                output_name, option_name
+                            ))
+                        }
+                    }
+                }
             }
-        }
-        // Close 10x10 grid line on bottom-right bitmap border if necessary
-        if (logical_first_coordinate_x + bitmap.width) % 10 == 0 {
-            scaled_bitmap.draw_rect_filled(
-                scaled_bitmap_width - 2,
-                0,
-                2,
-                scaled_bitmap_height,
-                COLOR_GRID_THICK,
-            );
-        }
-        if (logical_first_coordinate_y + bitmap.height) % 10 == 0 {
-            scaled_bitmap.draw_rect_filled(
-                0,
-                scaled_bitmap_height - 2,
-                scaled_bitmap_width,
-                2,
-                COLOR_GRID_THICK,
-            );
+            "--formats" => {
+                options.format_png = false;
+                options.format_pdf = false;
+                options.format_oxs = false;
+                options.format_svg = false;
+                for format_name in value.split(',').map(|name| name.trim()) {
+                    match format_name {
+                        "png" => options.format_png = true,
+                        "pdf" => options.format_pdf = true,
+                        "oxs" => options.format_oxs = true,
+                        "svg" => options.format_svg = true,
+                        _ => {
+                            return Err(format!(
+                                "Unknown format '{}' for option '{}'",
+                                format_name, option_name
+                            ))
+                        }
+                    }
+                }
+            }
+            "--output-root" => {
+                options.output_root = match value.trim() {
+                    "executable" => OutputRoot::ExecutableDir,
+                    "image" => OutputRoot::ImageDir,
+                    "" => return Err(format!("Missing value for option '{}'", option_name)),
+                    dir => OutputRoot::Dir(dir.to_owned()),
+                }
+            }
+            "--existing-output" => {
+                options.existing_output = match value.trim().to_lowercase().as_str() {
+                    "replace" => ExistingOutput::Replace,
+                    "version" => ExistingOutput::Version,
+                    // NOTE: All images of a run share the same timestamp
+                    "timestamp" => ExistingOutput::Timestamp(get_timestamp_now()),
+                    "refuse" => ExistingOutput::Refuse,
+                    _ => {
+                        return Err(format!(
+                            "Unknown mode '{}' for option '{}'",
+                            value, option_name
+                        ))
+                    }
+                }
+            }
+            "--backstitch-outline" => {
+                options.backstitch_outline_colors = value
+                    .split(',')
+                    .map(|color_text| commandline_option_value_color(option_name, color_text))
+                    .collect::<std::result::Result<Vec<PixelRGBA>, String>>()?
+            }
+            "--backstitch-color" => {
+                options.backstitch_outline_color =
+                    commandline_option_value_color(option_name, &value)?
+            }
+            "--page-size" => {
+                options.page_size = match value.trim().to_lowercase().as_str() {
+                    "a4" => PageSize::A4,
+                    "letter" => PageSize::Letter,
+                    _ => {
+                        return Err(format!(
+                            "Unknown page size '{}' for option '{}'",
+                            value, option_name
+                        ))
+                    }
+                }
+            }
+            _ => return Err(format!("Unknown option '{}'", option_name)),
         }
     }
 
-    // Add origin grid
-    if add_origin_grid_bars {
-        let origin_bitmap_coord_x = -logical_first_coordinate_x;
-        if 0 < origin_bitmap_coord_x && origin_bitmap_coord_x < bitmap.width {
-            draw_origin_line_vertical(&mut scaled_bitmap, TILE_SIZE * origin_bitmap_coord_x);
-        }
-
-        let origin_bitmap_coord_y = -logical_first_coordinate_y;
-        if 0 < origin_bitmap_coord_y && origin_bitmap_coord_y < bitmap.height {
-            draw_origin_line_horizontal(&mut scaled_bitmap, TILE_SIZE * origin_bitmap_coord_y);
-        }
-
-        // NOTE: If our origin grid is located on the edge of our image we want to extend our image
-        //       so that the origin grid is drawn more clearly visible
-        let needs_grid_left = logical_first_coordinate_x == 0;
-        let needs_grid_top = logical_first_coordinate_y == 0;
-        let needs_grid_right = logical_first_coordinate_x + bitmap.width == 0;
-        let needs_grid_bottom = logical_first_coordinate_y + bitmap.height == 0;
-
-        let padding_left = if needs_grid_left { 2 } else { 0 };
-        let padding_top = if needs_grid_top { 2 } else { 0 };
-        let padding_right = if needs_grid_right { 2 } else { 0 };
-        let padding_bottom = if needs_grid_bottom { 2 } else { 0 };
-
-        scaled_bitmap.extend(
-            padding_left,
-            padding_top,
-            padding_right,
-            padding_bottom,
-            PixelRGBA::white(),
+    if options.snap_to_floss && options.floss_catalog.is_none() {
+        return Err(
+            "The option '--snap-to-floss' requires a floss catalog given via '--floss'".to_owned(),
         );
-
-        if needs_grid_left {
-            draw_origin_line_vertical(&mut scaled_bitmap, 2);
-        }
-        if needs_grid_right {
-            draw_origin_line_vertical(&mut scaled_bitmap, scaled_bitmap_width);
-        }
-        if needs_grid_top {
-            draw_origin_line_horizontal(&mut scaled_bitmap, 2);
-        }
-        if needs_grid_bottom {
-            draw_origin_line_horizontal(&mut scaled_bitmap, scaled_bitmap_height);
+    }
+    if options.bead_catalog.is_some() {
+        if options.floss_catalog.is_some() {
+            return Err("The options '--beads' and '--floss' cannot be combined".to_owned());
         }
+        options.pattern.beads = Some(options.bead_settings);
+    } else if options.bead_settings != BeadSettings::default() {
+        return Err(
+            "The options '--pegboard' and '--bead-bag-size' require a bead brand given via \
+             '--beads'"
+                .to_owned(),
+        );
     }
-
-    // Add 10-grid labels
-    let final_bitmap = if add_thick_ten_grid {
-        // NOTE: At this point the scaled bitmap might not be an exact multiple of the original
-        //       bitmap because we may have padded it while drawing the origin grid bars. Therefore
-        //       the placement of the labels might be incorrectly shifted by two pixels. This is
-        //       okay because it is not really visible and the code complexity to fix this is not
-        //       worth it.
-        place_grid_labels_in_pattern(
-            &scaled_bitmap,
-            TILE_SIZE,
-            font_grid_label,
-            logical_first_coordinate_x,
-            logical_first_coordinate_y,
-        )
-    } else {
-        scaled_bitmap
-    };
-
-    // Add segment index indicator if necessary
-    let final_bitmap = if let Some(segment_index) = segment_index {
-        let text_bitmap = Bitmap::create_from_text(
-            font_segment_index_indicator,
-            &format!("\n Pattern Part {} \n", segment_index),
-            1,
-            PixelRGBA::white(),
+    if options.pattern.segment_overlap >= options.pattern.segment_width
+        || options.pattern.segment_overlap >= options.pattern.segment_height
+    {
+        return Err(
+            "The option '--segment-overlap' must be smaller than the segment width and height"
+                .to_owned(),
         );
-        text_bitmap.glued_to(
-            &final_bitmap,
-            GluePosition::TopCenter,
-            0,
-            PixelRGBA::white(),
-        )
-    } else {
-        final_bitmap
-    };
-
-    // Write out png image
-    let output_filepath = get_image_output_filepath(&image_filepath, output_dir_suffix)
-        + "_"
-        + output_filename_suffix
-        + ".png";
-    Bitmap::write_to_png_file(&final_bitmap, &output_filepath);
-}
-
-fn create_cross_stitch_pattern_set(
-    image: &Bitmap,
-    font_grid_label: &BitmapFont,
-    font_segment_index_indicator: &BitmapFont,
-    image_filepath: &str,
-    output_filename_suffix: &str,
-    output_dir_suffix: &str,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-    segment_index: Option<usize>,
-    logical_first_coordinate_x: i32,
-    logical_first_coordinate_y: i32,
-    create_paint_by_number_set: bool,
-    add_origin_grid_bars: bool,
-) {
-    rayon::scope(|scope| {
-        scope.spawn(|_| {
-            create_cross_stitch_pattern(
-                &image,
-                font_grid_label,
-                font_segment_index_indicator,
-                &image_filepath,
-                &("cross_stitch_colorized_".to_owned() + output_filename_suffix),
-                output_dir_suffix,
-                &color_mappings,
-                segment_index,
-                logical_first_coordinate_x,
-                logical_first_coordinate_y,
-                PatternType::Colorized,
-                true,
-                add_origin_grid_bars,
-                PixelRGBA::white(),
-            );
-        });
-        scope.spawn(|_| {
-            create_cross_stitch_pattern(
-                &image,
-                font_grid_label,
-                font_segment_index_indicator,
-                &image_filepath,
-                &("cross_stitch_".to_owned() + output_filename_suffix),
-                output_dir_suffix,
-                &color_mappings,
-                segment_index,
-                logical_first_coordinate_x,
-                logical_first_coordinate_y,
-                PatternType::BlackAndWhite,
-                true,
-                add_origin_grid_bars,
-                PixelRGBA::white(),
-            );
-        });
-        scope.spawn(|_| {
-            create_cross_stitch_pattern(
-                &image,
-                font_grid_label,
-                font_segment_index_indicator,
-                &image_filepath,
-                &("cross_stitch_colorized_no_symbols_".to_owned() + output_filename_suffix),
-                output_dir_suffix,
-                &color_mappings,
-                segment_index,
-                logical_first_coordinate_x,
-                logical_first_coordinate_y,
-                PatternType::ColorizedNoSymbols,
-                true,
-                add_origin_grid_bars,
-                PixelRGBA::white(),
-            );
-        });
-        if create_paint_by_number_set {
-            scope.spawn(|_| {
-                create_cross_stitch_pattern(
-                    &image,
-                    font_grid_label,
-                    font_segment_index_indicator,
-                    &image_filepath,
-                    &("paint_by_numbers_".to_owned() + output_filename_suffix),
-                    output_dir_suffix,
-                    &color_mappings,
-                    segment_index,
-                    logical_first_coordinate_x,
-                    logical_first_coordinate_y,
-                    PatternType::PaintByNumbers,
-                    false,
-                    false,
-                    PixelRGBA::transparent(),
-                );
-            });
-        }
-    });
-}
-
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Image analysis
-
-fn create_color_mappings_from_image(
-    image: &Bitmap,
-    image_filepath: &str,
-    symbols: &[Bitmap],
-    symbols_alphanum: &[Bitmap],
-    stitch_images_premultiplied_alpha: &[Bitmap],
-    stitch_images_luminance_premultiplied_alpha: &[Bitmap],
-) -> IndexMap<PixelRGBA, ColorInfo> {
-    let mut color_mappings = image_extract_colors_and_counts(&image);
-
-    // Stitch symbols
-    assert!(
-        symbols.len() >= color_mappings.len(),
-        "Not enough symbols to map {} colors found in given image '{}' for cross stitch",
-        color_mappings.len(),
-        &image_filepath,
-    );
-    for (entry, symbol) in color_mappings.values_mut().zip(symbols.iter()) {
-        entry.symbol = symbol.clone();
     }
 
-    // Alphanum symbols
-    assert!(
-        symbols_alphanum.len() >= color_mappings.len(),
-        "Not enough symbols to map {} colors found in given image '{}' for paint by numbers",
-        color_mappings.len(),
-        &image_filepath,
-    );
-    for (entry, symbol_alphanum) in color_mappings.values_mut().zip(symbols_alphanum.iter()) {
-        entry.symbol_alphanum = symbol_alphanum.clone();
+    if options.image_filepaths.is_empty() {
+        options.image_filepaths = get_default_image_filepaths();
+    }
+    if options.image_filepaths.is_empty() {
+        return Err("Please drag and drop one (or more) image(s) onto the executable".to_owned());
     }
 
-    // Colorized stitch tiles
-    for entry in color_mappings.values_mut() {
-        let color = entry.color;
-        if color.a != 0 {
-            for (stitch_image_premultipllied, stitch_image_luminance_premultiplied) in
-                stitch_images_premultiplied_alpha
-                    .iter()
-                    .zip(stitch_images_luminance_premultiplied_alpha.iter())
-            {
-                let mut stitch = stitch_image_premultipllied.clone();
+    Ok(CommandlineAction::Run(Box::new(options)))
+}
 
-                let screen_layer = Bitmap::new_filled(
-                    stitch_image_premultipllied.width as u32,
-                    stitch_image_premultipllied.height as u32,
-                    PixelRGBA::new(105, 109, 128, 255),
-                )
-                .to_premultiplied_alpha();
-                screen_layer.blit_to_alpha_blended_premultiplied(
-                    &mut stitch,
-                    Vec2i::zero(),
-                    false,
-                    ColorBlendMode::Screen,
-                );
+fn show_usage() {
+    println!("{}", USAGE);
 
-                let color_layer = Bitmap::new_filled(
-                    stitch_image_premultipllied.width as u32,
-                    stitch_image_premultipllied.height as u32,
-                    color,
-                )
-                .to_premultiplied_alpha();
-                color_layer.blit_to_alpha_blended_premultiplied(
-                    &mut stitch,
-                    Vec2i::zero(),
-                    false,
-                    ColorBlendMode::Multiply,
-                );
+    // NOTE: We are a windows subsystem application and have no console to print to
+    #[cfg(windows)]
+    show_messagebox("Pixie Stitch", USAGE, false);
+}
 
-                let mut luminosity_layer = stitch_image_luminance_premultiplied.clone();
-                let percent = (color.r as f32 + color.g as f32 + color.b as f32) / (3.0 * 255.0);
-                for pixel in luminosity_layer.data.iter_mut() {
-                    pixel.r /= 6 + (8.0 * percent * percent) as u8;
-                    pixel.g /= 6 + (8.0 * percent * percent) as u8;
-                    pixel.b /= 6 + (8.0 * percent * percent) as u8;
-                    pixel.a /= 6 + (8.0 * percent * percent) as u8;
-                }
-                luminosity_layer.blit_to_alpha_blended_premultiplied(
-                    &mut stitch,
-                    Vec2i::zero(),
-                    false,
-                    ColorBlendMode::Luminosity,
-                );
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Loading resources
 
-                entry
-                    .stitches_premultiplied
-                    .push(stitch.masked_by_premultiplied_alpha(&stitch_image_premultipllied));
+fn get_resource_dir_path() -> Result<String> {
+    let executable_dir_path = get_executable_dir();
+    let resource_dir_path = {
+        let candidate = path_join(&executable_dir_path, "resources");
+
+        if path_exists(&candidate) {
+            candidate
+        } else {
+            // There was no symbols dir in the executable dir. Lets try our current workingdir
+            // NOTE: When running from a fresh checkout (i.e. via `cargo run`) there is no baked
+            //       `resources` dir yet, so we use the assets it would be copied from
+            if path_exists("resources") {
+                "resources".to_owned()
+            } else if path_exists("assets_copy") {
+                "assets_copy".to_owned()
+            } else {
+                "resources".to_owned()
             }
         }
+    };
+
+    if !path_exists(&resource_dir_path) {
+        return Err(Error::resource(
+            &path_join(&executable_dir_path, "resources"),
+            "The `resources` directory is missing",
+        ));
     }
 
-    color_mappings
+    Ok(resource_dir_path)
 }
 
-fn image_extract_colors_and_counts(image: &Bitmap) -> IndexMap<PixelRGBA, ColorInfo> {
-    let mut color_mappings = IndexMap::new();
-    for pixel in &image.data {
-        if pixel.a == 0 {
-            // Ignore transparent regions
-            continue;
-        }
-
-        let entry = color_mappings.entry(*pixel).or_insert_with(|| ColorInfo {
-            color: *pixel,
-            count: 0,
-            symbol: Bitmap::new_empty(),
-            symbol_alphanum: Bitmap::new_empty(),
-            stitches_premultiplied: Vec::new(),
-        });
-        entry.count += 1;
+fn load_resource_png(filepath: &str) -> Result<Bitmap> {
+    if !path_exists(filepath) {
+        return Err(Error::resource(filepath, "The file is missing"));
     }
-
-    // This makes color ramps on the legend more pretty
-    color_mappings.sort_by(|color_a, _info_a, color_b, _info_b| {
-        PixelRGBA::compare_by_hue_luminosity_saturation(color_a, color_b)
-    });
-
-    color_mappings
+    Bitmap::from_png_file(filepath).map_err(|error| Error::resource(filepath, &error))
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
-// Pattern dir creation
-
-fn create_patterns_dir(
-    image: &Bitmap,
-    image_filepath: &str,
-    resources: &Resources,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-) {
-    let output_dir_suffix = "";
-
-    let (segment_images, segment_coordinates) =
-        image.to_segments(SPLIT_SEGMENT_WIDTH, SPLIT_SEGMENT_HEIGHT);
-
-    rayon::scope(|scope| {
-        // Legend
-        scope.spawn(|_| {
-            create_cross_stitch_legend(
-                image.dim(),
-                &color_mappings,
-                &image_filepath,
-                output_dir_suffix,
-                &resources.font,
-                &segment_coordinates,
-            );
-        });
+fn load_stitch_preview_images() -> Result<(Vec<Bitmap>, Vec<Bitmap>, Bitmap)> {
+    let resource_dir_path = get_resource_dir_path()?;
+    let background_tile_image_8x8 =
+        load_resource_png(&path_join(&resource_dir_path, "aida_8x8.png"))?;
+    let stitch_tile_images = ["stitch1.png", "stitch2.png", "stitch3.png"]
+        .iter()
+        .map(|filename| load_resource_png(&path_join(&resource_dir_path, filename)))
+        .collect::<Result<Vec<Bitmap>>>()?;
+    let stitch_tile_images_luminance = ["stitch1_lum.png", "stitch2_lum.png", "stitch3_lum.png"]
+        .iter()
+        .map(|filename| load_resource_png(&path_join(&resource_dir_path, filename)))
+        .collect::<Result<Vec<Bitmap>>>()?;
+    Ok((
+        stitch_tile_images,
+        stitch_tile_images_luminance,
+        background_tile_image_8x8,
+    ))
+}
 
-        // Create patterns for complete set
-        scope.spawn(|_| {
-            create_cross_stitch_pattern_set(
-                &image,
-                &resources.font,
-                &resources.font_big,
-                &image_filepath,
-                "complete",
-                output_dir_suffix,
-                &color_mappings,
-                None,
-                0,
-                0,
-                true,
-                false,
-            );
-        });
+/// Loads `floss_<NAME>.csv` or `beads_<NAME>.csv` from the resources dir
+fn load_floss_catalog(catalog_name: &str, is_bead_catalog: bool) -> Result<FlossCatalog> {
+    let kind = if is_bead_catalog { "beads" } else { "floss" };
+    let catalog_filepath = path_join(
+        &get_resource_dir_path()?,
+        &format!("{}_{}.csv", kind, catalog_name),
+    );
+    if !path_exists(&catalog_filepath) {
+        return Err(Error::resource(
+            &catalog_filepath,
+            &format!(
+                "Unknown {} catalog '{}'",
+                if is_bead_catalog { "bead" } else { "floss" },
+                catalog_name
+            ),
+        ));
+    }
+    FlossCatalog::from_csv_file(&catalog_filepath)
+}
 
-        // Create patterns for individual segments if needed
-        if segment_images.len() > 1 {
-            segment_images
-                .par_iter()
-                .zip(segment_coordinates.par_iter())
-                .enumerate()
-                .for_each(|(segment_index, (segment_image, segment_coordinate))| {
-                    let label_start_x = SPLIT_SEGMENT_WIDTH * segment_coordinate.x;
-                    let label_start_y = SPLIT_SEGMENT_HEIGHT * segment_coordinate.y;
-
-                    create_cross_stitch_pattern_set(
-                        segment_image,
-                        &resources.font,
-                        &resources.font_big,
-                        &image_filepath,
-                        &format!("segment_{}", segment_index + 1),
-                        output_dir_suffix,
-                        &color_mappings,
-                        Some(segment_index + 1),
-                        label_start_x,
-                        label_start_y,
-                        false,
-                        false,
-                    );
-                });
-        }
-    });
+fn collect_symbols() -> Result<Vec<Bitmap>> {
+    let resource_dir_path = get_resource_dir_path()?;
+    let mut symbols_filepaths = collect_files_by_extension_recursive(&resource_dir_path, ".png");
+    // NOTE: We need a stable order so that the symbol indices of exported patterns stay valid
+    symbols_filepaths.sort();
+    symbols_filepaths
+        .into_iter()
+        .filter(|filepath| {
+            path_to_filename_without_extension(filepath)
+                .parse::<u32>()
+                .is_ok()
+        })
+        .map(|symbol_filepath| load_resource_png(&symbol_filepath))
+        .collect()
 }
 
-fn create_patterns_dir_centered(
-    image: &Bitmap,
-    image_filepath: &str,
-    resources: &Resources,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-) {
-    let output_dir_suffix = "centered";
-    let image_center_x = make_even_upwards(image.width) / 2;
-    let image_center_y = make_even_upwards(image.height) / 2;
+fn load_resources(tile_size: i32) -> Result<Resources> {
+    let (stitch_images, stitch_images_luminance, stitch_background_image_8x8) =
+        load_stitch_preview_images()?;
+    Resources::new(
+        tile_size,
+        collect_symbols()?,
+        stitch_images,
+        stitch_images_luminance,
+        stitch_background_image_8x8,
+    )
+}
 
-    let (segment_images, segment_coordinates) =
-        image.to_segments(SPLIT_SEGMENT_WIDTH, SPLIT_SEGMENT_HEIGHT);
+type ColorMappings = IndexMap<PixelRGBA, ColorInfo>;
+/// The names and contents of the auxiliary layers of one frame
+type AuxiliaryLayers = Vec<(String, Bitmap)>;
 
-    rayon::scope(|scope| {
-        // Legend
-        scope.spawn(|_| {
-            create_cross_stitch_legend(
-                image.dim(),
-                &color_mappings,
-                &image_filepath,
-                output_dir_suffix,
-                &resources.font,
-                &segment_coordinates,
-            );
-        });
+/// Returns all frames of the image. Still images consist of a single frame. Imported patterns
+/// bring their own fractional stitches and backstitches and additionally return their color mappings.
+///
+/// Fractional stitch masks, backstitch line layers and progress masks come from the `fractional`,
+/// `backstitch` and `progress` layers of Aseprite files or from images next to the image file (i.e.
+/// `cat_fractional.png`, `cat_backstitch.png` and `cat_progress.png` for `cat.png`).
+///
+/// NOTE: We determine the file format by its content because the file extension can be wrong
+fn open_image(image_filepath: &str) -> Result<(Vec<ImageFrame>, Option<ColorMappings>)> {
+    let data = std::fs::read(image_filepath).map_err(|error| Error::io(image_filepath, error))?;
+    let decode_error = |message: String| Error::Decode {
+        filepath: image_filepath.to_owned(),
+        message,
+    };
 
-        // Create patterns for complete set
-        scope.spawn(|_| {
-            create_cross_stitch_pattern_set(
-                &image,
-                &resources.font,
-                &resources.font_big,
-                &image_filepath,
-                "complete",
-                output_dir_suffix,
-                &color_mappings,
+    // NOTE: Only Aseprite files can have auxiliary layers per frame
+    let (frames, auxiliary_layers_per_frame, imported_pattern): (
+        Vec<Bitmap>,
+        Vec<AuxiliaryLayers>,
+        _,
+    ) =
+        match image_formats::detect_image_format(&data) {
+            Some(ImageFormat::Png) => {
+                image_formats::png_check_dimensions(&data).map_err(decode_error)?;
+                (
+                    vec![Bitmap::from_png_file(image_filepath).map_err(decode_error)?],
+                    Vec::new(),
+                    None,
+                )
+            }
+            Some(ImageFormat::Gif) => (
+                bitmaps_create_from_gif_data(&data).map_err(decode_error)?,
+                Vec::new(),
                 None,
-                -image_center_x,
-                -image_center_y,
-                true,
-                true,
-            );
-        });
+            ),
+            Some(ImageFormat::Bmp) => (
+                vec![image_formats::bitmap_create_from_bmp_data(&data).map_err(decode_error)?],
+                Vec::new(),
+                None,
+            ),
+            Some(ImageFormat::Tga) => (
+                vec![image_formats::bitmap_create_from_tga_data(&data).map_err(decode_error)?],
+                Vec::new(),
+                None,
+            ),
+            Some(ImageFormat::Aseprite) => {
+                let aseprite_frames = aseprite::frames_create_from_aseprite_data(
+                    &data,
+                    &[
+                        stitch_kinds::FRACTIONAL_MASK_NAME,
+                        backstitch::BACKSTITCH_LAYER_NAME,
+                        progress::PROGRESS_MASK_NAME,
+                    ],
+                )
+                .map_err(decode_error)?;
+                let mut frames = Vec::new();
+                let mut auxiliary_layers_per_frame = Vec::new();
+                for aseprite_frame in aseprite_frames {
+                    frames.push(aseprite_frame.image);
+                    auxiliary_layers_per_frame.push(aseprite_frame.auxiliary_layers);
+                }
+                (frames, auxiliary_layers_per_frame, None)
+            }
+            Some(ImageFormat::Oxs) => {
+                let (image, pattern) = oxs::load_oxs_data(&data).map_err(decode_error)?;
+                (vec![image], Vec::new(), Some(pattern))
+            }
+            None => return Err(decode_error(
+                "Unsupported file format. We support PNG, GIF, BMP, TGA and Aseprite images as \
+                 well as OXS patterns"
+                    .to_owned(),
+            )),
+        };
 
-        // Create patterns for individual segments if needed
-        if segment_images.len() > 1 {
-            segment_images
-                .par_iter()
-                .zip(segment_coordinates.par_iter())
-                .enumerate()
-                .for_each(|(segment_index, (segment_image, segment_coordinate))| {
-                    let logical_first_coordinate_x =
-                        SPLIT_SEGMENT_WIDTH * segment_coordinate.x - image_center_x;
-                    let logical_first_coordinate_y =
-                        SPLIT_SEGMENT_HEIGHT * segment_coordinate.y - image_center_y;
-
-                    create_cross_stitch_pattern_set(
-                        segment_image,
-                        &resources.font,
-                        &resources.font_big,
-                        &image_filepath,
-                        &format!("segment_{}", segment_index + 1),
-                        output_dir_suffix,
-                        &color_mappings,
-                        Some(segment_index + 1),
-                        logical_first_coordinate_x,
-                        logical_first_coordinate_y,
-                        false,
-                        true,
-                    );
+    let fractional_mask_file =
+        open_auxiliary_image_file(image_filepath, stitch_kinds::FRACTIONAL_MASK_NAME)?;
+    let backstitch_layer_file =
+        open_auxiliary_image_file(image_filepath, backstitch::BACKSTITCH_LAYER_NAME)?;
+    let progress_mask_file =
+        open_auxiliary_image_file(image_filepath, progress::PROGRESS_MASK_NAME)?;
+
+    let mut result = Vec::new();
+    for (frame_index, image) in frames.into_iter().enumerate() {
+        // NOTE: Layers of the frame take precedence over files next to the image
+        let get_auxiliary_image = |name: &str, auxiliary_file: &Option<(String, Bitmap)>| {
+            auxiliary_layers_per_frame
+                .get(frame_index)
+                .and_then(|auxiliary_layers| {
+                    auxiliary_layers
+                        .iter()
+                        .find(|(layer_name, _layer)| layer_name == name)
+                })
+                .map(|(_layer_name, layer)| (image_filepath.to_owned(), layer.clone()))
+                .or_else(|| auxiliary_file.clone())
+        };
+
+        let stitch_kinds = if let Some((mask_filepath, mask)) =
+            get_auxiliary_image(stitch_kinds::FRACTIONAL_MASK_NAME, &fractional_mask_file)
+        {
+            if mask.dim() != image.dim() {
+                return Err(Error::Decode {
+                    filepath: mask_filepath,
+                    message: format!(
+                        "The fractional stitch mask is {}x{} but the image is {}x{}",
+                        mask.width, mask.height, image.width, image.height
+                    ),
                 });
-        }
-    });
-}
+            }
+            StitchKindMap::from_mask(&mask).map_err(|message| Error::Decode {
+                filepath: mask_filepath,
+                message,
+            })?
+        } else if let Some(imported_pattern) = &imported_pattern {
+            imported_pattern.stitch_kinds.clone()
+        } else {
+            StitchKindMap::new_full(image.width, image.height)
+        };
 
-fn create_cross_stitch_pattern_preview(
-    bitmap: &Bitmap,
-    image_filepath: &str,
-    output_filename_suffix: &str,
-    output_dir_suffix: &str,
-    resources: &Resources,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-) {
-    let bitmap = bitmap.extended(10, 10, 10, 10, PixelRGBA::transparent());
-    let tile_width = resources
-        .stitch_background_image_8x8_premultiplied_alpha
-        .width
-        / 8;
-    let tile_height = resources
-        .stitch_background_image_8x8_premultiplied_alpha
-        .height
-        / 8;
-
-    // Background only
-    let mut background_layer = Bitmap::new(
-        (tile_width * bitmap.width) as u32,
-        (tile_height * bitmap.height) as u32,
-    );
-    for y in 0..=bitmap.height / 8 {
-        for x in 0..=bitmap.width / 8 {
-            let pos = Vec2i::new(
-                resources
-                    .stitch_background_image_8x8_premultiplied_alpha
-                    .width
-                    * x,
-                resources
-                    .stitch_background_image_8x8_premultiplied_alpha
-                    .height
-                    * y,
-            );
-            resources
-                .stitch_background_image_8x8_premultiplied_alpha
-                .blit_to(&mut background_layer, pos, true);
-        }
-    }
-    // Write out png image
-    let output_filepath = get_image_output_filepath(&image_filepath, output_dir_suffix)
-        + "_"
-        + output_filename_suffix
-        + "_background.png";
-    Bitmap::write_to_png_file(&background_layer, &output_filepath);
-
-    // Stitches only
-    let mut colored_stitches_layer = Bitmap::new(
-        (tile_width * bitmap.width) as u32,
-        (tile_height * bitmap.height) as u32,
-    );
+        let backstitches = if let Some((layer_filepath, layer)) =
+            get_auxiliary_image(backstitch::BACKSTITCH_LAYER_NAME, &backstitch_layer_file)
+        {
+            // NOTE: The line layer has one grid point per cell corner and can therefore be one
+            //       pixel bigger than the image
+            let size_matches = (layer.width == image.width || layer.width == image.width + 1)
+                && (layer.height == image.height || layer.height == image.height + 1);
+            if !size_matches {
+                return Err(Error::Decode {
+                    filepath: layer_filepath,
+                    message: format!(
+                        "The backstitch layer is {}x{} but the image is {}x{}",
+                        layer.width, layer.height, image.width, image.height
+                    ),
+                });
+            }
+            backstitch::backstitches_create_from_line_layer(&layer)
+        } else if let Some(imported_pattern) = &imported_pattern {
+            imported_pattern.backstitches.clone()
+        } else {
+            Vec::new()
+        };
 
-    let mut random = Random::new_from_seed(1234);
-    for y in 0..bitmap.height {
-        for x in 0..bitmap.width {
-            let color = bitmap.get(x, y);
-
-            // Add stitch
-            if color.a != 0 {
-                let tile_pos_center =
-                    Vec2i::new(tile_width * x, tile_height * y) + (tile_width / 2);
-                let stitches = &color_mappings.get(&color).unwrap().stitches_premultiplied;
-                let stitches_count = stitches.len();
-                let stitch =
-                    &stitches[random.u32_bounded_exclusive(stitches_count as u32) as usize];
-                let stitch_center = Vec2i::new(stitch.width / 2, stitch.height / 2);
-                stitch.blit_to_alpha_blended_premultiplied(
-                    &mut colored_stitches_layer,
-                    tile_pos_center - stitch_center,
-                    true,
-                    ColorBlendMode::Normal,
-                );
+        let progress = if let Some((mask_filepath, mask)) =
+            get_auxiliary_image(progress::PROGRESS_MASK_NAME, &progress_mask_file)
+        {
+            if mask.dim() != image.dim() {
+                return Err(Error::Decode {
+                    filepath: mask_filepath,
+                    message: format!(
+                        "The progress mask is {}x{} but the image is {}x{}",
+                        mask.width, mask.height, image.width, image.height
+                    ),
+                });
             }
-        }
+            ProgressMap::from_mask(&mask)
+        } else {
+            ProgressMap::new_empty(image.width, image.height)
+        };
+
+        result.push(ImageFrame {
+            image,
+            stitch_kinds,
+            backstitches,
+            progress,
+        });
     }
-    // Write out png image
-    let output_filepath = get_image_output_filepath(&image_filepath, output_dir_suffix)
-        + "_"
-        + output_filename_suffix
-        + "_stitches.png";
-    Bitmap::write_to_png_file(
-        &colored_stitches_layer.to_unpremultiplied_alpha(),
-        &output_filepath,
-    );
 
-    // Combined
-    let mut combined = background_layer;
-    colored_stitches_layer.blit_to_alpha_blended_premultiplied(
-        &mut combined,
-        Vec2i::zero(),
-        false,
-        ColorBlendMode::Normal,
-    );
-    // Write out png image
-    let output_filepath = get_image_output_filepath(&image_filepath, output_dir_suffix)
-        + "_"
-        + output_filename_suffix
-        + ".png";
-    Bitmap::write_to_png_file(&combined, &output_filepath);
+    let imported_color_mappings = imported_pattern.map(|pattern| pattern.color_mappings);
+    Ok((result, imported_color_mappings))
 }
 
-fn create_preview_dir(
-    image: &Bitmap,
-    image_filepath: &str,
-    resources: &Resources,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-) {
-    let output_dir_suffix = "preview";
-
-    rayon::scope(|scope| {
-        // Create stitched preview
-        scope.spawn(|_| {
-            create_cross_stitch_pattern_preview(
-                &image,
-                &image_filepath,
-                "complete",
-                output_dir_suffix,
-                resources,
-                &color_mappings,
-            );
-        });
-    });
+/// Returns the filepath and content of the `.png` file with the given name suffix next to the
+/// image if there is one
+fn open_auxiliary_image_file(image_filepath: &str, name: &str) -> Result<Option<(String, Bitmap)>> {
+    let auxiliary_filepath = get_auxiliary_image_filepath(image_filepath, name);
+    if !path_exists(&auxiliary_filepath) {
+        return Ok(None);
+    }
+    let decode_error = |message: String| Error::Decode {
+        filepath: auxiliary_filepath.clone(),
+        message,
+    };
+    let data = std::fs::read(&auxiliary_filepath)
+        .map_err(|error| Error::io(&auxiliary_filepath, error))?;
+    image_formats::png_check_dimensions(&data).map_err(decode_error)?;
+    let bitmap = Bitmap::from_png_file(&auxiliary_filepath).map_err(decode_error)?;
+    Ok(Some((auxiliary_filepath, bitmap)))
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-// Legend creation
-
-fn create_pattern_page_layout(font: &BitmapFont, layout_indices: &[Vec2i]) -> Bitmap {
-    let caption_image =
-        Bitmap::create_from_text(font, "\n\nPattern parts overview:\n", 1, PixelRGBA::white());
-
-    let page_count = layout_indices.len();
-    // NOTE: Indexes begin at 0 therefore we add 1
-    let num_rows = 1 + layout_indices.iter().map(|v| v.y).max().unwrap();
-    let num_columns = 1 + layout_indices.iter().map(|v| v.x).max().unwrap();
-
-    let page_tile_dim = {
-        // NOTE: We want to have a 1px visual gap between page tiles therefore we add 1
-        let page_tile_width = 1 + font
-            .get_text_bounding_rect(&format!(" {} ", page_count), 1, false)
-            .dim
-            .x;
-        let page_tile_height = 1 + (page_tile_width as f32 * (9.0 / 6.0)) as i32;
-        Vec2i::new(page_tile_width, page_tile_height)
-    };
-
-    let image_width = num_columns * page_tile_dim.x;
-    let image_height = num_rows * page_tile_dim.y;
-    let mut image = Bitmap::new_filled(image_width as u32, image_height as u32, PixelRGBA::white());
-    for (page_index, pos_index) in layout_indices.iter().enumerate() {
-        let pos = *pos_index * page_tile_dim;
-        image.draw_rect(
-            pos.x,
-            pos.y,
-            page_tile_dim.x - 1,
-            page_tile_dim.y - 1,
-            PixelRGBA::black(),
-        );
-        image.draw_text_aligned_in_point(
-            font,
-            &(page_index + 1).to_string(),
-            1,
-            pos + page_tile_dim / 2,
-            Vec2i::zero(),
-            Some(TextAlignment {
-                horizontal: AlignmentHorizontal::Center,
-                vertical: AlignmentVertical::Center,
-                origin_is_baseline: false,
-                ignore_whitespace: false,
-            }),
-        );
-    }
+// Image decoding
 
-    caption_image.glued_to(&image, GluePosition::TopLeft, 0, PixelRGBA::white())
-}
+/// Returns every frame of the GIF as it is shown on screen. Partial frames are composited onto
+/// the previous frames according to their offsets and disposal methods.
+fn bitmaps_create_from_gif_data(data: &[u8]) -> std::result::Result<Vec<Bitmap>, String> {
+    let mut decoder = gif::Decoder::new(data);
 
-fn create_legend_entry(font: &BitmapFont, info: &ColorInfo) -> Bitmap {
-    // Draw color and symbol mapping
-    let mut color_symbol_map =
-        Bitmap::new_filled(2 * TILE_SIZE as u32, TILE_SIZE as u32, PixelRGBA::white());
-    color_symbol_map.draw_rect_filled(0, 0, TILE_SIZE, TILE_SIZE, info.color);
-    color_symbol_map.draw_rect(
-        0,
-        0,
-        TILE_SIZE,
-        TILE_SIZE,
-        PixelRGBA::from_color(Color::black()),
-    );
-    blit_symbol(
-        &info.symbol,
-        &mut color_symbol_map,
-        Vec2i::filled_x(TILE_SIZE),
-        PixelRGBA::white(),
-    );
-    color_symbol_map.draw_rect(
-        0 + TILE_SIZE,
-        0,
-        TILE_SIZE,
-        TILE_SIZE,
-        PixelRGBA::from_color(Color::black()),
+    decoder.set(gif::ColorOutput::RGBA);
+    let mut decoder = decoder.read_info().map_err(|error| error.to_string())?;
+    image_formats::pixel_count_checked(decoder.width() as i32, decoder.height() as i32)?;
+
+    // NOTE: We treat the background color of the logical screen as transparent which is what most
+    //       viewers and editors do
+    let mut canvas = Bitmap::new_filled(
+        decoder.width() as u32,
+        decoder.height() as u32,
+        PixelRGBA::transparent(),
     );
+    let mut frames = Vec::new();
+    while let Some(frame) = decoder
+        .read_next_frame()
+        .map_err(|error| format!("Cannot decode frame {}: {}", frames.len() + 1, error))?
+    {
+        let canvas_before_frame = if frame.dispose == gif::DisposalMethod::Previous {
+            Some(canvas.clone())
+        } else {
+            None
+        };
 
-    // Add stitches info
-    let stitches_info = Bitmap::create_from_text(
-        font,
-        &format!(" {} stitches      ", info.count),
-        1,
-        PixelRGBA::white(),
-    );
-    stitches_info.glued_to(
-        &mut color_symbol_map,
-        GluePosition::RightCenter,
-        0,
-        PixelRGBA::white(),
-    )
-}
+        let frame_left = frame.left as i32;
+        let frame_top = frame.top as i32;
+        let frame_width = frame.width as i32;
+        for (pixel_index, color) in frame.buffer.chunks_exact(4).enumerate() {
+            // NOTE: Transparent pixels of a frame let the previous frames shine through
+            if color[3] == 0 {
+                continue;
+            }
+            let x = frame_left + pixel_index as i32 % frame_width;
+            let y = frame_top + pixel_index as i32 / frame_width;
+            if x < canvas.width && y < canvas.height {
+                canvas.set(x, y, PixelRGBA::new(color[0], color[1], color[2], color[3]));
+            }
+        }
+        frames.push(canvas.clone());
+
+        match frame.dispose {
+            gif::DisposalMethod::Background => canvas.draw_rect_filled_safely(
+                frame_left,
+                frame_top,
+                frame_width,
+                frame.height as i32,
+                PixelRGBA::transparent(),
+            ),
+            gif::DisposalMethod::Previous => {
+                // NOTE: We can unwrap here because we saved the canvas above
+                canvas = canvas_before_frame.unwrap();
+            }
+            gif::DisposalMethod::Any | gif::DisposalMethod::Keep => {}
+        }
+    }
 
-fn create_legend_block(font: &BitmapFont, infos: &[ColorInfo]) -> Bitmap {
-    let entries: Vec<Bitmap> = infos
-        .iter()
-        .map(|entry| create_legend_entry(font, entry))
-        .collect();
-    Bitmap::glue_together_multiple(
-        &entries,
-        GluePosition::BottomLeft,
-        TILE_SIZE,
-        PixelRGBA::white(),
-    )
+    if frames.is_empty() {
+        return Err("No frame found".to_owned());
+    }
+    Ok(frames)
 }
 
-fn create_cross_stitch_legend(
-    image_dimensions: Vec2i,
-    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
-    image_filepath: &str,
-    output_dir_suffix: &str,
-    font: &BitmapFont,
-    segment_layout_indices: &[Vec2i],
-) {
-    let mut legend = {
-        // Create color and stitch stats
-        let stats_bitmap = {
-            let color_count = color_mappings.len();
-            let stitch_count = color_mappings
-                .values()
-                .fold(0, |acc, entry| acc + entry.count);
-
-            Bitmap::create_from_text(
-                &font,
-                &format!(
-                    "Size:     {}x{}\n\nColors:   {}\n\nStitches: {}\n\n\n",
-                    image_dimensions.x, image_dimensions.y, color_count, stitch_count
-                ),
-                1,
-                PixelRGBA::white(),
-            )
-        };
-
-        // Create color mapping blocks
-        let blocks = {
-            let color_infos: Vec<ColorInfo> = color_mappings.values().cloned().collect();
-            let block_bitmaps: Vec<Bitmap> = color_infos
-                .chunks(LEGEND_BLOCK_ENTRY_COUNT)
-                .map(|chunk| create_legend_block(&font, chunk))
-                .collect();
-            let num_columns = block_bitmaps.len().max(4);
-            let block_rows: Vec<Bitmap> = block_bitmaps
-                .chunks(num_columns)
-                .map(|chunk| {
-                    Bitmap::glue_together_multiple(
-                        chunk,
-                        GluePosition::RightTop,
-                        TILE_SIZE,
-                        PixelRGBA::white(),
-                    )
-                })
-                .collect();
-            Bitmap::glue_together_multiple(
-                &block_rows,
-                GluePosition::BottomLeft,
-                TILE_SIZE,
-                PixelRGBA::white(),
-            )
-            .extended(0, 0, 0, (1.5 * TILE_SIZE as f32) as i32, PixelRGBA::white())
-        };
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Pattern dir creation
 
-        Bitmap::glue_a_to_b(
-            &stats_bitmap,
-            &blocks,
-            GluePosition::TopLeft,
-            0,
-            PixelRGBA::white(),
+/// Writes the given plain or centered pattern set of a frame in all selected formats
+fn write_patterns_dir(
+    frame: &ImageFrame,
+    output_filepath_base: &str,
+    centered: bool,
+    pattern_set: &PatternSet,
+    patterns: &Patterns,
+    options: &Options,
+) -> Result<()> {
+    let (output_filepath, report) = if let Some(bead_settings) = &options.pattern.beads {
+        (
+            output_filepath_base.to_owned() + "_beads.csv",
+            create_bead_usage_report(&patterns.color_mappings, bead_settings),
+        )
+    } else {
+        (
+            output_filepath_base.to_owned() + "_thread_usage.csv",
+            create_thread_usage_report(
+                &patterns.color_mappings,
+                &patterns.backstitch_infos,
+                &options.pattern.thread_usage,
+            ),
         )
     };
+    std::fs::write(&output_filepath, report).map_err(|error| Error::io(&output_filepath, error))?;
 
-    // Add page layout order if necessary
-    if segment_layout_indices.len() > 1 {
-        let page_layout_image = create_pattern_page_layout(&font, segment_layout_indices);
+    if options.format_png {
+        let output_filepath = output_filepath_base.to_owned() + "_legend.png";
+        Bitmap::write_to_png_file(&pattern_set.legend, &output_filepath);
 
-        legend = legend.glued_to(
-            &page_layout_image,
-            GluePosition::TopLeft,
-            0,
-            PixelRGBA::white(),
-        );
+        pattern_set.pages.par_iter().for_each(|page| {
+            let output_filepath =
+                output_filepath_base.to_owned() + "_" + &page.filename_suffix() + ".png";
+            Bitmap::write_to_png_file(&page.bitmap, &output_filepath);
+        });
+    }
 
-        // Draw separating line between colors and page order layout
-        for x in 0..legend.width {
-            legend.set(
-                x,
-                legend.height - page_layout_image.height,
-                PixelRGBA::black(),
+    if options.format_pdf {
+        // NOTE: If we have pattern parts we leave out the complete patterns because they would be
+        //       too small to read when scaled down to a single page
+        let has_segments = pattern_set
+            .pages
+            .iter()
+            .any(|page| page.segment_index.is_some());
+        let mut pdf_pages = vec![&pattern_set.legend];
+        for pattern_type in [
+            PatternType::Colorized,
+            PatternType::BlackAndWhite,
+            PatternType::ColorizedNoSymbols,
+            PatternType::PaintByNumbers,
+            PatternType::PaintByNumbersRegions,
+        ]
+        .iter()
+        {
+            pdf_pages.extend(
+                pattern_set
+                    .pages
+                    .iter()
+                    .filter(|page| page.pattern_type == *pattern_type)
+                    .filter(|page| {
+                        page.segment_index.is_some()
+                            || !has_segments
+                            || page.pattern_type == PatternType::PaintByNumbers
+                            || page.pattern_type == PatternType::PaintByNumbersRegions
+                    })
+                    .map(|page| &page.bitmap),
             );
         }
+
+        let output_filepath = output_filepath_base.to_owned() + "_patterns.pdf";
+        pdf::write_pdf_file(&output_filepath, &pdf_pages, options.page_size)?;
+    }
+
+    if options.format_oxs {
+        let output_filepath = output_filepath_base.to_owned() + ".oxs";
+        oxs::write_oxs_file(
+            &output_filepath,
+            &frame.image,
+            &frame.stitch_kinds,
+            &frame.backstitches,
+            &patterns.color_mappings,
+            &options.pattern.thread_usage,
+            options.pattern.fabric_color,
+        )?;
+    }
+
+    if options.format_svg {
+        let context = PatternRenderContext::new_complete(
+            &frame.image,
+            &frame.stitch_kinds,
+            &frame.backstitches,
+            &frame.progress,
+            &patterns.color_mappings,
+            options.pattern.fabric_color,
+            centered,
+        );
+        let svg_pattern_set =
+            svg::create_svg_pattern_set(&context, &patterns.backstitch_infos, &options.pattern)?;
+        let output_filepath = output_filepath_base.to_owned() + "_legend.svg";
+        std::fs::write(&output_filepath, &svg_pattern_set.legend)
+            .map_err(|error| Error::io(&output_filepath, error))?;
+        for page in &svg_pattern_set.pages {
+            let output_filepath =
+                output_filepath_base.to_owned() + "_" + &page.filename_suffix() + ".svg";
+            std::fs::write(&output_filepath, &page.svg)
+                .map_err(|error| Error::io(&output_filepath, error))?;
+        }
     }
 
-    let padding = TILE_SIZE;
-    let final_image = legend.extended(padding, padding, padding, padding, PixelRGBA::white());
+    Ok(())
+}
 
-    // Write out png image
-    let output_filepath =
-        get_image_output_filepath(&image_filepath, output_dir_suffix) + "_legend.png";
-    Bitmap::write_to_png_file(&final_image, &output_filepath);
+fn write_preview_dir(output_filepath_base: &str, preview: &Preview) {
+    Bitmap::write_to_png_file(
+        &preview.background,
+        &(output_filepath_base.to_owned() + "_complete_background.png"),
+    );
+    Bitmap::write_to_png_file(
+        &preview.stitches,
+        &(output_filepath_base.to_owned() + "_complete_stitches.png"),
+    );
+    Bitmap::write_to_png_file(
+        &preview.combined,
+        &(output_filepath_base.to_owned() + "_complete.png"),
+    );
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Color reduction report
+
+fn write_color_reduction_report(
+    output_filepath_base: &str,
+    color_reduction: &ColorReduction,
+) -> Result<()> {
+    let output_filepath = output_filepath_base.to_owned() + "_color_reduction.txt";
+    std::fs::write(&output_filepath, color_reduction.to_report_text())
+        .map_err(|error| Error::io(&output_filepath, error))
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
 // Main
 
+/// Some of the images failed while the others were created successfully
+const EXIT_CODE_IMAGES_FAILED: i32 = 1;
+/// Invalid commandline options or missing resources, no image was processed
+const EXIT_CODE_SETUP_FAILED: i32 = 2;
+
 #[cfg(windows)]
 fn show_messagebox(caption: &str, message: &str, is_error: bool) {
     use std::iter::once;
@@ -1467,18 +1322,71 @@ fn show_messagebox(caption: &str, message: &str, is_error: bool) {
     };
 }
 
+/// Shows a desktop notification on Linux via `notify-send` (i.e. from libnotify). Does nothing if
+/// it is not installed.
+#[cfg(all(unix, not(target_os = "macos")))]
+fn show_desktop_notification(caption: &str, message: &str, is_error: bool) {
+    // NOTE: We ignore errors because the notification is just a convenience on top of our output
+    let _ = std::process::Command::new("notify-send")
+        .arg("--app-name=Pixie Stitch")
+        .arg(if is_error {
+            "--urgency=critical"
+        } else {
+            "--urgency=normal"
+        })
+        .arg(caption)
+        .arg(message)
+        .output();
+}
+
+/// Shows a desktop notification on macOS via AppleScript
+#[cfg(target_os = "macos")]
+fn show_desktop_notification(caption: &str, message: &str, _is_error: bool) {
+    fn applescript_string(text: &str) -> String {
+        format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+    let script = format!(
+        "display notification {} with title {}",
+        applescript_string(message),
+        applescript_string(caption)
+    );
+    // NOTE: We ignore errors because the notification is just a convenience on top of our output
+    let _ = std::process::Command::new("osascript")
+        .arg("-e")
+        .arg(script)
+        .output();
+}
+
+/// NOTE: On Windows we already show a messagebox instead
+#[cfg(windows)]
+fn show_desktop_notification(_caption: &str, _message: &str, _is_error: bool) {}
+
+fn show_error(message: &str) {
+    eprintln!("{}", message);
+
+    // NOTE: We are a windows subsystem application and have no console to print to
+    #[cfg(windows)]
+    show_messagebox("Pixie Stitch Error", message, true);
+}
+
 fn set_panic_hook() {
     std::panic::set_hook(Box::new(|panic_info| {
         let (message, location) = panic_message_split_to_message_and_location(panic_info);
-        let final_message = format!("{}\n\nError occured at: {}", message, location);
-
-        show_messagebox("Pixie Stitch Error", &final_message, true);
-
-        // NOTE: This forces the other threads to shutdown as well
-        std::process::abort();
+        // NOTE: The panic is caught in `main` and shows up in the summary of failed images
+        eprintln!("{}\n\nError occured at: {}", message, location);
     }));
 }
 
+fn panic_payload_to_message(panic_payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = panic_payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic_payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Unknown error".to_owned()
+    }
+}
+
 fn main() {
     set_panic_hook();
 
@@ -1486,24 +1394,41 @@ fn main() {
     // test_color_sorting();
     // test_symbols_contrast();
 
-    let (font, font_big) = load_fonts();
-    let symbols = collect_symbols();
-    let symbols_alphanum = create_alphanumeric_symbols(&font);
-    let (
-        stitch_images_premultiplied_alpha,
-        stitch_images_luminance_premultiplied_alpha,
-        stitch_background_image_8x8_premultiplied_alpha,
-    ) = load_stitch_preview_images_premultiplied_alpha();
-    let resources = Resources {
-        font,
-        font_big,
-        stitch_background_image_8x8_premultiplied_alpha,
+    let args: Vec<String> = std::env::args().collect();
+    let options = match parse_commandline(&args) {
+        Ok(CommandlineAction::Run(options)) => *options,
+        Ok(CommandlineAction::ShowUsage) => {
+            show_usage();
+            return;
+        }
+        Err(message) => {
+            show_error(&format!("{}\n\n{}", message, USAGE));
+            std::process::exit(EXIT_CODE_SETUP_FAILED);
+        }
+    };
+
+    let resources = load_resources(options.tile_size).unwrap_or_else(|error| {
+        show_error(&error.to_string());
+        std::process::exit(EXIT_CODE_SETUP_FAILED);
+    });
+    // NOTE: Beads are matched the same way as floss, so both use the same kind of catalog
+    let floss_catalog = match (&options.floss_catalog, &options.bead_catalog) {
+        (Some(catalog_name), _) => Some((catalog_name, false)),
+        (None, Some(catalog_name)) => Some((catalog_name, true)),
+        (None, None) => None,
     };
+    let floss_catalog = floss_catalog.map(|(catalog_name, is_bead_catalog)| {
+        load_floss_catalog(catalog_name, is_bead_catalog).unwrap_or_else(|error| {
+            show_error(&error.to_string());
+            std::process::exit(EXIT_CODE_SETUP_FAILED);
+        })
+    });
 
     // NOTE: We can uncomment this if we want to test with more colors than we have symbols
     /*
-    symbols = symbols.iter().cloned().cycle().take(50000).collect();
-    symbols_alphanum = symbols_alphanum
+    resources.symbols = resources.symbols.iter().cloned().cycle().take(50000).collect();
+    resources.symbols_alphanum = resources
+        .symbols_alphanum
         .iter()
         .cloned()
         .cycle()
@@ -1511,48 +1436,368 @@ fn main() {
         .collect();
     */
 
-    for image_filepath in get_image_filepaths_from_commandline() {
-        create_image_output_dir(&image_filepath, "");
-        create_image_output_dir(&image_filepath, "centered");
-        create_image_output_dir(&image_filepath, "preview");
+    let mut failed_images = Vec::new();
+    for image_filepath in &options.image_filepaths {
+        // NOTE: Unexpected errors (i.e. failing to write a png file) panic. We catch them here so
+        //       that they only fail the image they happened in.
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            create_patterns_for_file(image_filepath, &resources, floss_catalog.as_ref(), &options)
+        }));
+        let error_message = match result {
+            Ok(Ok(())) => continue,
+            Ok(Err(error)) => error.to_string(),
+            Err(panic_payload) => panic_payload_to_message(panic_payload),
+        };
+        eprintln!("Failed to create patterns for '{}'", image_filepath);
+        failed_images.push((image_filepath.clone(), error_message));
+    }
 
-        let image = open_image(&image_filepath);
-        let color_mappings = create_color_mappings_from_image(
-            &image,
-            &image_filepath,
-            &symbols,
-            &symbols_alphanum,
-            &stitch_images_premultiplied_alpha,
-            &stitch_images_luminance_premultiplied_alpha,
+    if failed_images.is_empty() {
+        println!("Finished creating patterns. Enjoy!");
+        #[cfg(all(windows, not(debug_assertions)))]
+        show_messagebox("Pixie Stitch", "Finished creating patterns. Enjoy!", false);
+        if options.desktop_notifications {
+            show_desktop_notification("Pixie Stitch", "Finished creating patterns. Enjoy!", false);
+        }
+    } else {
+        let mut summary = format!(
+            "Finished creating patterns for {} of {} image(s). The following failed:\n",
+            options.image_filepaths.len() - failed_images.len(),
+            options.image_filepaths.len()
         );
+        for (image_filepath, error_message) in &failed_images {
+            summary += &format!("\n'{}':\n{}\n", image_filepath, error_message);
+        }
+        show_error(&summary);
+        if options.desktop_notifications {
+            show_desktop_notification(
+                "Pixie Stitch Error",
+                &format!(
+                    "Failed to create patterns for {} of {} image(s)",
+                    failed_images.len(),
+                    options.image_filepaths.len()
+                ),
+                true,
+            );
+        }
+        std::process::exit(EXIT_CODE_IMAGES_FAILED);
+    }
+}
 
-        rayon::scope(|scope| {
-            scope.spawn(|_| {
-                create_patterns_dir(&image, &image_filepath, &resources, &color_mappings);
-            });
-            scope.spawn(|_| {
-                create_patterns_dir_centered(&image, &image_filepath, &resources, &color_mappings);
-            });
-            scope.spawn(|_| {
-                create_preview_dir(&image, &image_filepath, &resources, &color_mappings);
+/// Creates all patterns for all selected frames of the given image file
+fn create_patterns_for_file(
+    image_filepath: &str,
+    resources: &Resources,
+    floss_catalog: Option<&FlossCatalog>,
+    options: &Options,
+) -> Result<()> {
+    let (mut frames, imported_color_mappings) = open_image(image_filepath)?;
+    if let FrameSelection::Single(frame_index) = options.frame_selection {
+        if frame_index >= frames.len() {
+            return Err(Error::FrameNotFound {
+                filepath: image_filepath.to_owned(),
+                frame_index,
+                frame_count: frames.len(),
             });
-        });
+        }
+        frames = vec![frames.swap_remove(frame_index)];
+    }
+
+    // NOTE: Imported patterns have no background and are always at their native resolution. We
+    //       clear the background first so that it does not disturb the pixel scale detection.
+    if imported_color_mappings.is_none() {
+        for frame in frames.iter_mut() {
+            frame.image = background::image_clear_background(
+                &frame.image,
+                &options.key_colors,
+                options.alpha_threshold,
+                options.pattern.fabric_color,
+            );
+        }
+
+        let images: Vec<&Bitmap> = frames.iter().map(|frame| &frame.image).collect();
+        let pixel_scale = match options.pixel_scale {
+            PixelScaleSelection::Auto => pixel_scale::pixel_scale_detect(&images),
+            PixelScaleSelection::Factor(1) => None,
+            PixelScaleSelection::Factor(factor) => {
+                Some(pixel_scale::pixel_scale_with_factor(&images, factor))
+            }
+        };
+        if let Some(pixel_scale) = pixel_scale {
+            let (width, height) = (frames[0].image.width, frames[0].image.height);
+            println!(
+                "'{}' is upscaled {}x with an offset of {}x{}, reducing it from {}x{} to {}x{} pixels",
+                image_filepath,
+                pixel_scale.factor,
+                pixel_scale.offset.x,
+                pixel_scale.offset.y,
+                width,
+                height,
+                pixel_scale.native_size(width, pixel_scale.offset.x),
+                pixel_scale.native_size(height, pixel_scale.offset.y),
+            );
+            // NOTE: Pixel art whose features are all at least two pixels wide looks exactly like
+            //       upscaled pixel art, so we make sure that an unwanted reduction does not go
+            //       unnoticed
+            if options.pixel_scale == PixelScaleSelection::Auto {
+                println!(
+                    "Warning: The pixel scale {}x of '{}' was detected automatically. If the \
+                     image is already at its native resolution run again with `--pixel-scale 1` \
+                     to keep it as it is",
+                    pixel_scale.factor, image_filepath,
+                );
+            }
+            frames = frames
+                .into_iter()
+                .map(|frame| ImageFrame {
+                    image: pixel_scale.downscale_image(&frame.image),
+                    stitch_kinds: pixel_scale.downscale_stitch_kinds(&frame.stitch_kinds),
+                    backstitches: pixel_scale.downscale_backstitches(
+                        &frame.backstitches,
+                        width,
+                        height,
+                    ),
+                    progress: pixel_scale.downscale_progress(&frame.progress),
+                })
+                .collect();
+        }
+    }
+
+    // NOTE: Beads can only be whole, so we ignore fractional stitches and backstitches in bead
+    //       mode
+    if options.pattern.beads.is_some() {
+        for frame in frames.iter_mut() {
+            if frame.stitch_kinds.has_fractional_stitches() || !frame.backstitches.is_empty() {
+                println!(
+                    "Ignoring fractional stitches and backstitches of '{}' in bead mode",
+                    image_filepath
+                );
+            }
+            frame.stitch_kinds = StitchKindMap::new_full(frame.image.width, frame.image.height);
+            frame.backstitches.clear();
+        }
+    }
+
+    // NOTE: We outline the regions before the color reduction so that the outline colors refer to
+    //       the colors of the original image
+    if !options.backstitch_outline_colors.is_empty() && options.pattern.beads.is_none() {
+        for frame in frames.iter_mut() {
+            frame
+                .backstitches
+                .extend(backstitch::backstitches_create_from_region_outlines(
+                    &frame.image,
+                    &options.backstitch_outline_colors,
+                    options.backstitch_outline_color,
+                ));
+        }
+    }
+
+    // NOTE: All frames share the same color reduction and symbol assignment so that their
+    //       patterns can be used together
+    let frames_combined = Bitmap::glue_together_multiple(
+        &frames
+            .iter()
+            .map(|frame| frame.image.clone())
+            .collect::<Vec<Bitmap>>(),
+        GluePosition::BottomLeft,
+        0,
+        PixelRGBA::transparent(),
+    );
+    let color_reduction = options
+        .max_colors
+        .and_then(|max_colors| color_quantization::reduce_colors(&frames_combined, max_colors));
+    let frames: Vec<ImageFrame> = frames
+        .into_iter()
+        .map(|frame| {
+            let image = if let Some(color_reduction) = &color_reduction {
+                color_reduction.apply_to_image(&frame.image)
+            } else {
+                frame.image
+            };
+            let image = match floss_catalog {
+                // NOTE: In bead mode we can only use the colors of existing beads
+                Some(floss_catalog) if options.snap_to_floss || options.pattern.beads.is_some() => {
+                    floss_catalog.snap_image_colors(&image)
+                }
+                _ => image,
+            };
+            ImageFrame { image, ..frame }
+        })
+        .collect();
+    let shared_color_mappings = if frames.len() > 1 {
+        let mut color_mappings = image_extract_colors_and_counts(&Bitmap::glue_together_multiple(
+            &frames
+                .iter()
+                .map(|frame| frame.image.clone())
+                .collect::<Vec<Bitmap>>(),
+            GluePosition::BottomLeft,
+            0,
+            PixelRGBA::transparent(),
+        ));
+        // NOTE: The labels of the shared mapping follow its legend order, so every frame labels a
+        //       color the same way even if it lacks some of the other colors
+        color_mappings_assign_symbols(&mut color_mappings, resources);
+        Some(color_mappings)
+    } else {
+        imported_color_mappings
+    };
+
+    for (frame_index, frame) in frames.iter().enumerate() {
+        // NOTE: Every frame gets its own output directories if we have more than one
+        let image_filepath = if frames.len() > 1 {
+            get_frame_filepath(image_filepath, frame_index)
+        } else {
+            image_filepath.to_owned()
+        };
+        create_patterns_for_image(
+            frame,
+            &image_filepath,
+            resources,
+            floss_catalog,
+            shared_color_mappings.as_ref(),
+            color_reduction.as_ref(),
+            options,
+        )?;
+    }
+
+    Ok(())
+}
+
+fn create_patterns_for_image(
+    frame: &ImageFrame,
+    image_filepath: &str,
+    resources: &Resources,
+    floss_catalog: Option<&FlossCatalog>,
+    predefined_color_mappings: Option<&IndexMap<PixelRGBA, ColorInfo>>,
+    color_reduction: Option<&ColorReduction>,
+    options: &Options,
+) -> Result<()> {
+    // NOTE: We check all output dirs before we create any of them. Otherwise a refused dir would
+    //       leave the dirs created before it behind and these would be refused on the next run.
+    let output_dir_suffixes: Vec<&str> = [
+        (options.pattern.create_patterns, ""),
+        (options.pattern.create_patterns_centered, "centered"),
+        (options.pattern.create_preview, "preview"),
+    ]
+    .iter()
+    .filter(|(is_enabled, _suffix)| *is_enabled)
+    .map(|(_is_enabled, suffix)| *suffix)
+    .collect();
+    check_image_output_dirs_available(image_filepath, &output_dir_suffixes, options)?;
+
+    let patterns = create_patterns(
+        frame,
+        resources,
+        floss_catalog,
+        predefined_color_mappings,
+        &options.pattern,
+    )?;
+
+    if options.pattern.beads.is_none() {
+        let stitch_colors = patterns
+            .color_mappings
+            .values()
+            .map(|info| (info.color, info.floss.as_ref()));
+        let backstitch_colors = patterns
+            .backstitch_infos
+            .values()
+            .map(|info| (info.color, info.floss.as_ref()));
+        for (color, floss) in stitch_colors.chain(backstitch_colors) {
+            if fabric::color_is_hard_to_see_on_fabric(color, options.pattern.fabric_color) {
+                println!(
+                    "Warning: The color {}{} of '{}' is hard to see on the fabric color {}",
+                    color_quantization::color_to_hex_string(color),
+                    floss
+                        .map(|floss| format!(" ({} {})", floss.catalog_name, floss.number))
+                        .unwrap_or_default(),
+                    image_filepath,
+                    color_quantization::color_to_hex_string(options.pattern.fabric_color),
+                );
+            }
+        }
+    }
+
+    let output_filepath_base_plain = if options.pattern.create_patterns {
+        Some(create_image_output_dir(image_filepath, "", options)?)
+    } else {
+        None
+    };
+    let output_filepath_base_centered = if options.pattern.create_patterns_centered {
+        Some(create_image_output_dir(
+            image_filepath,
+            "centered",
+            options,
+        )?)
+    } else {
+        None
+    };
+    let output_filepath_base_preview = if options.pattern.create_preview {
+        Some(create_image_output_dir(image_filepath, "preview", options)?)
+    } else {
+        None
+    };
+
+    if let Some(color_reduction) = color_reduction {
+        for output_filepath_base in output_filepath_base_plain
+            .iter()
+            .chain(output_filepath_base_centered.iter())
+            .chain(output_filepath_base_preview.iter())
+        {
+            write_color_reduction_report(output_filepath_base, color_reduction)?;
+        }
     }
 
-    #[cfg(not(debug_assertions))]
-    show_messagebox("Pixie Stitch", "Finished creating patterns. Enjoy!", false);
+    let mut result_plain = Ok(());
+    let mut result_centered = Ok(());
+    rayon::scope(|scope| {
+        scope.spawn(|_| {
+            if let (Some(output_filepath_base), Some(pattern_set)) =
+                (&output_filepath_base_plain, &patterns.patterns)
+            {
+                result_plain = write_patterns_dir(
+                    frame,
+                    output_filepath_base,
+                    false,
+                    pattern_set,
+                    &patterns,
+                    options,
+                );
+            }
+        });
+        scope.spawn(|_| {
+            if let (Some(output_filepath_base), Some(pattern_set)) =
+                (&output_filepath_base_centered, &patterns.patterns_centered)
+            {
+                result_centered = write_patterns_dir(
+                    frame,
+                    output_filepath_base,
+                    true,
+                    pattern_set,
+                    &patterns,
+                    options,
+                );
+            }
+        });
+        scope.spawn(|_| {
+            if let (Some(output_filepath_base), Some(preview)) =
+                (&output_filepath_base_preview, &patterns.preview)
+            {
+                write_preview_dir(output_filepath_base, preview);
+            }
+        });
+    });
+
+    result_plain.and(result_centered)
 }
 
 ////////////////////////////////////////////////////////////////////////////////////////////////////
-/// Test functions
+// Test functions
 
 /// This is for test purposes. It creates a colorful image and repeats all available symbols onto
 /// it. Its main pupose was testing wheter the algorithm chooses black or white symbols correctly
 /// depending on the relative luminance of the background color
 #[allow(dead_code)]
 fn test_symbols_contrast() {
-    let (font, font_big) = load_fonts();
-
     fn create_test_color_ramp_bitmap() -> Bitmap {
         let mut colors = Vec::new();
 
@@ -1587,30 +1832,38 @@ fn test_symbols_contrast() {
     let image = create_test_color_ramp_bitmap();
     Bitmap::write_to_png_file(&image, "test_symbol_contrast.png");
 
-    let mut symbols = collect_symbols();
-    while symbols.len() < 128 * 128 {
-        symbols = [&symbols[..], &symbols[..]].concat()
+    let mut resources = load_resources(TILE_SIZE_DEFAULT).unwrap();
+    while resources.symbols.len() < 128 * 128 {
+        resources.symbols = [&resources.symbols[..], &resources.symbols[..]].concat();
+        resources.symbols_alphanum = [
+            &resources.symbols_alphanum[..],
+            &resources.symbols_alphanum[..],
+        ]
+        .concat();
     }
 
+    let stitch_kinds = StitchKindMap::new_full(image.width, image.height);
     let color_mappings =
-        create_color_mappings_from_image(&image, "", &symbols, &vec![], &vec![], &vec![]);
-
-    create_cross_stitch_pattern(
-        &image,
-        &font,
-        &font_big,
-        "test_symbol_contrast.png",
-        "cross_stitch_colorized",
-        "",
-        &color_mappings,
-        None,
-        0,
-        0,
-        PatternType::Colorized,
-        true,
-        true,
-        PixelRGBA::white(),
-    );
+        create_color_mappings_from_image(&image, &stitch_kinds, &resources, None, None).unwrap();
+
+    let progress = ProgressMap::new_empty(image.width, image.height);
+    let context = PatternRenderContext {
+        add_origin_grid_bars: true,
+        ..PatternRenderContext::new_complete(
+            &image,
+            &stitch_kinds,
+            &[],
+            &progress,
+            &color_mappings,
+            fabric::FABRIC_COLOR_DEFAULT,
+            false,
+        )
+    };
+    let pattern = create_cross_stitch_pattern(&context, &resources, PatternType::Colorized);
+    let output_filepath =
+        create_image_output_dir("test_symbol_contrast.png", "", &Options::default()).unwrap()
+            + "_cross_stitch_colorized.png";
+    Bitmap::write_to_png_file(&pattern, &output_filepath);
 }
 
 /// This is for test purposes. It creates a colorful image and sorts its colors to test the
@@ -1642,7 +1895,212 @@ fn test_color_sorting() {
 
     image
         .data
-        .sort_by(|a, b| PixelRGBA::compare_by_hue_luminosity_saturation(a, b));
+        .sort_by(PixelRGBA::compare_by_hue_luminosity_saturation);
 
     Bitmap::write_to_png_file(&image, "test_all_colors_sorted.png");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timestamps_follow_the_gregorian_calendar() {
+        assert_eq!(timestamp_from_unix_seconds(0), "1970-01-01_00-00-00");
+        assert_eq!(
+            timestamp_from_unix_seconds(951_827_696),
+            "2000-02-29_12-34-56"
+        );
+        // NOTE: 2100 is not a leap year, so February 28th is followed by March 1st
+        assert_eq!(
+            timestamp_from_unix_seconds(4_107_542_399),
+            "2100-02-28_23-59-59"
+        );
+        assert_eq!(
+            timestamp_from_unix_seconds(4_107_542_400),
+            "2100-03-01_00-00-00"
+        );
+    }
+
+    fn parse(args: &[&str]) -> std::result::Result<Options, String> {
+        let args: Vec<String> = std::iter::once("launcher")
+            .chain(args.iter().cloned())
+            .map(|arg| arg.to_owned())
+            .collect();
+        match parse_commandline(&args)? {
+            CommandlineAction::Run(options) => Ok(*options),
+            CommandlineAction::ShowUsage => Err("usage".to_owned()),
+        }
+    }
+
+    #[test]
+    fn plain_arguments_are_image_paths() {
+        let options = parse(&["a.png", "C:\\images\\b.gif"]).unwrap();
+        assert_eq!(options.image_filepaths, vec!["a.png", "C:\\images\\b.gif"]);
+        assert_eq!(options.tile_size, TILE_SIZE_DEFAULT);
+
+        let options = parse(&["a.png", "--", "--b.png"]).unwrap();
+        assert_eq!(options.image_filepaths, vec!["a.png", "--b.png"]);
+
+        assert!(matches!(
+            parse_commandline(&["launcher".to_owned(), "--help".to_owned()]),
+            Ok(CommandlineAction::ShowUsage)
+        ));
+    }
+
+    #[test]
+    fn option_values_can_be_inline_or_separate() {
+        let options = parse(&["--tile-size=20", "a.png"]).unwrap();
+        assert_eq!(options.tile_size, 20);
+        let options = parse(&["--tile-size", "24", "a.png"]).unwrap();
+        assert_eq!(options.tile_size, 24);
+        assert_eq!(options.image_filepaths, vec!["a.png"]);
+
+        assert!(parse(&["--tile-size=1", "a.png"]).is_err());
+        assert!(parse(&["--tile-size=big", "a.png"]).is_err());
+    }
+
+    #[test]
+    fn unknown_options_and_missing_values_are_errors() {
+        let error = parse(&["--frobnicate=1", "a.png"]).err().unwrap();
+        assert!(error.contains("--frobnicate"));
+
+        let error = parse(&["a.png", "--tile-size"]).err().unwrap();
+        assert!(error.contains("Missing value"));
+    }
+
+    #[test]
+    fn outputs_are_parsed_as_list() {
+        let options = parse(&["--outputs=centered, preview", "a.png"]).unwrap();
+        assert!(!options.pattern.create_patterns);
+        assert!(options.pattern.create_patterns_centered);
+        assert!(options.pattern.create_preview);
+
+        assert!(parse(&["--outputs=plain,fancy", "a.png"]).is_err());
+    }
+
+    #[test]
+    fn segment_overlap_must_be_smaller_than_segments() {
+        let options = parse(&[
+            "--segment-width=10",
+            "--segment-height=20",
+            "--segment-overlap=9",
+            "a.png",
+        ])
+        .unwrap();
+        assert_eq!(options.pattern.segment_overlap, 9);
+
+        assert!(parse(&["--segment-width=10", "--segment-overlap=10", "a.png"]).is_err());
+        assert!(parse(&["--segment-height=10", "--segment-overlap=12", "a.png"]).is_err());
+    }
+
+    const RED: PixelRGBA = PixelRGBA::new(255, 0, 0, 255);
+    const GREEN: PixelRGBA = PixelRGBA::new(0, 255, 0, 255);
+    const BLUE: PixelRGBA = PixelRGBA::new(0, 0, 255, 255);
+
+    /// Palette indices of the test GIFs
+    const INDEX_RED: u8 = 0;
+    const INDEX_GREEN: u8 = 1;
+    const INDEX_BLUE: u8 = 2;
+    const INDEX_TRANSPARENT: u8 = 3;
+
+    fn gif_frame(
+        left: u16,
+        top: u16,
+        width: u16,
+        height: u16,
+        dispose: gif::DisposalMethod,
+        pixels: Vec<u8>,
+    ) -> gif::Frame<'static> {
+        gif::Frame {
+            left,
+            top,
+            width,
+            height,
+            dispose,
+            transparent: Some(INDEX_TRANSPARENT),
+            buffer: std::borrow::Cow::Owned(pixels),
+            ..gif::Frame::default()
+        }
+    }
+
+    /// Encodes a 4x4 GIF from the given frames
+    fn gif_data(frames: &[gif::Frame]) -> Vec<u8> {
+        let palette = [255, 0, 0, 0, 255, 0, 0, 0, 255, 0, 0, 0];
+        let mut data = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, 4, 4, &palette).unwrap();
+            for frame in frames {
+                encoder.write_frame(frame).unwrap();
+            }
+        }
+        data
+    }
+
+    #[test]
+    fn gif_frames_are_composited_by_offset_and_disposal() {
+        let data = gif_data(&[
+            gif_frame(0, 0, 4, 4, gif::DisposalMethod::Keep, vec![INDEX_RED; 16]),
+            gif_frame(
+                1,
+                1,
+                2,
+                2,
+                gif::DisposalMethod::Background,
+                vec![INDEX_GREEN; 4],
+            ),
+            gif_frame(0, 0, 1, 1, gif::DisposalMethod::Previous, vec![INDEX_BLUE]),
+            gif_frame(
+                2,
+                3,
+                2,
+                1,
+                gif::DisposalMethod::Keep,
+                vec![INDEX_TRANSPARENT, INDEX_GREEN],
+            ),
+        ]);
+        let frames = bitmaps_create_from_gif_data(&data).unwrap();
+        assert_eq!(frames.len(), 4);
+
+        // Keep: the first frame covers the whole screen
+        assert!(frames[0].data.iter().all(|&pixel| pixel == RED));
+
+        // The second frame is drawn at its offset on top of the first one
+        assert_eq!(frames[1].get(0, 0), RED);
+        assert_eq!(frames[1].get(1, 1), GREEN);
+        assert_eq!(frames[1].get(2, 2), GREEN);
+        assert_eq!(frames[1].get(3, 3), RED);
+
+        // Background: the area of the second frame was cleared before the third frame
+        assert_eq!(frames[2].get(0, 0), BLUE);
+        assert_eq!(frames[2].get(1, 1), PixelRGBA::transparent());
+        assert_eq!(frames[2].get(2, 2), PixelRGBA::transparent());
+        assert_eq!(frames[2].get(3, 0), RED);
+
+        // Previous: the third frame was undone before the fourth frame. Its transparent pixel lets
+        // the previous frames shine through.
+        assert_eq!(frames[3].get(0, 0), RED);
+        assert_eq!(frames[3].get(1, 1), PixelRGBA::transparent());
+        assert_eq!(frames[3].get(2, 3), RED);
+        assert_eq!(frames[3].get(3, 3), GREEN);
+    }
+
+    #[test]
+    fn gif_with_implausible_screen_size_is_rejected() {
+        let palette = [0, 0, 0, 255, 255, 255];
+        let mut data = Vec::new();
+        {
+            let mut encoder = gif::Encoder::new(&mut data, 65535, 65535, &palette).unwrap();
+            encoder
+                .write_frame(&gif::Frame {
+                    width: 1,
+                    height: 1,
+                    buffer: std::borrow::Cow::Owned(vec![0]),
+                    ..gif::Frame::default()
+                })
+                .unwrap();
+        }
+        let error = bitmaps_create_from_gif_data(&data).err().unwrap();
+        assert!(error.contains("too big"));
+    }
+}
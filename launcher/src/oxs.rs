@@ -0,0 +1,725 @@
+use crate::backstitch::Backstitch;
+use crate::color_lab::ColorLab;
+use crate::error::{Error, Result};
+use crate::floss::Floss;
+use crate::stitch_kinds::{Corner, StitchKind, StitchKindMap};
+use crate::thread_usage::ThreadUsageSettings;
+use crate::xml::{xml_escape, xml_unescape};
+use crate::ColorInfo;
+
+use cottontail::core::*;
+use cottontail::image::bitmap::*;
+use cottontail::math::*;
+use indexmap::IndexMap;
+
+use std::collections::HashMap;
+
+const SOFTWARE_NAME: &str = "Pixie Stitch";
+
+/// The `direction` of a quarter `<partstitch>` for each corner of a cell
+const PARTSTITCH_DIRECTIONS: [(Corner, i32); 4] = [
+    (Corner::TopLeft, 1),
+    (Corner::TopRight, 2),
+    (Corner::BottomRight, 3),
+    (Corner::BottomLeft, 4),
+];
+
+fn color_to_oxs_string(color: PixelRGBA) -> String {
+    format!("{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+fn color_from_oxs_string(text: &str) -> Option<PixelRGBA> {
+    let text = text.trim().trim_start_matches('#');
+    if text.len() != 6 {
+        return None;
+    }
+    let value = u32::from_str_radix(text, 16).ok()?;
+    Some(PixelRGBA::new(
+        (value >> 16) as u8,
+        (value >> 8) as u8,
+        value as u8,
+        255,
+    ))
+}
+
+/// NOTE: Symbols past `ALPHANUMERIC_SYMBOL_CHARS` are written as two or more characters, just like
+///       the paint-by-numbers labels
+fn symbol_index_to_text(symbol_index: usize) -> String {
+    crate::alphanumeric_symbol_label(symbol_index)
+}
+
+fn symbol_index_from_text(symbol_text: &str) -> Option<usize> {
+    crate::alphanumeric_symbol_label_to_index(symbol_text.trim())
+}
+
+/// Creates an Open Cross Stitch (`.oxs`) document for the given image that can be opened by many
+/// other cross stitch applications. Transparent pixels are left unstitched.
+///
+/// NOTE: The symbols of our patterns are images and can't be represented in `.oxs`, so we write
+///       characters for the symbol index of a color instead. This way an imported pattern gets
+///       the same symbols again.
+///       Fractional stitches are written as one quarter `<partstitch>` per leg which lets us
+///       express all of them. Backstitch colors that no cell uses get their own palette entries.
+pub fn create_oxs(
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    backstitches: &[Backstitch],
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    title: &str,
+    thread_usage_settings: &ThreadUsageSettings,
+    fabric_color: PixelRGBA,
+) -> String {
+    let fabric_count = thread_usage_settings.fabric_count;
+    let strand_count = thread_usage_settings.strand_count;
+
+    let mut backstitch_only_colors: Vec<PixelRGBA> = Vec::new();
+    for backstitch in backstitches {
+        if !color_mappings.contains_key(&backstitch.color)
+            && !backstitch_only_colors.contains(&backstitch.color)
+        {
+            backstitch_only_colors.push(backstitch.color);
+        }
+    }
+    let palette_index_of = |color: PixelRGBA| {
+        // NOTE: We can unwrap here because the palette contains all colors of the image and the
+        //       backstitches
+        color_mappings.get_index_of(&color).unwrap_or_else(|| {
+            color_mappings.len()
+                + backstitch_only_colors
+                    .iter()
+                    .position(|&other| other == color)
+                    .unwrap()
+        }) + 1
+    };
+
+    let mut result = String::new();
+    result += "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n";
+    result += "<chart>\n";
+    result += &format!(
+        "<format comments01=\"Created by {}\" />\n",
+        xml_escape(SOFTWARE_NAME)
+    );
+    result += &format!(
+        "<properties oxsversion=\"1.0\" software=\"{}\" chartheight=\"{}\" chartwidth=\"{}\" \
+         charttitle=\"{}\" author=\"\" copyright=\"\" instructions=\"\" \
+         stitchesperinch=\"{}\" stitchesperinch_y=\"{}\" palettecount=\"{}\" />\n",
+        xml_escape(SOFTWARE_NAME),
+        image.height,
+        image.width,
+        xml_escape(title),
+        fabric_count,
+        fabric_count,
+        color_mappings.len() + backstitch_only_colors.len(),
+    );
+
+    // NOTE: The palette index 0 is reserved for the fabric
+    result += "<palette>\n";
+    result += &format!(
+        "<palette_item index=\"0\" number=\"cloth\" name=\"cloth\" color=\"{}\" \
+         printcolor=\"{}\" blendcolor=\"nil\" comments=\"aida\" strands=\"{}\" symbol=\"0\" \
+         dashpattern=\"\" bsstrands=\"{}\" bscolor=\"{}\" />\n",
+        color_to_oxs_string(fabric_color),
+        color_to_oxs_string(fabric_color),
+        strand_count,
+        strand_count,
+        color_to_oxs_string(fabric_color),
+    );
+    for info in color_mappings.values() {
+        let (number, name) = if let Some(floss) = &info.floss {
+            (
+                format!("{} {}", floss.catalog_name, floss.number),
+                floss.name.clone(),
+            )
+        } else {
+            (String::new(), String::new())
+        };
+        let symbol = symbol_index_to_text(info.symbol_index);
+        result += &palette_item_string(
+            palette_index_of(info.color),
+            &number,
+            &name,
+            info.color,
+            &symbol,
+            strand_count,
+        );
+    }
+    for &color in &backstitch_only_colors {
+        result += &palette_item_string(palette_index_of(color), "", "", color, "", strand_count);
+    }
+    result += "</palette>\n";
+
+    result += "<fullstitches>\n";
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let color = image.get(x, y);
+            if color.a == 0 || stitch_kinds.get(x, y).is_fractional() {
+                continue;
+            }
+            result += &format!(
+                "<stitch x=\"{}\" y=\"{}\" palindex=\"{}\" />\n",
+                x,
+                y,
+                palette_index_of(color)
+            );
+        }
+    }
+    result += "</fullstitches>\n";
+
+    result += "<partstitches>\n";
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let color = image.get(x, y);
+            let stitch_kind = stitch_kinds.get(x, y);
+            if color.a == 0 || !stitch_kind.is_fractional() {
+                continue;
+            }
+            let palette_index = palette_index_of(color);
+            for (corner, direction) in PARTSTITCH_DIRECTIONS.iter() {
+                if stitch_kind.has_leg(*corner) {
+                    result += &format!(
+                        "<partstitch x=\"{}\" y=\"{}\" palindex1=\"{}\" palindex2=\"0\" \
+                         direction=\"{}\" />\n",
+                        x, y, palette_index, direction
+                    );
+                }
+            }
+        }
+    }
+    result += "</partstitches>\n";
+    result += "<backstitches>\n";
+    for (sequence, backstitch) in backstitches.iter().enumerate() {
+        result += &format!(
+            "<backstitch x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" palindex=\"{}\" \
+             objecttype=\"backstitch\" sequence=\"{}\" />\n",
+            backstitch.start.x,
+            backstitch.start.y,
+            backstitch.end.x,
+            backstitch.end.y,
+            palette_index_of(backstitch.color),
+            sequence,
+        );
+    }
+    result += "</backstitches>\n";
+    result += "<ornaments_inc_knots_and_beads>\n</ornaments_inc_knots_and_beads>\n";
+    result += "<commentboxes>\n</commentboxes>\n";
+    result += "</chart>\n";
+
+    result
+}
+
+fn palette_item_string(
+    palette_index: usize,
+    number: &str,
+    name: &str,
+    color: PixelRGBA,
+    symbol: &str,
+    strand_count: i32,
+) -> String {
+    let color = color_to_oxs_string(color);
+    format!(
+        "<palette_item index=\"{}\" number=\"{}\" name=\"{}\" color=\"{}\" \
+         printcolor=\"{}\" blendcolor=\"nil\" comments=\"\" strands=\"{}\" symbol=\"{}\" \
+         dashpattern=\"\" bsstrands=\"{}\" bscolor=\"{}\" />\n",
+        palette_index,
+        xml_escape(number),
+        xml_escape(name),
+        color,
+        color,
+        strand_count,
+        xml_escape(symbol),
+        strand_count,
+        color,
+    )
+}
+
+pub fn write_oxs_file(
+    output_filepath: &str,
+    image: &Bitmap,
+    stitch_kinds: &StitchKindMap,
+    backstitches: &[Backstitch],
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    thread_usage_settings: &ThreadUsageSettings,
+    fabric_color: PixelRGBA,
+) -> Result<()> {
+    let title = path_to_filename_without_extension(output_filepath);
+    let content = create_oxs(
+        image,
+        stitch_kinds,
+        backstitches,
+        color_mappings,
+        &title,
+        thread_usage_settings,
+        fabric_color,
+    );
+    std::fs::write(output_filepath, &content).map_err(|error| Error::io(output_filepath, error))
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Reading
+
+struct XmlTag {
+    name: String,
+    attributes: HashMap<String, String>,
+}
+
+impl XmlTag {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|value| value.as_str())
+    }
+
+    fn attribute_i32(&self, name: &str) -> std::result::Result<i32, String> {
+        let value = self
+            .attribute(name)
+            .ok_or_else(|| format!("Missing attribute '{}' in <{}>", name, self.name))?;
+        value.trim().parse::<i32>().map_err(|_| {
+            format!(
+                "Invalid value '{}' of attribute '{}' in <{}>",
+                value, name, self.name
+            )
+        })
+    }
+
+    fn attribute_f64(&self, name: &str) -> std::result::Result<f64, String> {
+        let value = self
+            .attribute(name)
+            .ok_or_else(|| format!("Missing attribute '{}' in <{}>", name, self.name))?;
+        value
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|value| value.is_finite())
+            .ok_or_else(|| {
+                format!(
+                    "Invalid value '{}' of attribute '{}' in <{}>",
+                    value, name, self.name
+                )
+            })
+    }
+}
+
+/// Returns all opening and self-closing tags of the given XML document in order. This is just
+/// enough XML to read `.oxs` files which only store their data in attributes.
+fn xml_collect_tags(content: &str) -> Vec<XmlTag> {
+    let mut result = Vec::new();
+    let mut remaining = content;
+    while let Some(tag_start) = remaining.find('<') {
+        remaining = &remaining[tag_start + 1..];
+        let tag_end = match remaining.find('>') {
+            Some(tag_end) => tag_end,
+            None => break,
+        };
+        let tag_content = &remaining[..tag_end];
+        remaining = &remaining[tag_end + 1..];
+
+        if tag_content.starts_with('/')
+            || tag_content.starts_with('?')
+            || tag_content.starts_with('!')
+        {
+            // Closing tags, declarations and comments
+            continue;
+        }
+
+        let tag_content = tag_content.trim_end_matches('/').trim();
+        let name_end = tag_content
+            .find(char::is_whitespace)
+            .unwrap_or(tag_content.len());
+        let name = tag_content[..name_end].to_lowercase();
+
+        let mut attributes = HashMap::new();
+        let mut attributes_text = &tag_content[name_end..];
+        while let Some(equals_pos) = attributes_text.find('=') {
+            let attribute_name = attributes_text[..equals_pos].trim().to_lowercase();
+            let value_text = attributes_text[equals_pos + 1..].trim_start();
+            let quote = match value_text.chars().next() {
+                Some(quote) if quote == '"' || quote == '\'' => quote,
+                _ => break,
+            };
+            let value_text = &value_text[1..];
+            let value_end = match value_text.find(quote) {
+                Some(value_end) => value_end,
+                None => break,
+            };
+            attributes.insert(attribute_name, xml_unescape(&value_text[..value_end]));
+            attributes_text = &value_text[value_end + 1..];
+        }
+
+        result.push(XmlTag { name, attributes });
+    }
+    result
+}
+
+/// Everything besides the stitch grid that we read from an `.oxs` file
+pub struct OxsPattern {
+    pub stitch_kinds: StitchKindMap,
+    pub backstitches: Vec<Backstitch>,
+    pub color_mappings: IndexMap<PixelRGBA, ColorInfo>,
+}
+
+/// Loads the stitch grid, fractional stitches, backstitches and palette of an `.oxs` file. The
+/// returned color mappings only contain the color, stitch count, symbol index, label index and
+/// floss of every color. Unstitched cells become transparent.
+pub fn load_oxs_data(data: &[u8]) -> std::result::Result<(Bitmap, OxsPattern), String> {
+    let content = String::from_utf8_lossy(data);
+    let tags = xml_collect_tags(&content);
+
+    let properties = tags
+        .iter()
+        .find(|tag| tag.name == "properties")
+        .ok_or_else(|| "Missing <properties>".to_owned())?;
+    let width = properties.attribute_i32("chartwidth")?;
+    let height = properties.attribute_i32("chartheight")?;
+    if width <= 0 || height <= 0 {
+        return Err(format!("Invalid chart size {}x{}", width, height));
+    }
+    crate::image_formats::pixel_count_checked(width, height)?;
+
+    // Palette
+    let mut palette: HashMap<i32, PixelRGBA> = HashMap::new();
+    let mut color_mappings: IndexMap<PixelRGBA, ColorInfo> = IndexMap::new();
+    for tag in tags.iter().filter(|tag| tag.name == "palette_item") {
+        let palette_index = tag.attribute_i32("index")?;
+        let number = tag.attribute("number").unwrap_or("").trim();
+        if palette_index == 0 || number == "cloth" {
+            // NOTE: This is the fabric which we treat as unstitched
+            continue;
+        }
+        let color_text = tag.attribute("color").unwrap_or("");
+        let color = color_from_oxs_string(color_text).ok_or_else(|| {
+            format!(
+                "Invalid color '{}' of palette entry {}",
+                color_text, palette_index
+            )
+        })?;
+        palette.insert(palette_index, color);
+
+        // NOTE: Multiple palette entries can have the same color in which case we merge them
+        color_mappings.entry(color).or_insert_with(|| {
+            let floss = if number.is_empty() {
+                None
+            } else {
+                let (catalog_name, floss_number) = match number.rfind(' ') {
+                    Some(split_pos) => (&number[..split_pos], number[split_pos + 1..].trim()),
+                    None => ("", number),
+                };
+                Some(Floss {
+                    catalog_name: catalog_name.to_owned(),
+                    number: floss_number.to_owned(),
+                    name: tag.attribute("name").unwrap_or("").trim().to_owned(),
+                    color,
+                    color_lab: ColorLab::from_pixelrgba(color),
+                })
+            };
+            ColorInfo {
+                color,
+                count: 0,
+                count_half: 0,
+                count_quarter: 0,
+                count_three_quarter: 0,
+                count_completed: 0,
+                symbol_index: tag
+                    .attribute("symbol")
+                    .and_then(symbol_index_from_text)
+                    .unwrap_or(usize::MAX),
+                label_index: 0,
+                symbol: Bitmap::new_empty(),
+                symbol_alphanum: Bitmap::new_empty(),
+                stitches_premultiplied: Vec::new(),
+                floss,
+            }
+        });
+    }
+
+    let check_inside_chart = |x: i32, y: i32| {
+        if x < 0 || x >= width || y < 0 || y >= height {
+            Err(format!(
+                "Stitch at {}x{} is outside of the {}x{} chart",
+                x, y, width, height
+            ))
+        } else {
+            Ok(())
+        }
+    };
+
+    // Stitches
+    let mut image = Bitmap::new_filled(width as u32, height as u32, PixelRGBA::transparent());
+    let mut stitch_kinds = StitchKindMap::new_full(width, height);
+    for tag in tags.iter().filter(|tag| tag.name == "stitch") {
+        let x = tag.attribute_i32("x")?;
+        let y = tag.attribute_i32("y")?;
+        let palette_index = tag.attribute_i32("palindex")?;
+        check_inside_chart(x, y)?;
+        if let Some(color) = palette.get(&palette_index) {
+            image.set(x, y, *color);
+        }
+    }
+
+    // NOTE: We collect the legs of the part stitches per cell first as a fractional stitch can
+    //       consist of multiple part stitches. Cells with a full stitch stay full.
+    let mut part_stitch_legs: IndexMap<(i32, i32), Vec<Corner>> = IndexMap::new();
+    for tag in tags.iter().filter(|tag| tag.name == "partstitch") {
+        let x = tag.attribute_i32("x")?;
+        let y = tag.attribute_i32("y")?;
+        let direction = tag.attribute_i32("direction")?;
+        check_inside_chart(x, y)?;
+        if image.get(x, y).a != 0 && !part_stitch_legs.contains_key(&(x, y)) {
+            continue;
+        }
+        let corner = PARTSTITCH_DIRECTIONS
+            .iter()
+            .find(|(_corner, corner_direction)| *corner_direction == direction)
+            .map(|(corner, _corner_direction)| *corner)
+            .ok_or_else(|| {
+                format!(
+                    "Unsupported direction {} of part stitch at {}x{}",
+                    direction, x, y
+                )
+            })?;
+        // NOTE: We only have one color per cell, so the color of the last part stitch wins
+        let palette_indices = [
+            tag.attribute_i32("palindex1")?,
+            tag.attribute_i32("palindex2").unwrap_or(0),
+        ];
+        let color = palette_indices
+            .iter()
+            .find_map(|palette_index| palette.get(palette_index));
+        if let Some(color) = color {
+            image.set(x, y, *color);
+            part_stitch_legs.entry((x, y)).or_default().push(corner);
+        }
+    }
+    for ((x, y), legs) in &part_stitch_legs {
+        stitch_kinds.set(*x, *y, StitchKind::from_legs(legs));
+    }
+
+    // NOTE: Some applications allow backstitches that end in the middle of a cell. We only have
+    //       the cell corners, so we move them to the closest one.
+    let mut backstitches = Vec::new();
+    for tag in tags.iter().filter(|tag| tag.name == "backstitch") {
+        if let Some(object_type) = tag.attribute("objecttype") {
+            if object_type.trim() != "backstitch" {
+                continue;
+            }
+        }
+        let read_point = |name_x: &str, name_y: &str| {
+            let x = tag.attribute_f64(name_x)?.round();
+            let y = tag.attribute_f64(name_y)?.round();
+            if x < 0.0 || x > width as f64 || y < 0.0 || y > height as f64 {
+                return Err(format!(
+                    "Backstitch point {}x{} is outside of the {}x{} chart",
+                    x, y, width, height
+                ));
+            }
+            Ok(Vec2i::new(x as i32, y as i32))
+        };
+        let start = read_point("x1", "y1")?;
+        let end = read_point("x2", "y2")?;
+        let palette_index = tag.attribute_i32("palindex")?;
+        if let Some(color) = palette.get(&palette_index) {
+            if start != end {
+                backstitches.push(Backstitch {
+                    start,
+                    end,
+                    color: *color,
+                });
+            }
+        }
+    }
+
+    // NOTE: We recount the stitches as stitches can overwrite each other
+    for pixel in &image.data {
+        if pixel.a != 0 {
+            // NOTE: We can unwrap here because all stitched colors come from our palette
+            color_mappings.get_mut(pixel).unwrap().count += 1;
+        }
+    }
+    color_mappings.retain(|_color, info| info.count > 0);
+
+    // NOTE: We write the palette in legend order, so this keeps the paint-by-numbers labels
+    for (legend_index, info) in color_mappings.values_mut().enumerate() {
+        info.label_index = legend_index;
+    }
+
+    Ok((
+        image,
+        OxsPattern {
+            stitch_kinds,
+            backstitches,
+            color_mappings,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: PixelRGBA = PixelRGBA::new(200, 30, 40, 255);
+    const BLUE: PixelRGBA = PixelRGBA::new(20, 40, 180, 255);
+    const GREEN: PixelRGBA = PixelRGBA::new(10, 150, 20, 255);
+
+    fn color_info(color: PixelRGBA, symbol_index: usize, floss: Option<Floss>) -> ColorInfo {
+        ColorInfo {
+            color,
+            count: 0,
+            count_half: 0,
+            count_quarter: 0,
+            count_three_quarter: 0,
+            count_completed: 0,
+            symbol_index,
+            label_index: 0,
+            symbol: Bitmap::new_empty(),
+            symbol_alphanum: Bitmap::new_empty(),
+            stitches_premultiplied: Vec::new(),
+            floss,
+        }
+    }
+
+    #[test]
+    fn export_and_import_round_trip() {
+        let transparent = PixelRGBA::transparent();
+        let image = Bitmap::new_from_buffer(3, 2, vec![RED, BLUE, transparent, BLUE, RED, RED]);
+        let mut stitch_kinds = StitchKindMap::new_full(3, 2);
+        stitch_kinds.set(1, 0, StitchKind::ThreeQuarter(Corner::BottomLeft));
+        stitch_kinds.set(0, 1, StitchKind::Quarter(Corner::TopRight));
+        stitch_kinds.set(2, 1, StitchKind::HalfRising);
+        let backstitches = vec![
+            Backstitch {
+                start: Vec2i::new(0, 0),
+                end: Vec2i::new(1, 1),
+                color: GREEN,
+            },
+            Backstitch {
+                start: Vec2i::new(3, 2),
+                end: Vec2i::new(3, 1),
+                color: RED,
+            },
+        ];
+
+        let mut color_mappings = IndexMap::new();
+        color_mappings.insert(
+            RED,
+            color_info(
+                RED,
+                3,
+                Some(Floss {
+                    catalog_name: "DMC".to_owned(),
+                    number: "321".to_owned(),
+                    name: "Red & \"Christmas\" <bright>".to_owned(),
+                    color: RED,
+                    color_lab: ColorLab::from_pixelrgba(RED),
+                }),
+            ),
+        );
+        // NOTE: This symbol index is past the single character symbols
+        color_mappings.insert(BLUE, color_info(BLUE, 40, None));
+
+        let oxs = create_oxs(
+            &image,
+            &stitch_kinds,
+            &backstitches,
+            &color_mappings,
+            "Test <pattern>",
+            &ThreadUsageSettings::default(),
+            PixelRGBA::white(),
+        );
+        let (loaded_image, loaded) = load_oxs_data(oxs.as_bytes()).unwrap();
+
+        assert_eq!((loaded_image.width, loaded_image.height), (3, 2));
+        assert_eq!(loaded_image.data, image.data);
+        for y in 0..2 {
+            for x in 0..3 {
+                if image.get(x, y).a != 0 {
+                    assert_eq!(loaded.stitch_kinds.get(x, y), stitch_kinds.get(x, y));
+                }
+            }
+        }
+        assert_eq!(loaded.backstitches, backstitches);
+
+        assert_eq!(loaded.color_mappings.len(), 2);
+        let red_info = &loaded.color_mappings[&RED];
+        assert_eq!(red_info.count, 3);
+        assert_eq!(red_info.symbol_index, 3);
+        let red_floss = red_info.floss.as_ref().unwrap();
+        assert_eq!(red_floss.catalog_name, "DMC");
+        assert_eq!(red_floss.number, "321");
+        assert_eq!(red_floss.name, "Red & \"Christmas\" <bright>");
+
+        let blue_info = &loaded.color_mappings[&BLUE];
+        assert_eq!(blue_info.count, 2);
+        assert_eq!(blue_info.symbol_index, 40);
+        assert!(blue_info.floss.is_none());
+    }
+
+    #[test]
+    fn import_merges_part_stitches_and_snaps_backstitches() {
+        let oxs = r##"<?xml version="1.0" encoding="UTF-8"?>
+<chart>
+<properties oxsversion="1.0" chartheight="2" chartwidth="2" palettecount="2" />
+<palette>
+<palette_item index="0" number="cloth" name="cloth" color="FFFFFF" />
+<palette_item index="1" number="DMC 310" name="Black" color="000000" symbol="A" />
+<palette_item index="2" number="" name="" color="#FF0000" symbol="" />
+</palette>
+<fullstitches>
+<stitch x="0" y="0" palindex="1" />
+<stitch x="1" y="1" palindex="0" />
+</fullstitches>
+<partstitches>
+<partstitch x="0" y="0" palindex1="2" palindex2="0" direction="1" />
+<partstitch x="1" y="0" palindex1="2" palindex2="0" direction="2" />
+<partstitch x="1" y="0" palindex1="2" palindex2="0" direction="4" />
+<partstitch x="0" y="1" palindex1="0" palindex2="1" direction="3" />
+</partstitches>
+<backstitches>
+<backstitch x1="0.1" y1="0" x2="1.9" y2="2.2" palindex="1" objecttype="backstitch" />
+<backstitch x1="0" y1="0" x2="1" y2="0" palindex="1" objecttype="daisy" />
+</backstitches>
+</chart>
+"##;
+        let (image, pattern) = load_oxs_data(oxs.as_bytes()).unwrap();
+        let black = PixelRGBA::new(0, 0, 0, 255);
+        let red = PixelRGBA::new(255, 0, 0, 255);
+
+        // NOTE: Part stitches don't replace full stitches and the fabric counts as unstitched
+        assert_eq!(image.get(0, 0), black);
+        assert_eq!(pattern.stitch_kinds.get(0, 0), StitchKind::Full);
+        assert_eq!(image.get(1, 1), PixelRGBA::transparent());
+
+        assert_eq!(image.get(1, 0), red);
+        assert_eq!(pattern.stitch_kinds.get(1, 0), StitchKind::HalfRising);
+        assert_eq!(image.get(0, 1), black);
+        assert_eq!(
+            pattern.stitch_kinds.get(0, 1),
+            StitchKind::Quarter(Corner::BottomRight)
+        );
+
+        assert_eq!(
+            pattern.backstitches,
+            vec![Backstitch {
+                start: Vec2i::new(0, 0),
+                end: Vec2i::new(2, 2),
+                color: black,
+            }]
+        );
+
+        assert_eq!(pattern.color_mappings[&black].count, 2);
+        assert_eq!(pattern.color_mappings[&black].symbol_index, 9);
+        assert_eq!(pattern.color_mappings[&red].count, 1);
+        assert_eq!(pattern.color_mappings[&red].symbol_index, usize::MAX);
+    }
+
+    #[test]
+    fn import_rejects_stitches_outside_of_the_chart() {
+        let oxs = r#"<chart>
+<properties chartheight="1" chartwidth="1" />
+<palette>
+<palette_item index="1" number="" name="" color="000000" />
+</palette>
+<fullstitches>
+<stitch x="1" y="0" palindex="1" />
+</fullstitches>
+</chart>
+"#;
+        assert!(load_oxs_data(oxs.as_bytes()).is_err());
+    }
+}
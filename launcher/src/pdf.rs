@@ -0,0 +1,263 @@
+use crate::error::{Error, Result};
+
+use cottontail::image::bitmap::*;
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use std::io::Write;
+
+/// Page margin of 10mm in PDF points (1/72 inch)
+const PAGE_MARGIN: f64 = 28.35;
+
+/// NOTE: We print with at least 96 pixels per inch so that small images like the legend of a
+///       pattern with few colors don't get blown up to the full page size
+const SCALE_MAX: f64 = 72.0 / 96.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PageSize {
+    A4,
+    Letter,
+}
+
+impl PageSize {
+    /// Returns the portrait page dimensions in PDF points (1/72 inch)
+    pub fn dimensions(self) -> (f64, f64) {
+        match self {
+            PageSize::A4 => (595.276, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+        }
+    }
+}
+
+/// Places the bitmap centered at the top of the page. The page is rotated to landscape if that
+/// allows a bigger bitmap. Returns the page dimensions and the bitmap rect in PDF points.
+fn page_layout_for_bitmap(bitmap: &Bitmap, page_size: PageSize) -> ((f64, f64), [f64; 4]) {
+    let (portrait_width, portrait_height) = page_size.dimensions();
+    let bitmap_width = bitmap.width as f64;
+    let bitmap_height = bitmap.height as f64;
+
+    let scale_for_page = |page_width: f64, page_height: f64| {
+        f64::min(
+            (page_width - 2.0 * PAGE_MARGIN) / bitmap_width,
+            (page_height - 2.0 * PAGE_MARGIN) / bitmap_height,
+        )
+    };
+    let scale_portrait = scale_for_page(portrait_width, portrait_height);
+    let scale_landscape = scale_for_page(portrait_height, portrait_width);
+    let (page_width, page_height, scale) = if scale_landscape > scale_portrait {
+        (portrait_height, portrait_width, scale_landscape)
+    } else {
+        (portrait_width, portrait_height, scale_portrait)
+    };
+    let scale = scale.min(SCALE_MAX);
+
+    let draw_width = scale * bitmap_width;
+    let draw_height = scale * bitmap_height;
+    let draw_x = (page_width - draw_width) / 2.0;
+    // NOTE: PDF coordinates are y-up with the origin in the bottom-left corner of the page
+    let draw_y = page_height - PAGE_MARGIN - draw_height;
+
+    (
+        (page_width, page_height),
+        [draw_x, draw_y, draw_width, draw_height],
+    )
+}
+
+/// Returns the bitmap as zlib compressed RGB data. Transparent regions are blended against white.
+fn bitmap_to_compressed_rgb(bitmap: &Bitmap) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+    let mut row = Vec::with_capacity(3 * bitmap.width as usize);
+    for y in 0..bitmap.height {
+        row.clear();
+        for x in 0..bitmap.width {
+            let color = bitmap.get(x, y);
+            let alpha = color.a as u32;
+            for &channel in [color.r, color.g, color.b].iter() {
+                row.push(((channel as u32 * alpha + 255 * (255 - alpha)) / 255) as u8);
+            }
+        }
+        // NOTE: We can unwrap here because writing to a Vec cannot fail
+        encoder.write_all(&row).unwrap();
+    }
+    encoder.finish().unwrap()
+}
+
+/// Creates a PDF document with one page per bitmap
+pub fn create_pdf(pages: &[&Bitmap], page_size: PageSize) -> Vec<u8> {
+    // NOTE: Object 1 is the catalog, object 2 the page tree and every page consists of three
+    //       objects: the page itself, its content stream and its image
+    let object_count = 2 + 3 * pages.len();
+    let page_object_number = |page_index: usize| 3 + 3 * page_index;
+
+    let mut output: Vec<u8> = Vec::new();
+    let mut object_offsets = vec![0; object_count];
+
+    output.extend_from_slice(b"%PDF-1.4\n%\xE2\xE3\xCF\xD3\n");
+
+    object_offsets[0] = output.len();
+    output.extend_from_slice(b"1 0 obj\n<< /Type /Catalog /Pages 2 0 R >>\nendobj\n");
+
+    object_offsets[1] = output.len();
+    let page_references: Vec<String> = (0..pages.len())
+        .map(|page_index| format!("{} 0 R", page_object_number(page_index)))
+        .collect();
+    output.extend_from_slice(
+        format!(
+            "2 0 obj\n<< /Type /Pages /Kids [{}] /Count {} >>\nendobj\n",
+            page_references.join(" "),
+            pages.len()
+        )
+        .as_bytes(),
+    );
+
+    for (page_index, bitmap) in pages.iter().enumerate() {
+        let page_number = page_object_number(page_index);
+        let content_number = page_number + 1;
+        let image_number = page_number + 2;
+
+        let ((page_width, page_height), [draw_x, draw_y, draw_width, draw_height]) =
+            page_layout_for_bitmap(bitmap, page_size);
+
+        object_offsets[page_number - 1] = output.len();
+        output.extend_from_slice(
+            format!(
+                "{} 0 obj\n<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.3} {:.3}] \
+                 /Resources << /XObject << /Im0 {} 0 R >> >> /Contents {} 0 R >>\nendobj\n",
+                page_number, page_width, page_height, image_number, content_number
+            )
+            .as_bytes(),
+        );
+
+        let content = format!(
+            "q\n{:.3} 0 0 {:.3} {:.3} {:.3} cm\n/Im0 Do\nQ\n",
+            draw_width, draw_height, draw_x, draw_y
+        );
+        object_offsets[content_number - 1] = output.len();
+        output.extend_from_slice(
+            format!(
+                "{} 0 obj\n<< /Length {} >>\nstream\n{}endstream\nendobj\n",
+                content_number,
+                content.len(),
+                content
+            )
+            .as_bytes(),
+        );
+
+        let image_data = bitmap_to_compressed_rgb(bitmap);
+        object_offsets[image_number - 1] = output.len();
+        output.extend_from_slice(
+            format!(
+                "{} 0 obj\n<< /Type /XObject /Subtype /Image /Width {} /Height {} \
+                 /ColorSpace /DeviceRGB /BitsPerComponent 8 /Interpolate false \
+                 /Filter /FlateDecode /Length {} >>\nstream\n",
+                image_number,
+                bitmap.width,
+                bitmap.height,
+                image_data.len()
+            )
+            .as_bytes(),
+        );
+        output.extend_from_slice(&image_data);
+        output.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    let xref_offset = output.len();
+    output.extend_from_slice(
+        format!("xref\n0 {}\n0000000000 65535 f \n", object_count + 1).as_bytes(),
+    );
+    for offset in &object_offsets {
+        output.extend_from_slice(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    output.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n",
+            object_count + 1,
+            xref_offset
+        )
+        .as_bytes(),
+    );
+
+    output
+}
+
+pub fn write_pdf_file(output_filepath: &str, pages: &[&Bitmap], page_size: PageSize) -> Result<()> {
+    let pdf_data = create_pdf(pages, page_size);
+    std::fs::write(output_filepath, &pdf_data).map_err(|error| Error::io(output_filepath, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn page_layout_rotates_wide_bitmaps_to_landscape() {
+        let wide = Bitmap::new_from_buffer(2000, 1000, vec![PixelRGBA::white(); 2000 * 1000]);
+        let ((page_width, page_height), [_, draw_y, draw_width, draw_height]) =
+            page_layout_for_bitmap(&wide, PageSize::A4);
+        assert_eq!((page_width, page_height), (841.89, 595.276));
+        assert!(draw_width <= page_width - 2.0 * PAGE_MARGIN);
+        assert!((draw_width / draw_height - 2.0).abs() < 1e-9);
+        assert!((draw_y + draw_height - (page_height - PAGE_MARGIN)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn page_layout_does_not_blow_up_small_bitmaps() {
+        let small = Bitmap::new_from_buffer(48, 96, vec![PixelRGBA::white(); 48 * 96]);
+        let ((page_width, _), [draw_x, _, draw_width, draw_height]) =
+            page_layout_for_bitmap(&small, PageSize::Letter);
+        assert_eq!(page_width, 612.0);
+        assert!((draw_width - 36.0).abs() < 1e-9);
+        assert!((draw_height - 72.0).abs() < 1e-9);
+        assert!((draw_x - (page_width - draw_width) / 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn compressed_rgb_blends_transparency_against_white() {
+        let bitmap = Bitmap::new_from_buffer(
+            2,
+            1,
+            vec![PixelRGBA::new(10, 20, 30, 255), PixelRGBA::new(0, 0, 0, 0)],
+        );
+        let mut decoded = Vec::new();
+        ZlibDecoder::new(&bitmap_to_compressed_rgb(&bitmap)[..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, vec![10, 20, 30, 255, 255, 255]);
+    }
+
+    #[test]
+    fn create_pdf_writes_one_page_per_bitmap_with_valid_xref() {
+        let page = Bitmap::new_from_buffer(4, 4, vec![PixelRGBA::black(); 16]);
+        let pdf = create_pdf(&[&page, &page], PageSize::A4);
+        let text = String::from_utf8_lossy(&pdf);
+
+        assert!(text.starts_with("%PDF-1.4"));
+        assert!(text.ends_with("%%EOF\n"));
+        assert!(text.contains("/Kids [3 0 R 6 0 R] /Count 2"));
+
+        // NOTE: Every xref entry must point at the start of its object
+        let xref_offset: usize = text
+            .rsplit("startxref\n")
+            .next()
+            .unwrap()
+            .lines()
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let xref = String::from_utf8(pdf[xref_offset..].to_vec()).unwrap();
+        assert!(xref.starts_with("xref\n0 9\n"));
+        for (object_index, entry) in xref.lines().skip(3).take(8).enumerate() {
+            let offset: usize = entry[..10].parse().unwrap();
+            let object_header = format!("{} 0 obj", object_index + 1);
+            assert_eq!(
+                &pdf[offset..offset + object_header.len()],
+                object_header.as_bytes()
+            );
+        }
+    }
+}
@@ -0,0 +1,388 @@
+use crate::backstitch::Backstitch;
+use crate::progress::ProgressMap;
+use crate::stitch_kinds::StitchKindMap;
+
+use cottontail::image::bitmap::*;
+use cottontail::math::*;
+
+use std::collections::HashSet;
+
+/// Describes pixel art that was upscaled by an integer factor, so that every pixel became a
+/// `factor`x`factor` block. The block grid starts at `offset`, which means that the blocks at the
+/// left and top border can be cut off.
+#[derive(Debug, Clone, Copy)]
+pub struct PixelScale {
+    pub factor: i32,
+    /// NOTE: Both components are always smaller than `factor`
+    pub offset: Vec2i,
+}
+
+/// Returns the sorted positions along one axis where a pixel differs from its predecessor in any
+/// of the images
+fn color_change_positions(images: &[&Bitmap], horizontal: bool) -> Vec<i32> {
+    let mut positions = HashSet::new();
+    for image in images {
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (previous_x, previous_y, position) = if horizontal {
+                    (x - 1, y, x)
+                } else {
+                    (x, y - 1, y)
+                };
+                if previous_x >= 0
+                    && previous_y >= 0
+                    && image.get(x, y) != image.get(previous_x, previous_y)
+                {
+                    positions.insert(position);
+                }
+            }
+        }
+    }
+    let mut positions: Vec<i32> = positions.into_iter().collect();
+    positions.sort();
+    positions
+}
+
+fn greatest_common_divisor(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a
+    } else {
+        greatest_common_divisor(b, a % b)
+    }
+}
+
+/// Returns the block size along one axis or `None` if there are too few color changes to tell
+fn block_size_from_color_changes(change_positions: &[i32]) -> Option<i32> {
+    if change_positions.len() < 2 {
+        return None;
+    }
+    Some(change_positions.windows(2).fold(0, |acc, pair| {
+        greatest_common_divisor(acc, pair[1] - pair[0])
+    }))
+}
+
+/// Checks whether the images (i.e. all frames of an animation) are pixel art that was upscaled by
+/// an integer factor of at least 2. All images are expected to have the same size.
+///
+/// NOTE: Pixel art where every feature happens to be two or more pixels wide is indistinguishable
+///       from upscaled pixel art, so the caller should offer a way to override the result
+pub fn pixel_scale_detect(images: &[&Bitmap]) -> Option<PixelScale> {
+    let columns = color_change_positions(images, true);
+    let rows = color_change_positions(images, false);
+    let factor = match (
+        block_size_from_color_changes(&columns),
+        block_size_from_color_changes(&rows),
+    ) {
+        (Some(factor_x), Some(factor_y)) => greatest_common_divisor(factor_x, factor_y),
+        (Some(factor_x), None) => factor_x,
+        (None, Some(factor_y)) => factor_y,
+        (None, None) => return None,
+    };
+    if factor < 2 {
+        return None;
+    }
+    Some(pixel_scale_with_factor(images, factor))
+}
+
+/// Returns the pixel scale of the given factor with the block grid aligned to the first color
+/// changes of the images
+pub fn pixel_scale_with_factor(images: &[&Bitmap], factor: i32) -> PixelScale {
+    let offset_of = |change_positions: Vec<i32>| {
+        change_positions
+            .first()
+            .map(|position| position % factor)
+            .unwrap_or(0)
+    };
+    PixelScale {
+        factor,
+        offset: Vec2i::new(
+            offset_of(color_change_positions(images, true)),
+            offset_of(color_change_positions(images, false)),
+        ),
+    }
+}
+
+impl PixelScale {
+    /// Returns the size of the native image along one axis. Cut off blocks at the borders count
+    /// as whole pixels.
+    pub fn native_size(&self, upscaled_size: i32, offset: i32) -> i32 {
+        let leading_block_count = if offset > 0 { 1 } else { 0 };
+        leading_block_count + (upscaled_size - offset + self.factor - 1) / self.factor
+    }
+
+    /// Returns the upscaled position of a grid line (or pixel) of the native image along one axis
+    fn upscaled_position(&self, native_position: i32, offset: i32, upscaled_size: i32) -> i32 {
+        let leading_block_count = if offset > 0 { 1 } else { 0 };
+        if native_position < leading_block_count {
+            0
+        } else {
+            i32::min(
+                upscaled_size,
+                offset + (native_position - leading_block_count) * self.factor,
+            )
+        }
+    }
+
+    fn upscaled_point(
+        &self,
+        native_point: Vec2i,
+        upscaled_width: i32,
+        upscaled_height: i32,
+    ) -> Vec2i {
+        Vec2i::new(
+            self.upscaled_position(native_point.x, self.offset.x, upscaled_width),
+            self.upscaled_position(native_point.y, self.offset.y, upscaled_height),
+        )
+    }
+
+    /// Reduces the image to its native resolution by taking the top-left pixel of every block
+    pub fn downscale_image(&self, image: &Bitmap) -> Bitmap {
+        let width = self.native_size(image.width, self.offset.x);
+        let height = self.native_size(image.height, self.offset.y);
+        let mut result = Bitmap::new(width as u32, height as u32);
+        for y in 0..height {
+            for x in 0..width {
+                let pos = self.upscaled_point(Vec2i::new(x, y), image.width, image.height);
+                result.set(x, y, image.get(pos.x, pos.y));
+            }
+        }
+        result
+    }
+
+    /// Reduces the stitch kinds to the native resolution by taking the top-left cell of every
+    /// block
+    pub fn downscale_stitch_kinds(&self, stitch_kinds: &StitchKindMap) -> StitchKindMap {
+        let width = self.native_size(stitch_kinds.width, self.offset.x);
+        let height = self.native_size(stitch_kinds.height, self.offset.y);
+        let mut result = StitchKindMap::new_full(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let pos =
+                    self.upscaled_point(Vec2i::new(x, y), stitch_kinds.width, stitch_kinds.height);
+                result.set(x, y, stitch_kinds.get(pos.x, pos.y));
+            }
+        }
+        result
+    }
+
+    /// Reduces the progress map to the native resolution by taking the top-left cell of every
+    /// block
+    pub fn downscale_progress(&self, progress: &ProgressMap) -> ProgressMap {
+        let width = self.native_size(progress.width, self.offset.x);
+        let height = self.native_size(progress.height, self.offset.y);
+        let mut result = ProgressMap::new_empty(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                let pos = self.upscaled_point(Vec2i::new(x, y), progress.width, progress.height);
+                result.set_completed(x, y, progress.is_completed(pos.x, pos.y));
+            }
+        }
+        result
+    }
+
+    /// Reduces backstitches that were drawn at the upscaled resolution. A backstitch between two
+    /// neighbouring native grid points is kept if the whole upscaled line between them is
+    /// stitched in one color.
+    pub fn downscale_backstitches(
+        &self,
+        backstitches: &[Backstitch],
+        upscaled_width: i32,
+        upscaled_height: i32,
+    ) -> Vec<Backstitch> {
+        let upscaled_stitches: HashSet<Backstitch> = backstitches
+            .iter()
+            .map(|backstitch| backstitch.normalized())
+            .collect();
+        let mut colors: Vec<PixelRGBA> = Vec::new();
+        for backstitch in backstitches {
+            if !colors.contains(&backstitch.color) {
+                colors.push(backstitch.color);
+            }
+        }
+
+        let width = self.native_size(upscaled_width, self.offset.x);
+        let height = self.native_size(upscaled_height, self.offset.y);
+        let is_stitched_upscaled = |start: Vec2i, end: Vec2i, color: PixelRGBA| {
+            let delta_x = end.x - start.x;
+            let delta_y = end.y - start.y;
+            let step_count = i32::max(i32::abs(delta_x), i32::abs(delta_y));
+            // NOTE: Diagonals through cut off blocks at the borders are not square
+            if step_count == 0 || (delta_x != 0 && delta_y != 0 && delta_x.abs() != delta_y.abs()) {
+                return false;
+            }
+            let step = Vec2i::new(delta_x / step_count, delta_y / step_count);
+            (0..step_count).all(|step_index| {
+                let step_start =
+                    Vec2i::new(start.x + step_index * step.x, start.y + step_index * step.y);
+                let unit_stitch = Backstitch {
+                    start: step_start,
+                    end: step_start + step,
+                    color,
+                };
+                upscaled_stitches.contains(&unit_stitch.normalized())
+            })
+        };
+
+        let mut result = Vec::new();
+        for &color in &colors {
+            for y in 0..=height {
+                for x in 0..=width {
+                    let start = Vec2i::new(x, y);
+                    for neighbour in &[
+                        Vec2i::new(x + 1, y),
+                        Vec2i::new(x, y + 1),
+                        Vec2i::new(x + 1, y + 1),
+                        Vec2i::new(x - 1, y + 1),
+                    ] {
+                        if neighbour.x < 0 || neighbour.x > width || neighbour.y > height {
+                            continue;
+                        }
+                        let start_upscaled =
+                            self.upscaled_point(start, upscaled_width, upscaled_height);
+                        let end_upscaled =
+                            self.upscaled_point(*neighbour, upscaled_width, upscaled_height);
+                        if is_stitched_upscaled(start_upscaled, end_upscaled, color) {
+                            result.push(Backstitch {
+                                start,
+                                end: *neighbour,
+                                color,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns a color that differs from the colors of all four neighbours
+    fn native_color(x: i32, y: i32) -> PixelRGBA {
+        let value = ((x + 2 * y) % 5) as u8;
+        PixelRGBA::new(50 * value, 255 - 50 * value, 100, 255)
+    }
+
+    fn native_image(width: i32, height: i32) -> Bitmap {
+        let mut result = Bitmap::new(width as u32, height as u32);
+        for y in 0..height {
+            for x in 0..width {
+                result.set(x, y, native_color(x, y));
+            }
+        }
+        result
+    }
+
+    /// Upscales the native image with the block grid starting at `offset` and crops it to
+    /// `width`x`height`
+    fn upscaled_image(
+        native: &Bitmap,
+        factor: i32,
+        offset: Vec2i,
+        width: i32,
+        height: i32,
+    ) -> Bitmap {
+        let native_position = |position: i32, offset: i32| {
+            if offset == 0 {
+                position / factor
+            } else if position < offset {
+                0
+            } else {
+                1 + (position - offset) / factor
+            }
+        };
+        let mut result = Bitmap::new(width as u32, height as u32);
+        for y in 0..height {
+            for x in 0..width {
+                let color = native.get(native_position(x, offset.x), native_position(y, offset.y));
+                result.set(x, y, color);
+            }
+        }
+        result
+    }
+
+    #[test]
+    fn detects_factor_and_downscales_aligned_image() {
+        let native = native_image(4, 3);
+        let image = upscaled_image(&native, 2, Vec2i::zero(), 8, 6);
+
+        let pixel_scale = pixel_scale_detect(&[&image]).unwrap();
+        assert_eq!(pixel_scale.factor, 2);
+        assert_eq!(pixel_scale.offset, Vec2i::zero());
+        assert_eq!(pixel_scale.downscale_image(&image).data, native.data);
+    }
+
+    #[test]
+    fn detects_offset_of_cut_off_border_blocks() {
+        // NOTE: The leftmost column of blocks is cut to 1 pixel, the rightmost to 2 pixels and
+        //       the topmost row of blocks to 2 pixels
+        let native = native_image(4, 3);
+        let image = upscaled_image(&native, 3, Vec2i::new(1, 2), 9, 6);
+
+        let pixel_scale = pixel_scale_detect(&[&image]).unwrap();
+        assert_eq!(pixel_scale.factor, 3);
+        assert_eq!(pixel_scale.offset, Vec2i::new(1, 2));
+        assert_eq!(pixel_scale.native_size(9, 1), 4);
+        assert_eq!(pixel_scale.native_size(6, 2), 3);
+
+        let downscaled = pixel_scale.downscale_image(&image);
+        assert_eq!((downscaled.width, downscaled.height), (4, 3));
+        assert_eq!(downscaled.data, native.data);
+
+        let mut stitch_kinds = StitchKindMap::new_full(9, 6);
+        stitch_kinds.set(4, 2, crate::stitch_kinds::StitchKind::HalfRising);
+        let downscaled_stitch_kinds = pixel_scale.downscale_stitch_kinds(&stitch_kinds);
+        assert!(downscaled_stitch_kinds.get(2, 1).is_fractional());
+        assert!(!downscaled_stitch_kinds.get(1, 1).is_fractional());
+    }
+
+    #[test]
+    fn factor_is_shared_by_all_frames() {
+        let frame_coarse = upscaled_image(&native_image(6, 6), 4, Vec2i::zero(), 24, 24);
+        let frame_fine = upscaled_image(&native_image(12, 12), 2, Vec2i::zero(), 24, 24);
+
+        assert_eq!(pixel_scale_detect(&[&frame_coarse]).unwrap().factor, 4);
+        assert_eq!(
+            pixel_scale_detect(&[&frame_coarse, &frame_fine])
+                .unwrap()
+                .factor,
+            2
+        );
+    }
+
+    #[test]
+    fn native_pixel_art_is_not_detected() {
+        assert!(pixel_scale_detect(&[&native_image(8, 8)]).is_none());
+
+        let uniform = Bitmap::new_filled(8, 8, PixelRGBA::black());
+        assert!(pixel_scale_detect(&[&uniform]).is_none());
+    }
+
+    #[test]
+    fn downscales_only_backstitches_spanning_whole_blocks() {
+        let pixel_scale = PixelScale {
+            factor: 3,
+            offset: Vec2i::new(1, 2),
+        };
+        let color = PixelRGBA::black();
+        let unit_stitch = |start: (i32, i32), end: (i32, i32)| Backstitch {
+            start: Vec2i::new(start.0, start.1),
+            end: Vec2i::new(end.0, end.1),
+            color,
+        };
+        let upscaled_backstitches = vec![
+            // NOTE: A complete block edge that is drawn in reverse for one segment
+            unit_stitch((1, 2), (1, 3)),
+            unit_stitch((1, 4), (1, 3)),
+            unit_stitch((1, 4), (1, 5)),
+            // NOTE: Only a third of a block edge
+            unit_stitch((4, 2), (5, 2)),
+        ];
+
+        let downscaled = pixel_scale.downscale_backstitches(&upscaled_backstitches, 9, 6);
+        assert_eq!(downscaled, vec![unit_stitch((1, 1), (1, 2))]);
+    }
+}
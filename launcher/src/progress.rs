@@ -0,0 +1,53 @@
+use cottontail::image::bitmap::*;
+
+/// Name of the Aseprite layer and suffix of the image file (i.e. `cat_progress.png` for
+/// `cat.png`) that mark the already stitched cells of an image
+pub const PROGRESS_MASK_NAME: &str = "progress";
+
+/// Which cells of an image are already stitched
+#[derive(Debug, Clone)]
+pub struct ProgressMap {
+    pub width: i32,
+    pub height: i32,
+    completed: Vec<bool>,
+}
+
+impl ProgressMap {
+    pub fn new_empty(width: i32, height: i32) -> ProgressMap {
+        ProgressMap {
+            width,
+            height,
+            completed: vec![false; (width * height) as usize],
+        }
+    }
+
+    /// Every opaque pixel of the progress mask marks a completed cell, regardless of its color.
+    /// This way we can just paint over the cells we finished.
+    pub fn from_mask(mask: &Bitmap) -> ProgressMap {
+        ProgressMap {
+            width: mask.width,
+            height: mask.height,
+            completed: mask.data.iter().map(|pixel| pixel.a != 0).collect(),
+        }
+    }
+
+    pub fn is_completed(&self, x: i32, y: i32) -> bool {
+        self.completed[(x + y * self.width) as usize]
+    }
+
+    pub fn set_completed(&mut self, x: i32, y: i32, completed: bool) {
+        self.completed[(x + y * self.width) as usize] = completed;
+    }
+
+    /// Returns the `width`x`height` region that starts at the given position
+    pub fn sub_map(&self, pos_x: i32, pos_y: i32, width: i32, height: i32) -> ProgressMap {
+        let mut result = ProgressMap::new_empty(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                result.completed[(x + y * width) as usize] =
+                    self.is_completed(pos_x + x, pos_y + y);
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,231 @@
+use crate::color_quantization::color_to_hex_string;
+
+use cottontail::image::bitmap::*;
+
+/// Name of the Aseprite layer and suffix of the image file (i.e. `cat_fractional.png` for
+/// `cat.png`) that contain the fractional stitch mask of an image
+pub const FRACTIONAL_MASK_NAME: &str = "fractional";
+
+/// The colors we use in fractional stitch masks. Transparent, white and black pixels mark full
+/// stitches.
+pub const FRACTIONAL_MASK_COLORS: [(PixelRGBA, StitchKind); 10] = [
+    (PixelRGBA::new(255, 0, 0, 255), StitchKind::HalfRising),
+    (PixelRGBA::new(0, 0, 255, 255), StitchKind::HalfFalling),
+    (
+        PixelRGBA::new(255, 255, 0, 255),
+        StitchKind::Quarter(Corner::TopLeft),
+    ),
+    (
+        PixelRGBA::new(0, 255, 255, 255),
+        StitchKind::Quarter(Corner::TopRight),
+    ),
+    (
+        PixelRGBA::new(255, 0, 255, 255),
+        StitchKind::Quarter(Corner::BottomLeft),
+    ),
+    (
+        PixelRGBA::new(0, 255, 0, 255),
+        StitchKind::Quarter(Corner::BottomRight),
+    ),
+    (
+        PixelRGBA::new(128, 128, 0, 255),
+        StitchKind::ThreeQuarter(Corner::TopLeft),
+    ),
+    (
+        PixelRGBA::new(0, 128, 128, 255),
+        StitchKind::ThreeQuarter(Corner::TopRight),
+    ),
+    (
+        PixelRGBA::new(128, 0, 128, 255),
+        StitchKind::ThreeQuarter(Corner::BottomLeft),
+    ),
+    (
+        PixelRGBA::new(0, 128, 0, 255),
+        StitchKind::ThreeQuarter(Corner::BottomRight),
+    ),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Corner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl Corner {
+    pub const ALL: [Corner; 4] = [
+        Corner::TopLeft,
+        Corner::TopRight,
+        Corner::BottomLeft,
+        Corner::BottomRight,
+    ];
+
+    /// Returns the corner whose leg is closest to the given point. The point is given in doubled
+    /// pixel coordinates relative to the cell center so that it stays an integer.
+    fn of_leg_closest_to(double_x: i32, double_y: i32) -> Corner {
+        // NOTE: The `\` diagonal is where x == y and the `/` diagonal is where x == -y
+        if i32::abs(double_x - double_y) < i32::abs(double_x + double_y) {
+            if double_x + double_y > 0 {
+                Corner::BottomRight
+            } else {
+                Corner::TopLeft
+            }
+        } else if double_x - double_y > 0 {
+            Corner::TopRight
+        } else {
+            Corner::BottomLeft
+        }
+    }
+}
+
+/// A stitch consists of up to four legs that go from the center of its cell to the corners
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StitchKind {
+    Full,
+    /// Goes from the bottom-left to the top-right corner (`/`)
+    HalfRising,
+    /// Goes from the top-left to the bottom-right corner (`\`)
+    HalfFalling,
+    /// Goes from the given corner to the center
+    Quarter(Corner),
+    /// A half stitch plus a quarter stitch from the given corner to the center
+    ThreeQuarter(Corner),
+}
+
+impl StitchKind {
+    pub fn is_fractional(self) -> bool {
+        self != StitchKind::Full
+    }
+
+    /// Returns the stitch kind with the fewest legs that has all of the given legs
+    pub fn from_legs(legs: &[Corner]) -> StitchKind {
+        FRACTIONAL_MASK_COLORS
+            .iter()
+            .map(|(_mask_color, kind)| *kind)
+            .filter(|kind| legs.iter().all(|&corner| kind.has_leg(corner)))
+            .min_by_key(|kind| kind.leg_count())
+            .unwrap_or(StitchKind::Full)
+    }
+
+    pub fn leg_count(self) -> usize {
+        Corner::ALL
+            .iter()
+            .filter(|&&corner| self.has_leg(corner))
+            .count()
+    }
+
+    pub fn has_leg(self, corner: Corner) -> bool {
+        match self {
+            StitchKind::Full => true,
+            StitchKind::HalfRising => corner == Corner::BottomLeft || corner == Corner::TopRight,
+            StitchKind::HalfFalling => corner == Corner::TopLeft || corner == Corner::BottomRight,
+            StitchKind::Quarter(quarter_corner) => corner == quarter_corner,
+            StitchKind::ThreeQuarter(quarter_corner) => {
+                // NOTE: The half stitch is the diagonal that does not touch the quarter corner
+                let half = match quarter_corner {
+                    Corner::TopLeft | Corner::BottomRight => StitchKind::HalfRising,
+                    Corner::TopRight | Corner::BottomLeft => StitchKind::HalfFalling,
+                };
+                corner == quarter_corner || half.has_leg(corner)
+            }
+        }
+    }
+
+    /// Returns whether the given pixel of a `width`x`height` cell belongs to one of our legs. With
+    /// a `leg_thickness` only pixels roughly that close to the legs count, otherwise every pixel
+    /// counts that is closer to one of our legs than to any other leg.
+    pub fn covers_pixel(
+        self,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+        leg_thickness: Option<i32>,
+    ) -> bool {
+        let double_x = 2 * x - (width - 1);
+        let double_y = 2 * y - (height - 1);
+        let corner = Corner::of_leg_closest_to(double_x, double_y);
+        if !self.has_leg(corner) {
+            return false;
+        }
+
+        if let Some(leg_thickness) = leg_thickness {
+            let double_distance_to_leg = match corner {
+                Corner::TopLeft | Corner::BottomRight => i32::abs(double_x - double_y),
+                Corner::TopRight | Corner::BottomLeft => i32::abs(double_x + double_y),
+            };
+            double_distance_to_leg <= leg_thickness
+        } else {
+            true
+        }
+    }
+}
+
+/// The kind of stitch of every cell of an image
+#[derive(Debug, Clone)]
+pub struct StitchKindMap {
+    pub width: i32,
+    pub height: i32,
+    kinds: Vec<StitchKind>,
+}
+
+impl StitchKindMap {
+    pub fn new_full(width: i32, height: i32) -> StitchKindMap {
+        StitchKindMap {
+            width,
+            height,
+            kinds: vec![StitchKind::Full; (width * height) as usize],
+        }
+    }
+
+    /// Reads a fractional stitch mask that uses the `FRACTIONAL_MASK_COLORS`
+    pub fn from_mask(mask: &Bitmap) -> Result<StitchKindMap, String> {
+        let mut result = StitchKindMap::new_full(mask.width, mask.height);
+        for y in 0..mask.height {
+            for x in 0..mask.width {
+                let color = mask.get(x, y);
+                if color.a == 0 || color == PixelRGBA::white() || color == PixelRGBA::black() {
+                    continue;
+                }
+                let kind = FRACTIONAL_MASK_COLORS
+                    .iter()
+                    .find(|(mask_color, _kind)| *mask_color == color)
+                    .map(|(_mask_color, kind)| *kind)
+                    .ok_or_else(|| {
+                        format!(
+                            "Unknown fractional stitch color {} at {}x{}",
+                            color_to_hex_string(color),
+                            x,
+                            y
+                        )
+                    })?;
+                result.kinds[(x + y * mask.width) as usize] = kind;
+            }
+        }
+        Ok(result)
+    }
+
+    pub fn get(&self, x: i32, y: i32) -> StitchKind {
+        self.kinds[(x + y * self.width) as usize]
+    }
+
+    pub fn set(&mut self, x: i32, y: i32, kind: StitchKind) {
+        self.kinds[(x + y * self.width) as usize] = kind;
+    }
+
+    /// Returns the `width`x`height` region that starts at the given position
+    pub fn sub_map(&self, pos_x: i32, pos_y: i32, width: i32, height: i32) -> StitchKindMap {
+        let mut result = StitchKindMap::new_full(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                result.kinds[(x + y * width) as usize] = self.get(pos_x + x, pos_y + y);
+            }
+        }
+        result
+    }
+
+    pub fn has_fractional_stitches(&self) -> bool {
+        self.kinds.iter().any(|kind| kind.is_fractional())
+    }
+}
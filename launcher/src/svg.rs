@@ -0,0 +1,902 @@
+use crate::backstitch::BackstitchInfo;
+use crate::color_regions::ColorRegions;
+use crate::stitch_kinds::{Corner, StitchKind};
+use crate::xml::xml_escape;
+use crate::{
+    ColorInfo, PatternOptions, PatternRenderContext, PatternType, Result,
+    COLOR_FRACTIONAL_STITCH_UNCOLORIZED, COLOR_GRID_THICK, COLOR_GRID_THIN, COLOR_OVERLAP_SHADE,
+    COLOR_PROGRESS_HATCH, COLOR_REGION_OUTLINE, LEGEND_BLOCK_ENTRY_COUNT,
+};
+
+use cottontail::image::bitmap::*;
+use cottontail::math::*;
+use indexmap::IndexMap;
+use rayon::prelude::*;
+
+/// Width and height of a stitch cell in SVG user units. A user unit is one CSS pixel by default,
+/// so the pages show up as big as the `.png` patterns of the default tile size.
+const CELL_SIZE: i32 = crate::TILE_SIZE_DEFAULT;
+/// NOTE: The texts use the monospace font of the viewer, so we only know roughly how wide they are
+const FONT_SIZE_SMALL: f64 = 8.0;
+const FONT_SIZE_BIG: f64 = 16.0;
+const FONT_CHAR_WIDTH_PER_SIZE: f64 = 0.6;
+const LINE_HEIGHT_SMALL: f64 = 1.25 * FONT_SIZE_SMALL;
+/// Space around the grid of patterns without grid labels so that the outer grid lines are not cut
+/// off
+const GRID_MARGIN: i32 = 2;
+
+pub struct SvgPatternPage {
+    pub pattern_type: PatternType,
+    /// This is `None` for the complete pattern
+    pub segment_index: Option<usize>,
+    pub svg: String,
+}
+
+impl SvgPatternPage {
+    pub fn filename_suffix(&self) -> String {
+        self.pattern_type.filename_suffix(self.segment_index)
+    }
+}
+
+/// The legend and pattern pages of one image as `.svg` documents
+pub struct SvgPatternSet {
+    pub legend: String,
+    /// The complete patterns followed by the patterns of the individual segments if there is more
+    /// than one segment
+    pub pages: Vec<SvgPatternPage>,
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Helpers
+
+fn svg_color(color: PixelRGBA) -> String {
+    format!("#{:02X}{:02X}{:02X}", color.r, color.g, color.b)
+}
+
+/// Symbols are black on bright and white on dark backgrounds, the same as on the `.png` patterns
+fn symbol_color_on(background_color: PixelRGBA) -> PixelRGBA {
+    if Color::from_pixelrgba(background_color).to_relative_luminance() > 0.2 {
+        PixelRGBA::black()
+    } else {
+        PixelRGBA::white()
+    }
+}
+
+fn text_width(text: &str, font_size: f64) -> f64 {
+    text.chars().count() as f64 * FONT_CHAR_WIDTH_PER_SIZE * font_size
+}
+
+fn svg_document(width: f64, height: f64, defs: &str, content: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         version=\"1.1\" width=\"{:.1}\" height=\"{:.1}\" viewBox=\"0 0 {:.1} {:.1}\">\n\
+         <defs>\n{}</defs>\n\
+         <rect width=\"{:.1}\" height=\"{:.1}\" fill=\"#FFFFFF\"/>\n\
+         {}</svg>\n",
+        width, height, width, height, defs, width, height, content
+    )
+}
+
+/// A line of text centered on the given point
+fn svg_text_centered(x: f64, y: f64, font_size: f64, text: &str) -> String {
+    format!(
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"{:.1}\" \
+         text-anchor=\"middle\" dominant-baseline=\"central\">{}</text>\n",
+        x,
+        y,
+        font_size,
+        xml_escape(text)
+    )
+}
+
+/// A line of text that starts at the given point and is vertically centered on it
+fn svg_text_left(x: f64, y: f64, font_size: f64, text: &str) -> String {
+    // NOTE: We preserve whitespace because the legend aligns its columns with spaces
+    format!(
+        "<text x=\"{:.1}\" y=\"{:.1}\" font-family=\"monospace\" font-size=\"{:.1}\" \
+         dominant-baseline=\"central\" xml:space=\"preserve\">{}</text>\n",
+        x,
+        y,
+        font_size,
+        xml_escape(text)
+    )
+}
+
+fn svg_line(start: Vec2i, end: Vec2i, thickness: i32, color: PixelRGBA) -> String {
+    format!(
+        "<line x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" stroke=\"{}\" stroke-width=\"{}\" \
+         stroke-linecap=\"round\"/>\n",
+        start.x,
+        start.y,
+        end.x,
+        end.y,
+        svg_color(color),
+        thickness
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Reusable definitions
+
+/// Converts the pixels of a symbol bitmap that differ from the mask color into a path. Horizontal
+/// runs of pixels become a single rectangle.
+fn symbol_path_data(symbol: &Bitmap, mask_color: PixelRGBA) -> String {
+    let mut result = String::new();
+    for y in 0..symbol.height {
+        let mut run_start = None;
+        for x in 0..=symbol.width {
+            let is_set = x < symbol.width && symbol.get(x, y) != mask_color;
+            match (run_start, is_set) {
+                (None, true) => run_start = Some(x),
+                (Some(start), false) => {
+                    result += &format!("M{} {}h{}v1h-{}z", start, y, x - start, x - start);
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    result
+}
+
+fn symbol_id(info: &ColorInfo, use_alphanum: bool) -> String {
+    if use_alphanum {
+        format!("label_{}", info.label_index)
+    } else {
+        format!("symbol_{}", info.symbol_index)
+    }
+}
+
+/// Creates a `<symbol>` for the stitch symbol or paint-by-numbers label of every color. The
+/// symbols have no fill of their own, so every use can choose the color.
+fn symbol_defs(color_mappings: &IndexMap<PixelRGBA, ColorInfo>, use_alphanum: bool) -> String {
+    let mut result = String::new();
+    for info in color_mappings.values() {
+        // NOTE: The stitch symbols are black on white and the labels are white on transparent
+        let (symbol, mask_color) = if use_alphanum {
+            (&info.symbol_alphanum, PixelRGBA::transparent())
+        } else {
+            (&info.symbol, PixelRGBA::white())
+        };
+        result += &format!(
+            "<symbol id=\"{}\" viewBox=\"0 0 {} {}\"><path d=\"{}\"/></symbol>\n",
+            symbol_id(info, use_alphanum),
+            symbol.width,
+            symbol.height,
+            symbol_path_data(symbol, mask_color)
+        );
+    }
+    result
+}
+
+fn stitch_kind_id(stitch_kind: StitchKind) -> String {
+    let corner_name = |corner: Corner| match corner {
+        Corner::TopLeft => "top_left",
+        Corner::TopRight => "top_right",
+        Corner::BottomLeft => "bottom_left",
+        Corner::BottomRight => "bottom_right",
+    };
+    match stitch_kind {
+        StitchKind::Full => "stitch_full".to_owned(),
+        StitchKind::HalfRising => "stitch_half_rising".to_owned(),
+        StitchKind::HalfFalling => "stitch_half_falling".to_owned(),
+        StitchKind::Quarter(corner) => format!("stitch_quarter_{}", corner_name(corner)),
+        StitchKind::ThreeQuarter(corner) => {
+            format!("stitch_three_quarter_{}", corner_name(corner))
+        }
+    }
+}
+
+/// Creates a path of the legs of every given stitch kind within a cell at the origin and a clip
+/// path of the same shape. The legs are as thick as on the `.png` patterns.
+fn stitch_kind_defs(stitch_kinds: &[StitchKind]) -> String {
+    // NOTE: This is the top-left leg. It ends where the legs of the neighbouring corners begin.
+    let leg_thickness = CELL_SIZE / 4;
+    let center = CELL_SIZE / 2;
+    let leg_top_left = [
+        (0, 0),
+        (leg_thickness, 0),
+        (center, center - leg_thickness),
+        (center, center),
+        (center - leg_thickness, center),
+        (0, leg_thickness),
+    ];
+
+    let mut result = String::new();
+    for &stitch_kind in stitch_kinds {
+        let mut path_data = String::new();
+        for &(corner, mirror_x, mirror_y) in &[
+            (Corner::TopLeft, false, false),
+            (Corner::TopRight, true, false),
+            (Corner::BottomLeft, false, true),
+            (Corner::BottomRight, true, true),
+        ] {
+            if !stitch_kind.has_leg(corner) {
+                continue;
+            }
+            for (point_index, &(x, y)) in leg_top_left.iter().enumerate() {
+                let x = if mirror_x { CELL_SIZE - x } else { x };
+                let y = if mirror_y { CELL_SIZE - y } else { y };
+                let command = if point_index == 0 { "M" } else { "L" };
+                path_data += &format!("{}{} {}", command, x, y);
+            }
+            path_data += "z";
+        }
+        let id = stitch_kind_id(stitch_kind);
+        result += &format!("<path id=\"{}\" d=\"{}\"/>\n", id, path_data);
+        result += &format!(
+            "<clipPath id=\"clip_{}\"><use xlink:href=\"#{}\"/></clipPath>\n",
+            id, id
+        );
+    }
+    result
+}
+
+/// Diagonal lines for hatching completed stitches. The pattern tiles are aligned to the grid, so
+/// the lines continue across neighbouring cells like on the `.png` patterns.
+fn progress_hatch_def() -> String {
+    let spacing = i32::max(3, CELL_SIZE / 4);
+    format!(
+        "<pattern id=\"progress_hatch\" width=\"{}\" height=\"{}\" \
+         patternUnits=\"userSpaceOnUse\">\
+         <path d=\"M-1 1L1 -1M0 {}L{} 0M{} {}L{} {}\" stroke=\"{}\" stroke-width=\"1\"/>\
+         </pattern>\n",
+        spacing,
+        spacing,
+        spacing,
+        spacing,
+        spacing - 1,
+        spacing + 1,
+        spacing + 1,
+        spacing - 1,
+        svg_color(COLOR_PROGRESS_HATCH)
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Patterns
+
+/// Creates the same pattern as `create_cross_stitch_pattern` as an `.svg` document. Cells,
+/// fractional stitches, grid lines, backstitches and origin bars are vector shapes and every
+/// symbol is defined once and then reused, so the pattern stays crisp at any zoom level and print
+/// size.
+pub fn create_svg_pattern(context: &PatternRenderContext, pattern_type: PatternType) -> String {
+    let PatternRenderContext {
+        bitmap,
+        stitch_kinds,
+        backstitches,
+        progress,
+        color_mappings,
+        fabric_color,
+        segment_index,
+        logical_first_coordinate,
+        overlap_left,
+        overlap_top,
+        add_origin_grid_bars,
+    } = *context;
+    let (logical_first_coordinate_x, logical_first_coordinate_y) =
+        (logical_first_coordinate.x, logical_first_coordinate.y);
+    let add_thick_ten_grid = !pattern_type.is_paint_by_numbers();
+    let add_origin_grid_bars = add_origin_grid_bars && !pattern_type.is_paint_by_numbers();
+    let (colorize, add_symbol, use_alphanum) = match pattern_type {
+        PatternType::BlackAndWhite => (false, true, false),
+        PatternType::Colorized => (true, true, false),
+        PatternType::ColorizedNoSymbols => (true, false, false),
+        PatternType::PaintByNumbers => (false, true, true),
+        PatternType::PaintByNumbersRegions => (false, true, true),
+    };
+    // NOTE: Region patterns are meant for painting, so they show neither the stitch grid,
+    //       fractional stitches nor backstitches
+    let color_regions = if pattern_type == PatternType::PaintByNumbersRegions {
+        Some(ColorRegions::from_image(bitmap))
+    } else {
+        None
+    };
+
+    let grid_width = CELL_SIZE * bitmap.width;
+    let grid_height = CELL_SIZE * bitmap.height;
+
+    // NOTE: The grid labels are only placed on patterns with the 10x10 grid. We reserve as much
+    //       space for them as the `.png` patterns do.
+    let margin = if add_thick_ten_grid {
+        let max_text_charcount = [
+            logical_first_coordinate_x,
+            logical_first_coordinate_y,
+            logical_first_coordinate_x + bitmap.width,
+            logical_first_coordinate_y + bitmap.height,
+        ]
+        .iter()
+        .map(|coordinate| coordinate.to_string().len())
+        .max()
+        .unwrap();
+        (FONT_CHAR_WIDTH_PER_SIZE * FONT_SIZE_SMALL * (max_text_charcount + 4) as f64).ceil() as i32
+    } else {
+        GRID_MARGIN
+    };
+    let title_height = if segment_index.is_some() {
+        (3.0 * FONT_SIZE_BIG) as i32
+    } else {
+        0
+    };
+    let width = grid_width + 2 * margin;
+    let height = grid_height + 2 * margin + title_height;
+
+    let mut fractional_stitch_kinds = Vec::new();
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let stitch_kind = stitch_kinds.get(x, y);
+            if bitmap.get(x, y).a != 0
+                && stitch_kind.is_fractional()
+                && color_regions.is_none()
+                && !fractional_stitch_kinds.contains(&stitch_kind)
+            {
+                fractional_stitch_kinds.push(stitch_kind);
+            }
+        }
+    }
+    let mut defs = stitch_kind_defs(&fractional_stitch_kinds);
+    if add_symbol {
+        defs += &symbol_defs(color_mappings, use_alphanum);
+    }
+    defs += &progress_hatch_def();
+
+    let mut content = String::new();
+
+    // Add segment index indicator if necessary
+    if let Some(segment_index) = segment_index {
+        content += &svg_text_centered(
+            width as f64 / 2.0,
+            title_height as f64 / 2.0,
+            FONT_SIZE_BIG,
+            &format!("Pattern Part {}", segment_index),
+        );
+    }
+
+    content += &format!(
+        "<g transform=\"translate({} {})\">\n",
+        margin,
+        margin + title_height
+    );
+
+    // Colorize cells
+    // NOTE: We merge horizontal runs of full stitches of the same color into one rectangle and
+    //       render them with crisp edges so that there are no seams between neighbouring cells
+    if colorize {
+        content += "<g shape-rendering=\"crispEdges\">\n";
+        // NOTE: The fabric shows through unstitched cells
+        content += &format!(
+            "<rect x=\"0\" y=\"0\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+            grid_width,
+            grid_height,
+            svg_color(fabric_color)
+        );
+        let full_stitch_color = |x: i32, y: i32| {
+            let color = bitmap.get(x, y);
+            if color.a != 0 && !stitch_kinds.get(x, y).is_fractional() {
+                Some(color)
+            } else {
+                None
+            }
+        };
+        for y in 0..bitmap.height {
+            let mut run_start = 0;
+            for x in 1..=bitmap.width {
+                let run_color = full_stitch_color(run_start, y);
+                if x < bitmap.width && full_stitch_color(x, y) == run_color {
+                    continue;
+                }
+                if let Some(color) = run_color {
+                    content += &format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/>\n",
+                        CELL_SIZE * run_start,
+                        CELL_SIZE * y,
+                        CELL_SIZE * (x - run_start),
+                        CELL_SIZE,
+                        svg_color(color)
+                    );
+                }
+                run_start = x;
+            }
+        }
+        content += "</g>\n";
+    }
+    // NOTE: Without colors we still draw fractional stitches in grey so that their shape is
+    //       visible
+    for y in 0..bitmap.height {
+        for x in 0..bitmap.width {
+            let color = bitmap.get(x, y);
+            let stitch_kind = stitch_kinds.get(x, y);
+            if color.a != 0 && stitch_kind.is_fractional() && color_regions.is_none() {
+                let leg_color = if colorize {
+                    color
+                } else {
+                    COLOR_FRACTIONAL_STITCH_UNCOLORIZED
+                };
+                content += &format!(
+                    "<use xlink:href=\"#{}\" x=\"{}\" y=\"{}\" fill=\"{}\"/>\n",
+                    stitch_kind_id(stitch_kind),
+                    CELL_SIZE * x,
+                    CELL_SIZE * y,
+                    svg_color(leg_color)
+                );
+            }
+        }
+    }
+
+    // Add symbols
+    if add_symbol {
+        for y in 0..bitmap.height {
+            for x in 0..bitmap.width {
+                let color = bitmap.get(x, y);
+                let is_label_cell = color_regions
+                    .as_ref()
+                    .map(|color_regions| color_regions.is_label_cell(x, y))
+                    .unwrap_or(true);
+                if color.a == 0 || !is_label_cell {
+                    continue;
+                }
+                let info = color_mappings.get(&color).unwrap();
+                let id = symbol_id(info, use_alphanum);
+                let stitch_kind = if color_regions.is_some() {
+                    StitchKind::Full
+                } else {
+                    stitch_kinds.get(x, y)
+                };
+                let background_color = if !colorize {
+                    PixelRGBA::white()
+                } else if stitch_kind.is_fractional() {
+                    fabric_color
+                } else {
+                    color
+                };
+                content += &format!(
+                    "<use xlink:href=\"#{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                     fill=\"{}\"/>\n",
+                    id,
+                    CELL_SIZE * x,
+                    CELL_SIZE * y,
+                    CELL_SIZE,
+                    CELL_SIZE,
+                    svg_color(symbol_color_on(background_color))
+                );
+
+                // NOTE: Cells of fractional stitches are only partially colored. Where the symbol
+                //       lies on dark legs we draw it again in white, clipped to the legs.
+                if colorize
+                    && stitch_kind.is_fractional()
+                    && symbol_color_on(color) != symbol_color_on(background_color)
+                {
+                    content += &format!(
+                        "<g transform=\"translate({} {})\" clip-path=\"url(#clip_{})\">\
+                         <use xlink:href=\"#{}\" width=\"{}\" height=\"{}\" fill=\"{}\"/></g>\n",
+                        CELL_SIZE * x,
+                        CELL_SIZE * y,
+                        stitch_kind_id(stitch_kind),
+                        id,
+                        CELL_SIZE,
+                        CELL_SIZE,
+                        svg_color(symbol_color_on(color))
+                    );
+                }
+            }
+        }
+    }
+
+    // Shade stitches that are repeated from previous pattern parts
+    // NOTE: Blending halfway towards the shade color is the same as drawing it half transparent
+    let shade_rect = |x: i32, y: i32, width: i32, height: i32| {
+        format!(
+            "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.5\" \
+             shape-rendering=\"crispEdges\"/>\n",
+            x,
+            y,
+            width,
+            height,
+            svg_color(COLOR_OVERLAP_SHADE)
+        )
+    };
+    if overlap_left > 0 {
+        content += &shade_rect(0, 0, CELL_SIZE * overlap_left, grid_height);
+    }
+    if overlap_top > 0 {
+        // NOTE: We skip the top-left corner if it was already shaded above
+        content += &shade_rect(
+            CELL_SIZE * overlap_left,
+            0,
+            grid_width - CELL_SIZE * overlap_left,
+            CELL_SIZE * overlap_top,
+        );
+    }
+
+    // Mark stitches that are already done
+    // NOTE: Like the cells we merge horizontal runs of completed stitches into one rectangle
+    let is_completed = |x: i32, y: i32| bitmap.get(x, y).a != 0 && progress.is_completed(x, y);
+    for y in 0..bitmap.height {
+        let mut run_start = None;
+        for x in 0..=bitmap.width {
+            let completed = x < bitmap.width && is_completed(x, y);
+            match (run_start, completed) {
+                (None, true) => run_start = Some(x),
+                (Some(start), false) => {
+                    content += &format!(
+                        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#FFFFFF\" \
+                         fill-opacity=\"0.5\" shape-rendering=\"crispEdges\"/>\n\
+                         <rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" \
+                         fill=\"url(#progress_hatch)\"/>\n",
+                        CELL_SIZE * start,
+                        CELL_SIZE * y,
+                        CELL_SIZE * (x - start),
+                        CELL_SIZE,
+                        CELL_SIZE * start,
+                        CELL_SIZE * y,
+                        CELL_SIZE * (x - start),
+                        CELL_SIZE,
+                    );
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // Add 1x1 grid or the outlines of the color regions
+    let grid_path = |x_coords: &[i32], y_coords: &[i32], thickness: i32, color: PixelRGBA| {
+        let mut path_data = String::new();
+        for x in x_coords {
+            path_data += &format!("M{} 0V{}", CELL_SIZE * x, grid_height);
+        }
+        for y in y_coords {
+            path_data += &format!("M0 {}H{}", CELL_SIZE * y, grid_width);
+        }
+        format!(
+            "<path d=\"{}\" stroke=\"{}\" stroke-width=\"{}\" fill=\"none\" \
+             shape-rendering=\"crispEdges\"/>\n",
+            path_data,
+            svg_color(color),
+            thickness
+        )
+    };
+    let grid_x_coords: Vec<i32> = (0..=bitmap.width).collect();
+    let grid_y_coords: Vec<i32> = (0..=bitmap.height).collect();
+    if let Some(color_regions) = &color_regions {
+        let mut path_data = String::new();
+        for (start, end) in color_regions.outline_edges() {
+            path_data += &format!(
+                "M{} {}L{} {}",
+                CELL_SIZE * start.x,
+                CELL_SIZE * start.y,
+                CELL_SIZE * end.x,
+                CELL_SIZE * end.y
+            );
+        }
+        content += &format!(
+            "<path d=\"{}\" stroke=\"{}\" stroke-width=\"2\" stroke-linecap=\"square\" \
+             fill=\"none\"/>\n",
+            path_data,
+            svg_color(COLOR_REGION_OUTLINE)
+        );
+    } else {
+        content += &grid_path(&grid_x_coords, &grid_y_coords, 1, COLOR_GRID_THIN);
+    }
+
+    // Add 10x10 grid
+    if add_thick_ten_grid {
+        let ten_grid_x_coords: Vec<i32> = grid_x_coords
+            .iter()
+            .cloned()
+            .filter(|x| (logical_first_coordinate_x + x) % 10 == 0)
+            .collect();
+        let ten_grid_y_coords: Vec<i32> = grid_y_coords
+            .iter()
+            .cloned()
+            .filter(|y| (logical_first_coordinate_y + y) % 10 == 0)
+            .collect();
+        content += &grid_path(&ten_grid_x_coords, &ten_grid_y_coords, 2, COLOR_GRID_THICK);
+    }
+
+    // Add backstitches
+    // NOTE: Without colors all backstitches are black. With colors they get a black border so that
+    //       they are also visible on stitches of the same color
+    if color_regions.is_none() {
+        let backstitch_thickness = i32::max(2, CELL_SIZE / 6);
+        for backstitch in backstitches {
+            let start = Vec2i::new(
+                CELL_SIZE * backstitch.start.x,
+                CELL_SIZE * backstitch.start.y,
+            );
+            let end = Vec2i::new(CELL_SIZE * backstitch.end.x, CELL_SIZE * backstitch.end.y);
+            if colorize {
+                content += &svg_line(start, end, backstitch_thickness + 2, PixelRGBA::black());
+                content += &svg_line(start, end, backstitch_thickness, backstitch.color);
+            } else {
+                content += &svg_line(start, end, backstitch_thickness, PixelRGBA::black());
+            }
+        }
+    }
+
+    // Add origin grid
+    // NOTE: Unlike on the `.png` patterns we don't need to extend the pattern for origin bars on
+    //       its border because they fit into the margin
+    if add_origin_grid_bars {
+        let bar = |x: i32, y: i32, width: i32, height: i32, color: PixelRGBA| {
+            format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" \
+                 shape-rendering=\"crispEdges\"/>\n",
+                x,
+                y,
+                width,
+                height,
+                svg_color(color)
+            )
+        };
+        let origin_bitmap_coord_x = -logical_first_coordinate_x;
+        if 0 <= origin_bitmap_coord_x && origin_bitmap_coord_x <= bitmap.width {
+            let pos_x = CELL_SIZE * origin_bitmap_coord_x;
+            content += &bar(pos_x - 2, 0, 4, grid_height, PixelRGBA::black());
+            content += &bar(pos_x - 1, 0, 2, grid_height, PixelRGBA::white());
+        }
+        let origin_bitmap_coord_y = -logical_first_coordinate_y;
+        if 0 <= origin_bitmap_coord_y && origin_bitmap_coord_y <= bitmap.height {
+            let pos_y = CELL_SIZE * origin_bitmap_coord_y;
+            content += &bar(0, pos_y - 2, grid_width, 4, PixelRGBA::black());
+            content += &bar(0, pos_y - 1, grid_width, 2, PixelRGBA::white());
+        }
+    }
+
+    content += "</g>\n";
+
+    // Add 10-grid labels
+    if add_thick_ten_grid {
+        let grid_top = (title_height + margin) as f64;
+        let grid_left = margin as f64;
+        let label_pos_top = title_height as f64 + margin as f64 / 2.0;
+        let label_pos_bottom = grid_top + grid_height as f64 + margin as f64 / 2.0;
+        let label_pos_left = margin as f64 / 2.0;
+        let label_pos_right = grid_left + grid_width as f64 + margin as f64 / 2.0;
+
+        for (grid_coord_x, logical_coord_x) in
+            crate::grid_label_coordinates(bitmap.width, logical_first_coordinate_x)
+        {
+            let text = logical_coord_x.to_string();
+            let pos_x = grid_left + (CELL_SIZE * grid_coord_x) as f64;
+            content += &svg_text_centered(pos_x, label_pos_top, FONT_SIZE_SMALL, &text);
+            content += &svg_text_centered(pos_x, label_pos_bottom, FONT_SIZE_SMALL, &text);
+        }
+        for (grid_coord_y, logical_coord_y) in
+            crate::grid_label_coordinates(bitmap.height, logical_first_coordinate_y)
+        {
+            // NOTE: In pixel space our y-coordinates are y-down. We want cartesian y-up so we
+            //       negate y
+            let text = (-logical_coord_y).to_string();
+            let pos_y = grid_top + (CELL_SIZE * grid_coord_y) as f64;
+            content += &svg_text_centered(label_pos_left, pos_y, FONT_SIZE_SMALL, &text);
+            content += &svg_text_centered(label_pos_right, pos_y, FONT_SIZE_SMALL, &text);
+        }
+    }
+
+    svg_document(width as f64, height as f64, &defs, &content)
+}
+
+/// Creates the same pattern types as `create_cross_stitch_pattern_set` as `.svg` documents
+fn create_svg_pattern_page_set(
+    context: &PatternRenderContext,
+    create_paint_by_number_set: bool,
+) -> Vec<SvgPatternPage> {
+    let mut pattern_types = vec![
+        PatternType::Colorized,
+        PatternType::BlackAndWhite,
+        PatternType::ColorizedNoSymbols,
+    ];
+    if create_paint_by_number_set {
+        pattern_types.push(PatternType::PaintByNumbers);
+        pattern_types.push(PatternType::PaintByNumbersRegions);
+    }
+
+    pattern_types
+        .into_par_iter()
+        .map(|pattern_type| SvgPatternPage {
+            pattern_type,
+            segment_index: context.segment_index,
+            svg: create_svg_pattern(context, pattern_type),
+        })
+        .collect()
+}
+
+/// Creates the legend and all pattern pages for the image of the given complete pattern context
+/// as `.svg` documents. This mirrors `create_pattern_set`.
+pub fn create_svg_pattern_set(
+    context: &PatternRenderContext,
+    backstitch_infos: &IndexMap<PixelRGBA, BackstitchInfo>,
+    options: &PatternOptions,
+) -> Result<SvgPatternSet> {
+    context.check(options)?;
+    let segments = context.segments(options);
+    let segment_layout_indices: Vec<Vec2i> = segments
+        .iter()
+        .map(|segment| segment.layout_index)
+        .collect();
+
+    let legend = create_svg_legend(
+        context.bitmap.dim(),
+        context.color_mappings,
+        backstitch_infos,
+        &segment_layout_indices,
+        options,
+    );
+
+    let mut pages = create_svg_pattern_page_set(context, true);
+    if segments.len() > 1 {
+        let pages_segments: Vec<Vec<SvgPatternPage>> = segments
+            .par_iter()
+            .enumerate()
+            .map(|(segment_index, segment)| {
+                create_svg_pattern_page_set(&context.for_segment(segment, segment_index), false)
+            })
+            .collect();
+        pages.extend(pages_segments.into_iter().flatten());
+    }
+
+    Ok(SvgPatternSet { legend, pages })
+}
+
+////////////////////////////////////////////////////////////////////////////////////////////////////
+// Legend
+
+/// Creates the same legend as `create_cross_stitch_legend` as an `.svg` document
+pub fn create_svg_legend(
+    image_dimensions: Vec2i,
+    color_mappings: &IndexMap<PixelRGBA, ColorInfo>,
+    backstitch_infos: &IndexMap<PixelRGBA, BackstitchInfo>,
+    segment_layout_indices: &[Vec2i],
+    options: &PatternOptions,
+) -> String {
+    let cell_size = CELL_SIZE as f64;
+    let padding = cell_size;
+    let show_progress = crate::color_mappings_have_progress(color_mappings);
+    let mut content = String::new();
+    let mut content_width: f64 = 0.0;
+    let mut pos_y = padding;
+
+    // Add color and stitch stats
+    let stats_text = crate::legend_stats_text(
+        image_dimensions,
+        color_mappings,
+        backstitch_infos,
+        &options.thread_usage,
+        options.fabric_margin_centimeters,
+        options.beads.as_ref(),
+    );
+    for line in stats_text.lines() {
+        if !line.is_empty() {
+            content += &svg_text_left(
+                padding,
+                pos_y + LINE_HEIGHT_SMALL / 2.0,
+                FONT_SIZE_SMALL,
+                line,
+            );
+            content_width = content_width.max(text_width(line, FONT_SIZE_SMALL));
+        }
+        pos_y += LINE_HEIGHT_SMALL;
+    }
+
+    // Add color mapping blocks
+    // NOTE: Backstitches come after all colors. The blocks are placed next to each other.
+    let entries: Vec<(String, String)> = color_mappings
+        .values()
+        .map(|info| {
+            let swatch = format!(
+                "<rect x=\"0.5\" y=\"0.5\" width=\"{}\" height=\"{}\" fill=\"{}\" \
+                 stroke=\"#000000\"/>\n\
+                 <use xlink:href=\"#{}\" x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" \
+                 fill=\"#000000\"/>\n\
+                 <rect x=\"{}\" y=\"0.5\" width=\"{}\" height=\"{}\" fill=\"none\" \
+                 stroke=\"#000000\"/>\n",
+                CELL_SIZE - 1,
+                CELL_SIZE - 1,
+                svg_color(info.color),
+                symbol_id(info, false),
+                CELL_SIZE,
+                CELL_SIZE,
+                CELL_SIZE,
+                CELL_SIZE as f64 + 0.5,
+                CELL_SIZE - 1,
+                CELL_SIZE - 1,
+            );
+            let text = crate::legend_entry_text(
+                info,
+                &options.thread_usage,
+                options.beads.as_ref(),
+                show_progress,
+            );
+            (swatch, text)
+        })
+        .chain(backstitch_infos.values().map(|info| {
+            // NOTE: The backstitch sample is drawn the same way as on the colorized patterns
+            let backstitch_thickness = i32::max(2, CELL_SIZE / 6);
+            let start = Vec2i::new(CELL_SIZE / 4, CELL_SIZE / 2);
+            let end = Vec2i::new(2 * CELL_SIZE - CELL_SIZE / 4, CELL_SIZE / 2);
+            let sample = svg_line(start, end, backstitch_thickness + 2, PixelRGBA::black())
+                + &svg_line(start, end, backstitch_thickness, info.color);
+            let text = crate::backstitch_legend_entry_text(info, &options.thread_usage);
+            (sample, text)
+        }))
+        .collect();
+    let mut pos_x = padding;
+    let mut blocks_height: f64 = 0.0;
+    for block in entries.chunks(LEGEND_BLOCK_ENTRY_COUNT) {
+        let mut block_width: f64 = 0.0;
+        for (entry_index, (sample, text)) in block.iter().enumerate() {
+            let entry_pos_y = pos_y + (2 * CELL_SIZE * entry_index as i32) as f64;
+            content += &format!(
+                "<g transform=\"translate({:.1} {:.1})\">\n{}</g>\n",
+                pos_x, entry_pos_y, sample
+            );
+            let text = format!(" {}", text);
+            content += &svg_text_left(
+                pos_x + 2.0 * cell_size,
+                entry_pos_y + cell_size / 2.0,
+                FONT_SIZE_SMALL,
+                &text,
+            );
+            block_width = block_width.max(2.0 * cell_size + text_width(&text, FONT_SIZE_SMALL));
+        }
+        blocks_height = blocks_height.max((2 * block.len() as i32 - 1) as f64 * cell_size);
+        pos_x += block_width + 2.0 * cell_size;
+    }
+    content_width = content_width.max(pos_x - 2.0 * cell_size - padding);
+    pos_y += blocks_height + 1.5 * cell_size;
+
+    // Add page layout order if necessary
+    if segment_layout_indices.len() > 1 {
+        // Draw separating line between colors and page order layout
+        let separator_pos_y = pos_y;
+        pos_y += 2.0 * LINE_HEIGHT_SMALL;
+        content += &svg_text_left(
+            padding,
+            pos_y + LINE_HEIGHT_SMALL / 2.0,
+            FONT_SIZE_SMALL,
+            "Pattern parts overview:",
+        );
+        pos_y += 2.0 * LINE_HEIGHT_SMALL;
+
+        let page_count = segment_layout_indices.len();
+        // NOTE: We want to have a visual gap between page tiles therefore we add 1
+        let page_tile_width = 1.0 + text_width(&format!(" {} ", page_count), FONT_SIZE_SMALL);
+        let page_tile_height = 1.0 + page_tile_width * (9.0 / 6.0);
+        for (page_index, pos_index) in segment_layout_indices.iter().enumerate() {
+            let tile_pos_x = padding + pos_index.x as f64 * page_tile_width;
+            let tile_pos_y = pos_y + pos_index.y as f64 * page_tile_height;
+            content += &format!(
+                "<rect x=\"{:.1}\" y=\"{:.1}\" width=\"{:.1}\" height=\"{:.1}\" fill=\"none\" \
+                 stroke=\"#000000\"/>\n",
+                tile_pos_x + 0.5,
+                tile_pos_y + 0.5,
+                page_tile_width - 2.0,
+                page_tile_height - 2.0
+            );
+            content += &svg_text_centered(
+                tile_pos_x + page_tile_width / 2.0,
+                tile_pos_y + page_tile_height / 2.0,
+                FONT_SIZE_SMALL,
+                &(page_index + 1).to_string(),
+            );
+        }
+        // NOTE: Indexes begin at 0 therefore we add 1
+        let num_rows = 1 + segment_layout_indices.iter().map(|v| v.y).max().unwrap();
+        let num_columns = 1 + segment_layout_indices.iter().map(|v| v.x).max().unwrap();
+        content_width = content_width.max(num_columns as f64 * page_tile_width);
+        pos_y += num_rows as f64 * page_tile_height;
+
+        content += &format!(
+            "<path d=\"M{:.1} {:.1}H{:.1}\" stroke=\"#000000\" stroke-width=\"1\"/>\n",
+            0.0,
+            separator_pos_y + 0.5,
+            content_width + 2.0 * padding
+        );
+    }
+
+    let width = content_width + 2.0 * padding;
+    let height = pos_y + padding;
+    svg_document(width, height, &symbol_defs(color_mappings, false), &content)
+}
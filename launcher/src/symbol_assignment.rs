@@ -0,0 +1,270 @@
+use crate::color_lab::ColorLab;
+
+use cottontail::image::bitmap::*;
+
+/// Colors that are further apart than this (CIEDE2000) are hardly ever confused, so their symbols
+/// barely matter
+const COLOR_CONFUSION_DISTANCE: f64 = 10.0;
+/// Upper bound for the passes of the swap refinement. It usually converges after two or three.
+const SWAP_REFINEMENT_PASS_COUNT_MAX: usize = 16;
+
+/// Returns which pixels of the given black on white symbol are set
+pub(crate) fn symbol_pixels(symbol: &Bitmap) -> Vec<bool> {
+    symbol
+        .data
+        .iter()
+        .map(|pixel| *pixel != PixelRGBA::white() && pixel.a != 0)
+        .collect()
+}
+
+/// Returns how different two symbols look as a value between 0 (identical) and 1 (no common
+/// pixels), given their pixels from `symbol_pixels`
+pub(crate) fn symbol_pixels_dissimilarity(pixels_a: &[bool], pixels_b: &[bool]) -> f64 {
+    // NOTE: This is the Jaccard distance of the symbol pixels
+    let mut union_count = 0;
+    let mut intersection_count = 0;
+    for (pixel_a, pixel_b) in pixels_a.iter().zip(pixels_b.iter()) {
+        if *pixel_a || *pixel_b {
+            union_count += 1;
+        }
+        if *pixel_a && *pixel_b {
+            intersection_count += 1;
+        }
+    }
+    if union_count == 0 {
+        0.0
+    } else {
+        1.0 - intersection_count as f64 / union_count as f64
+    }
+}
+
+/// Returns how different every pair of symbols looks as a value between 0 (identical) and 1 (no
+/// common pixels). The symbols are expected to be black on white and of the same size.
+pub fn symbol_dissimilarities(symbols: &[Bitmap]) -> Vec<Vec<f64>> {
+    let symbol_pixels: Vec<Vec<bool>> = symbols.iter().map(symbol_pixels).collect();
+
+    let mut result = vec![vec![0.0; symbols.len()]; symbols.len()];
+    for a in 0..symbols.len() {
+        for b in (a + 1)..symbols.len() {
+            let dissimilarity = symbol_pixels_dissimilarity(&symbol_pixels[a], &symbol_pixels[b]);
+            result[a][b] = dissimilarity;
+            result[b][a] = dissimilarity;
+        }
+    }
+    result
+}
+
+/// Assigns one of the first `symbol_count` symbols to every color so that colors that are easily
+/// confused get symbols that look very different. Returns the symbol index for every color.
+///
+/// The result only depends on the order of the given colors, so it is the same on every run.
+///
+/// NOTE: Expects at least as many symbols as colors
+pub fn assign_symbols(
+    colors: &[PixelRGBA],
+    symbol_dissimilarities: &[Vec<f64>],
+    symbol_count: usize,
+) -> Vec<usize> {
+    assert!(
+        colors.len() <= symbol_count,
+        "Got {} colors but only {} symbols",
+        colors.len(),
+        symbol_count
+    );
+
+    let colors_lab: Vec<ColorLab> = colors
+        .iter()
+        .map(|color| ColorLab::from_pixelrgba(*color))
+        .collect();
+    let mut confusions = vec![vec![0.0; colors.len()]; colors.len()];
+    for a in 0..colors.len() {
+        for b in (a + 1)..colors.len() {
+            let distance = colors_lab[a].distance_ciede2000(colors_lab[b]);
+            let confusion = f64::exp(-distance / COLOR_CONFUSION_DISTANCE);
+            confusions[a][b] = confusion;
+            confusions[b][a] = confusion;
+        }
+    }
+
+    // The cost of a color having a symbol is how similar that symbol is to the symbols of all
+    // other colors, weighted by how easily the colors are confused
+    let symbol_cost = |color_index: usize, symbol_index: usize, assignment: &[Option<usize>]| {
+        assignment
+            .iter()
+            .enumerate()
+            .filter(|(other_index, _)| *other_index != color_index)
+            .filter_map(|(other_index, other_symbol)| {
+                other_symbol.map(|other_symbol| {
+                    confusions[color_index][other_index]
+                        * (1.0 - symbol_dissimilarities[symbol_index][other_symbol])
+                })
+            })
+            .sum::<f64>()
+    };
+
+    // NOTE: The most easily confused colors choose first so that they get the best picks
+    let mut color_order: Vec<usize> = (0..colors.len()).collect();
+    let max_confusion =
+        |color_index: usize| confusions[color_index].iter().cloned().fold(0.0, f64::max);
+    color_order.sort_by(|a, b| {
+        max_confusion(*b)
+            .partial_cmp(&max_confusion(*a))
+            .unwrap()
+            .then(a.cmp(b))
+    });
+
+    // Greedy assignment
+    let mut assignment: Vec<Option<usize>> = vec![None; colors.len()];
+    let mut symbol_used = vec![false; symbol_count];
+    for &color_index in &color_order {
+        // NOTE: On equal costs we take the lowest symbol index which keeps the result stable
+        let mut best_symbol = None;
+        let mut best_cost = f64::MAX;
+        for symbol_index in (0..symbol_count).filter(|index| !symbol_used[*index]) {
+            let cost = symbol_cost(color_index, symbol_index, &assignment);
+            if cost < best_cost {
+                best_cost = cost;
+                best_symbol = Some(symbol_index);
+            }
+        }
+        // NOTE: We can unwrap here because we have at least as many symbols as colors
+        let symbol_index = best_symbol.unwrap();
+        symbol_used[symbol_index] = true;
+        assignment[color_index] = Some(symbol_index);
+    }
+
+    // Refine by swapping the symbols of two colors or exchanging a symbol with an unused one
+    // as long as that lowers the total cost
+    for _ in 0..SWAP_REFINEMENT_PASS_COUNT_MAX {
+        let mut improved = false;
+        for a in 0..colors.len() {
+            for b in (a + 1)..colors.len() {
+                let cost_before = symbol_cost(a, assignment[a].unwrap(), &assignment)
+                    + symbol_cost(b, assignment[b].unwrap(), &assignment);
+                assignment.swap(a, b);
+                let cost_after = symbol_cost(a, assignment[a].unwrap(), &assignment)
+                    + symbol_cost(b, assignment[b].unwrap(), &assignment);
+                // NOTE: The small epsilon prevents endless swapping due to rounding errors
+                if cost_after < cost_before - 1e-9 {
+                    improved = true;
+                } else {
+                    assignment.swap(a, b);
+                }
+            }
+            for symbol_index in 0..symbol_count {
+                if symbol_used[symbol_index] {
+                    continue;
+                }
+                let current_symbol = assignment[a].unwrap();
+                if symbol_cost(a, symbol_index, &assignment)
+                    < symbol_cost(a, current_symbol, &assignment) - 1e-9
+                {
+                    symbol_used[current_symbol] = false;
+                    symbol_used[symbol_index] = true;
+                    assignment[a] = Some(symbol_index);
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    assignment
+        .into_iter()
+        .map(|symbol_index| symbol_index.unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates a black on white symbol from rows where `#` marks a black pixel
+    fn symbol(rows: &[&str]) -> Bitmap {
+        let mut result =
+            Bitmap::new_filled(rows[0].len() as u32, rows.len() as u32, PixelRGBA::white());
+        for (y, row) in rows.iter().enumerate() {
+            for (x, character) in row.chars().enumerate() {
+                if character == '#' {
+                    result.set(x as i32, y as i32, PixelRGBA::black());
+                }
+            }
+        }
+        result
+    }
+
+    fn test_symbols() -> Vec<Bitmap> {
+        vec![
+            symbol(&["##..", "##..", "....", "...."]),
+            symbol(&["##..", "##..", "#...", "...."]),
+            symbol(&["....", "....", "..##", "..##"]),
+            symbol(&["#..#", "....", "....", "#..#"]),
+            symbol(&["....", ".##.", ".##.", "...."]),
+        ]
+    }
+
+    #[test]
+    fn symbol_dissimilarities_are_jaccard_distances() {
+        let dissimilarities = symbol_dissimilarities(&test_symbols());
+        for (index, row) in dissimilarities.iter().enumerate() {
+            assert_eq!(row[index], 0.0);
+        }
+        assert!((dissimilarities[0][1] - 0.2).abs() < 1e-9);
+        assert_eq!(dissimilarities[0][2], 1.0);
+        assert_eq!(dissimilarities[2][0], 1.0);
+    }
+
+    #[test]
+    fn similar_colors_get_dissimilar_symbols() {
+        let symbols = test_symbols();
+        let dissimilarities = symbol_dissimilarities(&symbols);
+        let colors = [
+            PixelRGBA::new(200, 20, 20, 255),
+            PixelRGBA::new(205, 25, 20, 255),
+        ];
+
+        let assignment = assign_symbols(&colors, &dissimilarities, symbols.len());
+        assert_eq!(assignment.len(), 2);
+        assert_eq!(dissimilarities[assignment[0]][assignment[1]], 1.0);
+    }
+
+    #[test]
+    fn assignment_is_unique_and_deterministic() {
+        let symbols = test_symbols();
+        let dissimilarities = symbol_dissimilarities(&symbols);
+        let colors = [
+            PixelRGBA::new(250, 250, 250, 255),
+            PixelRGBA::new(10, 10, 10, 255),
+            PixelRGBA::new(240, 245, 250, 255),
+            PixelRGBA::new(200, 20, 20, 255),
+            PixelRGBA::new(20, 20, 200, 255),
+        ];
+
+        let assignment = assign_symbols(&colors, &dissimilarities, symbols.len());
+        let mut used_symbols = assignment.clone();
+        used_symbols.sort_unstable();
+        used_symbols.dedup();
+        assert_eq!(used_symbols.len(), colors.len());
+        assert!(used_symbols.iter().all(|&index| index < symbols.len()));
+
+        for _ in 0..8 {
+            assert_eq!(
+                assign_symbols(&colors, &dissimilarities, symbols.len()),
+                assignment
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_with_too_few_symbols() {
+        let dissimilarities = symbol_dissimilarities(&test_symbols()[..1]);
+        assign_symbols(
+            &[PixelRGBA::black(), PixelRGBA::white()],
+            &dissimilarities,
+            1,
+        );
+    }
+}
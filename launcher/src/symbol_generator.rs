@@ -0,0 +1,297 @@
+use crate::symbol_assignment::{symbol_pixels, symbol_pixels_dissimilarity};
+
+use cottontail::image::{bitmap::*, font::*};
+use cottontail::math::*;
+
+/// Characters of the tiny font that we use as symbols on their own and inside of outlined shapes
+const SYMBOL_GLYPH_CHARS: &str = "ABCDEFGHJKLMNPQRSTUVWXYZabdeghknqrsuy#%&@?!$";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Shape {
+    None,
+    CircleOutline,
+    SquareOutline,
+    DiamondOutline,
+    TriangleOutline,
+    CircleFilled,
+    SquareFilled,
+    DiamondFilled,
+    TriangleFilled,
+    SquareHalfFilled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mark {
+    None,
+    Dot,
+    Plus,
+    Cross,
+    BarHorizontal,
+    BarVertical,
+    Slash,
+    Backslash,
+    BarsHorizontal,
+    BarsVertical,
+    Ring,
+    Square,
+}
+
+const SHAPES: [Shape; 10] = [
+    Shape::None,
+    Shape::CircleOutline,
+    Shape::SquareOutline,
+    Shape::DiamondOutline,
+    Shape::TriangleOutline,
+    Shape::CircleFilled,
+    Shape::SquareFilled,
+    Shape::DiamondFilled,
+    Shape::TriangleFilled,
+    Shape::SquareHalfFilled,
+];
+
+const MARKS: [Mark; 12] = [
+    Mark::None,
+    Mark::Dot,
+    Mark::Plus,
+    Mark::Cross,
+    Mark::BarHorizontal,
+    Mark::BarVertical,
+    Mark::Slash,
+    Mark::Backslash,
+    Mark::BarsHorizontal,
+    Mark::BarsVertical,
+    Mark::Ring,
+    Mark::Square,
+];
+
+/// Size of the marks relative to the shapes
+const MARK_EXTENT: f32 = 0.45;
+
+/// Generated symbols must differ at least this much (Jaccard distance of their pixels) from every
+/// other symbol, otherwise they are too easily confused on a printed pattern
+pub const SYMBOL_DISSIMILARITY_MIN: f64 = 0.1;
+
+/// NOTE: All coordinates are normalized so that the symbol area goes from -1 to 1 on both axes
+fn shape_covers(shape: Shape, u: f32, v: f32, line_width: f32) -> bool {
+    let is_inside = |shape: Shape, inset: f32| -> bool {
+        let extent = 1.0 - inset;
+        match shape {
+            Shape::CircleOutline | Shape::CircleFilled => u * u + v * v <= extent * extent,
+            Shape::SquareOutline | Shape::SquareFilled | Shape::SquareHalfFilled => {
+                f32::max(u.abs(), v.abs()) <= extent
+            }
+            Shape::DiamondOutline | Shape::DiamondFilled => u.abs() + v.abs() <= extent,
+            // NOTE: The sloped sides are longer than the inset, so we inset them a bit more
+            Shape::TriangleOutline | Shape::TriangleFilled => {
+                v <= extent && u.abs() <= (v + 1.0) / 2.0 - 1.2 * inset
+            }
+            Shape::None => false,
+        }
+    };
+    match shape {
+        Shape::None => false,
+        Shape::CircleFilled
+        | Shape::SquareFilled
+        | Shape::DiamondFilled
+        | Shape::TriangleFilled => is_inside(shape, 0.0),
+        Shape::SquareHalfFilled => {
+            is_inside(shape, 0.0) && (u <= 0.0 || !is_inside(shape, line_width))
+        }
+        Shape::CircleOutline
+        | Shape::SquareOutline
+        | Shape::DiamondOutline
+        | Shape::TriangleOutline => is_inside(shape, 0.0) && !is_inside(shape, line_width),
+    }
+}
+
+fn mark_covers(mark: Mark, u: f32, v: f32, line_width: f32) -> bool {
+    let half_width = line_width / 2.0;
+    let is_in_extent = f32::max(u.abs(), v.abs()) <= MARK_EXTENT;
+    let distance_to_diagonal_falling = (u - v).abs() / std::f32::consts::SQRT_2;
+    let distance_to_diagonal_rising = (u + v).abs() / std::f32::consts::SQRT_2;
+    let bar_offset = MARK_EXTENT / 2.0;
+    match mark {
+        Mark::None => false,
+        Mark::Dot => u * u + v * v <= 0.5 * MARK_EXTENT * 0.5 * MARK_EXTENT,
+        Mark::Plus => is_in_extent && (u.abs() <= half_width || v.abs() <= half_width),
+        Mark::Cross => {
+            is_in_extent
+                && (distance_to_diagonal_falling <= half_width
+                    || distance_to_diagonal_rising <= half_width)
+        }
+        Mark::BarHorizontal => is_in_extent && v.abs() <= half_width,
+        Mark::BarVertical => is_in_extent && u.abs() <= half_width,
+        Mark::Slash => is_in_extent && distance_to_diagonal_rising <= half_width,
+        Mark::Backslash => is_in_extent && distance_to_diagonal_falling <= half_width,
+        Mark::BarsHorizontal => {
+            is_in_extent
+                && ((v - bar_offset).abs() <= half_width || (v + bar_offset).abs() <= half_width)
+        }
+        Mark::BarsVertical => {
+            is_in_extent
+                && ((u - bar_offset).abs() <= half_width || (u + bar_offset).abs() <= half_width)
+        }
+        Mark::Ring => ((u * u + v * v).sqrt() - 0.6 * MARK_EXTENT).abs() <= half_width,
+        Mark::Square => f32::max(u.abs(), v.abs()) <= 0.5 * MARK_EXTENT,
+    }
+}
+
+/// Draws a shape with a mark in it. Marks on filled shapes are drawn white.
+fn create_geometric_symbol(shape: Shape, mark: Mark, tile_size: i32) -> Bitmap {
+    let margin = i32::max(1, tile_size / 8);
+    let area_size = tile_size - 2 * margin;
+    let pixel_size = 2.0 / area_size as f32;
+    let line_width = pixel_size * i32::max(1, tile_size / 12) as f32;
+
+    let mut result = Bitmap::new_filled(tile_size as u32, tile_size as u32, PixelRGBA::white());
+    for y in 0..area_size {
+        for x in 0..area_size {
+            let u = -1.0 + (x as f32 + 0.5) * pixel_size;
+            let v = -1.0 + (y as f32 + 0.5) * pixel_size;
+            if shape_covers(shape, u, v, line_width) != mark_covers(mark, u, v, line_width) {
+                result.set(margin + x, margin + y, PixelRGBA::black());
+            }
+        }
+    }
+    result
+}
+
+/// Draws the glyph of the given char centered, optionally inside an outlined shape. Returns `None`
+/// if the font has no such glyph or it does not fit.
+fn create_glyph_symbol(font: &BitmapFont, c: char, shape: Shape, tile_size: i32) -> Option<Bitmap> {
+    let glyph_bitmap = font.glyphs.get(&(c as Codepoint))?.bitmap.as_ref()?;
+    let mut result = if shape == Shape::None {
+        Bitmap::new_filled(tile_size as u32, tile_size as u32, PixelRGBA::white())
+    } else {
+        create_geometric_symbol(shape, Mark::None, tile_size)
+    };
+
+    // NOTE: Inside a shape the glyph must not touch the outline
+    let space = if shape == Shape::None {
+        tile_size
+    } else {
+        (tile_size as f32 * 0.6) as i32
+    };
+    if glyph_bitmap.width > space || glyph_bitmap.height > space {
+        return None;
+    }
+
+    let pos = Vec2i::new(
+        block_centered_in_block(glyph_bitmap.width, tile_size),
+        block_centered_in_block(glyph_bitmap.height, tile_size),
+    );
+    for y in 0..glyph_bitmap.height {
+        for x in 0..glyph_bitmap.width {
+            if glyph_bitmap.get(x, y).a != 0 {
+                result.set(pos.x + x, pos.y + y, PixelRGBA::black());
+            }
+        }
+    }
+    Some(result)
+}
+
+/// Creates a family of black-on-white symbols of the given size from geometric shapes, marks and
+/// the glyphs of the given font. The simplest symbols come first. Candidates that look too similar
+/// to one of the given existing symbols or to an earlier generated symbol (see
+/// `SYMBOL_DISSIMILARITY_MIN`) are left out, so we get fewer symbols at small tile sizes.
+///
+/// NOTE: The existing symbols are expected to be of the given size already
+pub fn generate_symbols(
+    font: &BitmapFont,
+    tile_size: i32,
+    existing_symbols: &[Bitmap],
+) -> Vec<Bitmap> {
+    let mut candidates = Vec::new();
+
+    // Shapes and marks on their own
+    for &shape in SHAPES.iter().skip(1) {
+        candidates.push(create_geometric_symbol(shape, Mark::None, tile_size));
+    }
+    for &mark in MARKS.iter().skip(1) {
+        candidates.push(create_geometric_symbol(Shape::None, mark, tile_size));
+    }
+
+    // Glyphs on their own
+    for c in SYMBOL_GLYPH_CHARS.chars() {
+        candidates.extend(create_glyph_symbol(font, c, Shape::None, tile_size));
+    }
+
+    // Shapes with marks
+    for &shape in SHAPES.iter().skip(1) {
+        for &mark in MARKS.iter().skip(1) {
+            candidates.push(create_geometric_symbol(shape, mark, tile_size));
+        }
+    }
+
+    // Glyphs inside of outlines
+    for &shape in &[
+        Shape::CircleOutline,
+        Shape::SquareOutline,
+        Shape::DiamondOutline,
+    ] {
+        for c in SYMBOL_GLYPH_CHARS.chars() {
+            candidates.extend(create_glyph_symbol(font, c, shape, tile_size));
+        }
+    }
+
+    let mut accepted_pixels: Vec<Vec<bool>> = existing_symbols.iter().map(symbol_pixels).collect();
+    let mut result: Vec<Bitmap> = Vec::new();
+    for candidate in candidates {
+        let candidate_pixels = symbol_pixels(&candidate);
+        let is_distinct = accepted_pixels.iter().all(|pixels| {
+            symbol_pixels_dissimilarity(pixels, &candidate_pixels) >= SYMBOL_DISSIMILARITY_MIN
+        });
+        if is_distinct {
+            accepted_pixels.push(candidate_pixels);
+            result.push(candidate);
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol_assignment::symbol_dissimilarities;
+    use crate::{load_fonts, TILE_SIZE_DEFAULT, TILE_SIZE_MIN};
+
+    #[test]
+    fn generated_symbols_are_plenty_and_dissimilar() {
+        let (font, _) = load_fonts();
+        for &(tile_size, symbol_count_min) in &[(TILE_SIZE_MIN, 50), (TILE_SIZE_DEFAULT, 150)] {
+            let symbols = generate_symbols(&font, tile_size, &[]);
+            assert!(
+                symbols.len() >= symbol_count_min,
+                "Only {} symbols at tile size {}",
+                symbols.len(),
+                tile_size
+            );
+
+            let dissimilarities = symbol_dissimilarities(&symbols);
+            for (a, row) in dissimilarities.iter().enumerate() {
+                for &dissimilarity in &row[(a + 1)..] {
+                    assert!(dissimilarity >= SYMBOL_DISSIMILARITY_MIN);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn generated_symbols_differ_from_existing_symbols() {
+        let (font, _) = load_fonts();
+        let generated = generate_symbols(&font, TILE_SIZE_DEFAULT, &[]);
+        let existing = vec![generated[0].clone(), generated[5].clone()];
+
+        let symbols = generate_symbols(&font, TILE_SIZE_DEFAULT, &existing);
+        assert_eq!(symbols.len(), generated.len() - 2);
+        let existing_pixels: Vec<Vec<bool>> = existing.iter().map(symbol_pixels).collect();
+        for symbol in &symbols {
+            let pixels = symbol_pixels(symbol);
+            for other_pixels in &existing_pixels {
+                assert!(
+                    symbol_pixels_dissimilarity(other_pixels, &pixels) >= SYMBOL_DISSIMILARITY_MIN
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,116 @@
+/// Length of one skein of stranded cotton as sold by DMC and Anchor
+const SKEIN_LENGTH_METERS: f64 = 8.0;
+/// Number of strands a skein of stranded cotton can be separated into
+const SKEIN_STRAND_COUNT: i32 = 6;
+
+pub(crate) const CENTIMETERS_PER_INCH: f64 = 2.54;
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadUsageSettings {
+    /// Number of stitches per inch of the fabric, i.e. 14 for 14-count Aida
+    pub fabric_count: i32,
+    /// Number of strands that are stitched with at once
+    pub strand_count: i32,
+    /// Extra thread for tails, travelling between stitches and mistakes
+    pub waste_percent: i32,
+}
+
+impl Default for ThreadUsageSettings {
+    fn default() -> Self {
+        ThreadUsageSettings {
+            fabric_count: 14,
+            strand_count: 2,
+            waste_percent: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct ThreadUsage {
+    /// Length of six-stranded floss as it comes from the skein
+    pub length_meters: f64,
+    /// NOTE: This is always rounded up because we can only buy whole skeins
+    pub skein_count: usize,
+}
+
+impl ThreadUsageSettings {
+    /// Example: "14 count, 2 strands, 20% waste"
+    pub fn display_name(&self) -> String {
+        format!(
+            "{} count, {} strand{}, {}% waste",
+            self.fabric_count,
+            self.strand_count,
+            if self.strand_count == 1 { "" } else { "s" },
+            self.waste_percent
+        )
+    }
+
+    /// Estimates the thread needed for the given number of full cross stitches of one color.
+    /// Fractional stitches count as the corresponding fraction of a full stitch.
+    pub fn estimate(&self, stitch_count: f64) -> ThreadUsage {
+        // NOTE: A full cross stitch consists of two diagonals on the front of the fabric and
+        //       roughly two stitch-sized straight lines on the back
+        let length_per_stitch = 2.0 + 2.0 * std::f64::consts::SQRT_2;
+        self.thread_usage_for_stitched_length(length_per_stitch * stitch_count)
+    }
+
+    /// Estimates the thread needed for backstitches of one color with the given total length in
+    /// stitch cells
+    pub fn estimate_backstitch(&self, length: f64) -> ThreadUsage {
+        // NOTE: Every backstitch goes one step back on the front of the fabric and two steps
+        //       forward on the back
+        self.thread_usage_for_stitched_length(3.0 * length)
+    }
+
+    /// Takes the length of a single strand measured in stitch cells
+    fn thread_usage_for_stitched_length(&self, length: f64) -> ThreadUsage {
+        let stitch_size_meters = CENTIMETERS_PER_INCH / self.fabric_count as f64 / 100.0;
+        let strand_length_meters = length
+            * stitch_size_meters
+            * self.strand_count as f64
+            * (1.0 + self.waste_percent as f64 / 100.0);
+        let length_meters = strand_length_meters / SKEIN_STRAND_COUNT as f64;
+
+        ThreadUsage {
+            length_meters,
+            skein_count: (length_meters / SKEIN_LENGTH_METERS).ceil() as usize,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn full_stitches_are_estimated_from_their_thread_path() {
+        // NOTE: 1000 stitches * 4.83 cells * 1.81 mm * 2 strands * 1.2 / 6 strands per skein
+        let usage = ThreadUsageSettings::default().estimate(1000.0);
+        assert!((usage.length_meters - 3.504).abs() < 0.001);
+        assert_eq!(usage.skein_count, 1);
+
+        let usage = ThreadUsageSettings::default().estimate(3000.0);
+        assert!((usage.length_meters - 10.512).abs() < 0.001);
+        assert_eq!(usage.skein_count, 2);
+    }
+
+    #[test]
+    fn skeins_are_rounded_up_only_beyond_a_full_skein() {
+        // NOTE: On 127 count fabric a stitch cell is exactly 0.2 mm, so a backstitch line of
+        //       40000 cells needs 3 * 40000 * 0.2 mm * 2 strands = 48 m of strands or 8 m of floss
+        let settings = ThreadUsageSettings {
+            fabric_count: 127,
+            strand_count: 2,
+            waste_percent: 0,
+        };
+        let usage = settings.estimate_backstitch(40000.0);
+        assert_eq!(usage.length_meters, SKEIN_LENGTH_METERS);
+        assert_eq!(usage.skein_count, 1);
+
+        let usage = settings.estimate_backstitch(40001.0);
+        assert!(usage.length_meters > SKEIN_LENGTH_METERS);
+        assert_eq!(usage.skein_count, 2);
+
+        assert_eq!(settings.estimate_backstitch(0.0).skein_count, 0);
+    }
+}